@@ -12,9 +12,9 @@ CREATE TABLE IF NOT EXISTS users (
     first_name VARCHAR(50),
     last_name VARCHAR(50),
     phone VARCHAR(20),
-    role VARCHAR(20) DEFAULT 'job_seeker' CHECK (role IN ('job_seeker', 'employer', 'admin')),
+    role VARCHAR(20) DEFAULT 'job_seeker', -- references roles(name), see migrations/017_permissions.sql
     professional_role VARCHAR(100), -- For job_seekers/freelancers (e.g., "Senior Full Stack Developer")
-    company_name VARCHAR(100), -- For employers/business_owners (e.g., "ChabokSoft")
+    company_name VARCHAR(100), -- Free text; companies that post jobs live in companies, see migrations/022_companies.sql
     is_active BOOLEAN DEFAULT true,
     email_verified BOOLEAN DEFAULT false,
     created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
@@ -0,0 +1,21 @@
+-- Migration: Add password reset tokens and token revocation cutoff
+-- Description: Single-use, hashed password reset tokens and a per-user cutoff
+-- used to invalidate every JWT issued before a password reset
+
+CREATE TABLE IF NOT EXISTS password_reset_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash VARCHAR(64) UNIQUE NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    used_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_password_reset_tokens_user_id ON password_reset_tokens(user_id);
+CREATE INDEX IF NOT EXISTS idx_password_reset_tokens_expires_at ON password_reset_tokens(expires_at);
+
+-- Tokens issued before this timestamp are rejected by the auth middleware
+ALTER TABLE users ADD COLUMN IF NOT EXISTS tokens_invalid_before TIMESTAMP WITH TIME ZONE;
+
+COMMENT ON COLUMN password_reset_tokens.token_hash IS 'SHA-256 hex digest of the reset token; the raw token is only ever sent by email';
+COMMENT ON COLUMN users.tokens_invalid_before IS 'JWTs with an iat earlier than this timestamp are treated as revoked';
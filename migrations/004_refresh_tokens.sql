@@ -0,0 +1,19 @@
+-- Migration: Refresh token rotation on user_sessions
+-- Description: Each user_sessions row now holds the hash of one refresh token.
+-- Rows rotated from the same login share a family_id so that reuse of a
+-- retired token can revoke the whole chain.
+
+ALTER TABLE user_sessions ADD COLUMN IF NOT EXISTS family_id UUID;
+ALTER TABLE user_sessions ADD COLUMN IF NOT EXISTS revoked_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE user_sessions ADD COLUMN IF NOT EXISTS replaced_by INTEGER REFERENCES user_sessions(id) ON DELETE SET NULL;
+
+-- Existing rows were never used as refresh tokens; give each its own family
+UPDATE user_sessions SET family_id = md5(random()::text || id::text)::uuid WHERE family_id IS NULL;
+ALTER TABLE user_sessions ALTER COLUMN family_id SET NOT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_user_sessions_family_id ON user_sessions(family_id);
+
+COMMENT ON COLUMN user_sessions.session_token IS 'SHA-256 hex digest of the refresh token';
+COMMENT ON COLUMN user_sessions.family_id IS 'Shared by every refresh token rotated from the same login';
+COMMENT ON COLUMN user_sessions.revoked_at IS 'Set when the token is rotated, logged out or revoked';
+COMMENT ON COLUMN user_sessions.replaced_by IS 'Session row issued when this refresh token was rotated';
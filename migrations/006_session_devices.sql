@@ -0,0 +1,13 @@
+-- Migration: Per-device session metadata
+-- Description: Track which device each session belongs to so users can review
+-- and revoke their sessions individually
+
+ALTER TABLE user_sessions ADD COLUMN IF NOT EXISTS user_agent VARCHAR(512);
+ALTER TABLE user_sessions ADD COLUMN IF NOT EXISTS ip_address VARCHAR(45);
+ALTER TABLE user_sessions ADD COLUMN IF NOT EXISTS device_label VARCHAR(100);
+ALTER TABLE user_sessions ADD COLUMN IF NOT EXISTS last_seen_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP;
+
+CREATE INDEX IF NOT EXISTS idx_user_sessions_active ON user_sessions(user_id) WHERE revoked_at IS NULL;
+
+COMMENT ON COLUMN user_sessions.device_label IS 'User-chosen name for the device, e.g. "Work laptop"';
+COMMENT ON COLUMN user_sessions.last_seen_at IS 'Last time the session was used to refresh or authenticate a request';
@@ -0,0 +1,13 @@
+-- Migration: Durable token revocation
+-- Description: Revoked access tokens are recorded by their jti claim until they
+-- would have expired anyway. Replicas learn about new revocations through
+-- NOTIFY on the auth_revocations channel.
+
+CREATE TABLE IF NOT EXISTS revoked_tokens (
+    jti UUID PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    revoked_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_revoked_tokens_expires_at ON revoked_tokens(expires_at);
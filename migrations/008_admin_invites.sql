@@ -0,0 +1,17 @@
+-- Migration: Invite-only admin accounts
+-- Description: Admin accounts can no longer be self-registered. Existing admins
+-- invite new ones by email; the invite token is stored hashed and single-use.
+
+CREATE TABLE IF NOT EXISTS admin_invites (
+    id SERIAL PRIMARY KEY,
+    email VARCHAR(100) NOT NULL,
+    token_hash VARCHAR(64) UNIQUE NOT NULL,
+    invited_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    accepted_at TIMESTAMP WITH TIME ZONE,
+    accepted_user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    revoked_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_admin_invites_email ON admin_invites(email);
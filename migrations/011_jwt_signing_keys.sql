@@ -0,0 +1,15 @@
+-- Migration: Asymmetric JWT signing keys
+-- Description: Access tokens are signed with RS256 or EdDSA keys identified by kid.
+-- A key starts signing at activates_at, which is set a little in the future on
+-- rotation so every replica and JWKS consumer sees the key before it is used.
+-- The previous key stops signing then but keeps validating for a grace period.
+
+CREATE TABLE IF NOT EXISTS jwt_signing_keys (
+    kid VARCHAR(64) PRIMARY KEY,
+    algorithm VARCHAR(10) NOT NULL, -- 'RS256' or 'EdDSA'
+    private_key TEXT NOT NULL, -- PEM (PKCS#8, or PKCS#1 for RSA)
+    activates_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_jwt_signing_keys_activates_at ON jwt_signing_keys(activates_at);
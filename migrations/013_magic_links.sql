@@ -0,0 +1,30 @@
+-- Migration: Passwordless magic-link login
+-- Description: Hashed, single-use login tokens sent by email, plus a log of
+-- link requests used to rate limit them per email address and per IP
+
+CREATE TABLE IF NOT EXISTS magic_link_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    email VARCHAR(100) NOT NULL,
+    token_hash VARCHAR(64) UNIQUE NOT NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    used_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_magic_link_tokens_user_id ON magic_link_tokens(user_id);
+CREATE INDEX IF NOT EXISTS idx_magic_link_tokens_expires_at ON magic_link_tokens(expires_at);
+
+CREATE TABLE IF NOT EXISTS magic_link_requests (
+    id BIGSERIAL PRIMARY KEY,
+    email VARCHAR(255) NOT NULL,
+    ip_address VARCHAR(45),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_magic_link_requests_email ON magic_link_requests(email, created_at);
+CREATE INDEX IF NOT EXISTS idx_magic_link_requests_ip ON magic_link_requests(ip_address, created_at);
+
+COMMENT ON COLUMN magic_link_tokens.token_hash IS 'SHA-256 hex digest of the login token; the raw token is only ever sent by email';
+COMMENT ON COLUMN magic_link_tokens.email IS 'Address the link was sent to; the link stops working if the user changes their email';
+COMMENT ON COLUMN magic_link_requests.email IS 'Lower-cased address as requested, whether or not an account exists';
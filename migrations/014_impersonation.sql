@@ -0,0 +1,37 @@
+-- Migration: Admin impersonation
+-- Description: Time-limited sessions in which an admin acts as another user.
+-- Access tokens issued for them carry an `act` claim naming the admin and are
+-- only valid while the session here is open. Every start, stop and request
+-- made during the session is kept in impersonation_events.
+
+CREATE TABLE IF NOT EXISTS impersonation_sessions (
+    id UUID PRIMARY KEY, -- `sid` claim of the impersonation token
+    admin_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    reason TEXT NOT NULL,
+    ip_address VARCHAR(45),
+    started_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    last_seen_at TIMESTAMP WITH TIME ZONE,
+    ended_at TIMESTAMP WITH TIME ZONE,
+    ended_by INTEGER REFERENCES users(id) ON DELETE SET NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_impersonation_sessions_admin_id ON impersonation_sessions(admin_id, started_at DESC);
+CREATE INDEX IF NOT EXISTS idx_impersonation_sessions_user_id ON impersonation_sessions(user_id, started_at DESC);
+
+CREATE TABLE IF NOT EXISTS impersonation_events (
+    id BIGSERIAL PRIMARY KEY,
+    session_id UUID NOT NULL REFERENCES impersonation_sessions(id) ON DELETE CASCADE,
+    event_type VARCHAR(20) NOT NULL, -- 'start', 'stop', 'request' or 'blocked'
+    method VARCHAR(10),
+    path TEXT,
+    status_code SMALLINT,
+    ip_address VARCHAR(45),
+    details TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_impersonation_events_session_id ON impersonation_events(session_id, created_at);
+
+COMMENT ON COLUMN impersonation_sessions.ended_by IS 'Admin who ended the session early; NULL when it expired or ended from the impersonation token';
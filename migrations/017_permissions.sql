@@ -0,0 +1,53 @@
+-- Migration: Roles and permissions
+-- Description: Roles are rows mapped to sets of permissions instead of a CHECK
+-- constraint on users.role, so new roles (recruiter, moderator, support, ...) need
+-- no schema change. The permission names are the ones the code checks. Every change
+-- to a role's permissions bumps permissions_version; access tokens carry the version
+-- they were issued with (`pv` claim) and stop working once it is out of date.
+
+CREATE TABLE IF NOT EXISTS permissions (
+    name VARCHAR(64) PRIMARY KEY,
+    description TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS roles (
+    name VARCHAR(20) PRIMARY KEY,
+    description TEXT,
+    self_assignable BOOLEAN NOT NULL DEFAULT false, -- can be picked at registration
+    builtin BOOLEAN NOT NULL DEFAULT false, -- referenced by the code, cannot be deleted
+    permissions_version INTEGER NOT NULL DEFAULT 1,
+    created_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS role_permissions (
+    role VARCHAR(20) NOT NULL REFERENCES roles(name) ON DELETE CASCADE ON UPDATE CASCADE,
+    permission VARCHAR(64) NOT NULL REFERENCES permissions(name) ON DELETE CASCADE,
+    PRIMARY KEY (role, permission)
+);
+
+INSERT INTO permissions (name, description) VALUES
+    ('invites:manage', 'Invite new admins and revoke pending invites'),
+    ('roles:manage', 'Create roles and change which permissions they grant'),
+    ('roles:assign', 'Change the role of a user'),
+    ('users:unlock', 'Clear login lockouts'),
+    ('users:suspend', 'Suspend accounts and lift suspensions'),
+    ('users:impersonate', 'Act as another user for support, and end impersonations'),
+    ('audit:read', 'Review security events and impersonation audit trails'),
+    ('signing_keys:rotate', 'Rotate the JWT signing key'),
+    ('maintenance:run', 'Run cleanup jobs')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO roles (name, description, self_assignable, builtin) VALUES
+    ('job_seeker', 'Looks for and applies to jobs', true, true),
+    ('employer', 'Posts jobs and reviews applications', true, true),
+    ('admin', 'Full administrative access', false, true)
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO role_permissions (role, permission)
+SELECT 'admin', name FROM permissions
+ON CONFLICT DO NOTHING;
+
+-- users.role now points at a row instead of a fixed list
+ALTER TABLE users DROP CONSTRAINT IF EXISTS users_role_check;
+ALTER TABLE users ADD CONSTRAINT users_role_fkey FOREIGN KEY (role) REFERENCES roles(name) ON UPDATE CASCADE;
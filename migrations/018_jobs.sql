@@ -0,0 +1,43 @@
+-- Migration: Job postings
+-- Description: Employers write postings as drafts, publish them and close them.
+-- Published postings expire on their own at expires_at. The allowed status
+-- transitions are enforced in code; the CHECK constraints only keep values sane.
+
+CREATE TABLE IF NOT EXISTS jobs (
+    id SERIAL PRIMARY KEY,
+    employer_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    title VARCHAR(200) NOT NULL,
+    description TEXT NOT NULL,
+    employment_type VARCHAR(20) NOT NULL CHECK (employment_type IN ('full_time', 'part_time', 'contract', 'temporary', 'internship', 'freelance')),
+    seniority VARCHAR(20) NOT NULL CHECK (seniority IN ('intern', 'junior', 'mid', 'senior', 'lead', 'executive')),
+    location VARCHAR(200), -- optional for fully remote postings
+    remote_policy VARCHAR(20) NOT NULL CHECK (remote_policy IN ('on_site', 'hybrid', 'remote')),
+    salary_min INTEGER CHECK (salary_min >= 0),
+    salary_max INTEGER CHECK (salary_max >= salary_min),
+    salary_currency CHAR(3), -- ISO 4217, required when a salary is given
+    skills TEXT[] NOT NULL DEFAULT '{}',
+    status VARCHAR(20) NOT NULL DEFAULT 'draft' CHECK (status IN ('draft', 'published', 'closed', 'expired')),
+    published_at TIMESTAMP WITH TIME ZONE,
+    closed_at TIMESTAMP WITH TIME ZONE,
+    expires_at TIMESTAMP WITH TIME ZONE, -- set on publish when the employer gave none
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_jobs_employer_id ON jobs(employer_id);
+CREATE INDEX IF NOT EXISTS idx_jobs_published ON jobs(published_at DESC) WHERE status = 'published';
+CREATE INDEX IF NOT EXISTS idx_jobs_expires_at ON jobs(expires_at) WHERE status = 'published';
+CREATE INDEX IF NOT EXISTS idx_jobs_skills ON jobs USING GIN (skills);
+
+-- Posting jobs is a permission like any other, held by employers (and admins, who hold them all)
+INSERT INTO permissions (name, description) VALUES
+    ('jobs:post', 'Create, edit, publish and close your own job postings')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO role_permissions (role, permission) VALUES
+    ('employer', 'jobs:post'),
+    ('admin', 'jobs:post')
+ON CONFLICT DO NOTHING;
+
+UPDATE roles SET permissions_version = permissions_version + 1, updated_at = CURRENT_TIMESTAMP
+WHERE name IN ('employer', 'admin');
@@ -0,0 +1,54 @@
+-- Migration: Full-text job search
+-- Description: A weighted tsvector per posting: title (A) above the employer's
+-- company name (B) above the description (C). The company name lives on users, so
+-- the vector is kept up to date by triggers on both tables instead of a generated column.
+
+ALTER TABLE jobs ADD COLUMN IF NOT EXISTS search_vector TSVECTOR;
+
+CREATE OR REPLACE FUNCTION job_search_vector(title TEXT, company TEXT, description TEXT)
+RETURNS TSVECTOR AS $$
+    SELECT setweight(to_tsvector('english', COALESCE(title, '')), 'A')
+        || setweight(to_tsvector('english', COALESCE(company, '')), 'B')
+        || setweight(to_tsvector('english', COALESCE(description, '')), 'C')
+$$ LANGUAGE SQL IMMUTABLE;
+
+CREATE OR REPLACE FUNCTION update_job_search_vector()
+RETURNS TRIGGER AS $$
+BEGIN
+    NEW.search_vector := job_search_vector(
+        NEW.title,
+        (SELECT company_name FROM users WHERE id = NEW.employer_id),
+        NEW.description
+    );
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS update_job_search_vector ON jobs;
+CREATE TRIGGER update_job_search_vector
+    BEFORE INSERT OR UPDATE OF title, description, employer_id ON jobs
+    FOR EACH ROW
+    EXECUTE FUNCTION update_job_search_vector();
+
+-- Renaming a company re-indexes all of its postings
+CREATE OR REPLACE FUNCTION update_company_job_search_vectors()
+RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE jobs SET search_vector = job_search_vector(title, NEW.company_name, description)
+    WHERE employer_id = NEW.id;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS update_company_job_search_vectors ON users;
+CREATE TRIGGER update_company_job_search_vectors
+    AFTER UPDATE OF company_name ON users
+    FOR EACH ROW
+    WHEN (OLD.company_name IS DISTINCT FROM NEW.company_name)
+    EXECUTE FUNCTION update_company_job_search_vectors();
+
+UPDATE jobs j SET search_vector = job_search_vector(j.title, u.company_name, j.description)
+FROM users u
+WHERE u.id = j.employer_id;
+
+CREATE INDEX IF NOT EXISTS idx_jobs_search_vector ON jobs USING GIN (search_vector);
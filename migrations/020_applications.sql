@@ -0,0 +1,34 @@
+-- Migration: Job applications
+-- Description: A job seeker applies once per posting with a cover letter and a
+-- link to their resume. The employer moves the application through review; the
+-- applicant can withdraw it. Allowed status transitions are enforced in code.
+
+CREATE TABLE IF NOT EXISTS applications (
+    id SERIAL PRIMARY KEY,
+    job_id INTEGER NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+    applicant_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    cover_letter TEXT,
+    resume_url VARCHAR(500) NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'submitted' CHECK (status IN ('submitted', 'reviewing', 'shortlisted', 'rejected', 'hired', 'withdrawn')),
+    status_changed_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (job_id, applicant_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_applications_applicant_id ON applications(applicant_id);
+CREATE INDEX IF NOT EXISTS idx_applications_job_id_status ON applications(job_id, status);
+
+INSERT INTO permissions (name, description) VALUES
+    ('jobs:apply', 'Apply to published jobs and track your own applications'),
+    ('applications:read_any', 'Read applications to any job, not just your own postings')
+ON CONFLICT (name) DO NOTHING;
+
+INSERT INTO role_permissions (role, permission) VALUES
+    ('job_seeker', 'jobs:apply'),
+    ('admin', 'jobs:apply'),
+    ('admin', 'applications:read_any')
+ON CONFLICT DO NOTHING;
+
+UPDATE roles SET permissions_version = permissions_version + 1, updated_at = CURRENT_TIMESTAMP
+WHERE name IN ('job_seeker', 'admin');
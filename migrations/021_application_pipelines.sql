@@ -0,0 +1,100 @@
+-- Migration: Applicant tracking pipelines
+-- Description: Every job gets its own list of stages, copied from a default
+-- template when the job is created, which the employer can rename, reorder and
+-- extend. An application sits in one stage; the stage's kind decides whether it
+-- is still active, hired or rejected. Every move between stages is recorded in an
+-- append-only history with who made it and why.
+
+CREATE TABLE IF NOT EXISTS pipeline_template_stages (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(50) UNIQUE NOT NULL,
+    kind VARCHAR(20) NOT NULL CHECK (kind IN ('active', 'hired', 'rejected')),
+    position INTEGER NOT NULL
+);
+
+INSERT INTO pipeline_template_stages (name, kind, position) VALUES
+    ('screening', 'active', 1),
+    ('interview', 'active', 2),
+    ('offer', 'active', 3),
+    ('hired', 'hired', 4),
+    ('rejected', 'rejected', 5)
+ON CONFLICT (name) DO NOTHING;
+
+CREATE TABLE IF NOT EXISTS pipeline_stages (
+    id SERIAL PRIMARY KEY,
+    job_id INTEGER NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+    name VARCHAR(50) NOT NULL,
+    kind VARCHAR(20) NOT NULL CHECK (kind IN ('active', 'hired', 'rejected')),
+    position INTEGER NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (job_id, name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_pipeline_stages_job_id ON pipeline_stages(job_id, position);
+
+INSERT INTO pipeline_stages (job_id, name, kind, position)
+SELECT j.id, t.name, t.kind, t.position
+FROM jobs j
+CROSS JOIN pipeline_template_stages t
+ON CONFLICT (job_id, name) DO NOTHING;
+
+-- Existing applications land in the stage closest to their old status
+ALTER TABLE applications ADD COLUMN IF NOT EXISTS stage_id INTEGER REFERENCES pipeline_stages(id);
+
+UPDATE applications a SET stage_id = s.id
+FROM pipeline_stages s
+WHERE s.job_id = a.job_id AND s.name = CASE a.status
+    WHEN 'shortlisted' THEN 'interview'
+    WHEN 'hired' THEN 'hired'
+    WHEN 'rejected' THEN 'rejected'
+    ELSE 'screening'
+END;
+
+ALTER TABLE applications ALTER COLUMN stage_id SET NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_applications_stage_id ON applications(stage_id);
+
+-- The stage now says how far along an application is; status only says how it ended
+ALTER TABLE applications DROP CONSTRAINT IF EXISTS applications_status_check;
+UPDATE applications SET status = 'active' WHERE status IN ('submitted', 'reviewing', 'shortlisted');
+ALTER TABLE applications ALTER COLUMN status SET DEFAULT 'active';
+ALTER TABLE applications ADD CONSTRAINT applications_status_check CHECK (status IN ('active', 'hired', 'rejected', 'withdrawn'));
+
+-- Stage ids and the mover are plain values rather than foreign keys, so deleting
+-- a stage or a user never rewrites history; the stage names are kept alongside
+CREATE TABLE IF NOT EXISTS application_stage_history (
+    id BIGSERIAL PRIMARY KEY,
+    application_id INTEGER NOT NULL REFERENCES applications(id) ON DELETE CASCADE,
+    from_stage_id INTEGER, -- NULL for the stage an application started in
+    from_stage_name VARCHAR(50),
+    to_stage_id INTEGER NOT NULL,
+    to_stage_name VARCHAR(50) NOT NULL,
+    moved_by INTEGER,
+    reason TEXT,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Existing applications start their history in the stage they were placed in
+INSERT INTO application_stage_history (application_id, to_stage_id, to_stage_name, reason, created_at)
+SELECT a.id, a.stage_id, s.name, 'Placed in the pipeline when pipelines were introduced', a.created_at
+FROM applications a
+JOIN pipeline_stages s ON s.id = a.stage_id
+WHERE NOT EXISTS (SELECT 1 FROM application_stage_history h WHERE h.application_id = a.id);
+
+CREATE INDEX IF NOT EXISTS idx_application_stage_history_application_id ON application_stage_history(application_id, created_at);
+
+CREATE OR REPLACE FUNCTION prevent_stage_history_changes()
+RETURNS TRIGGER AS $$
+BEGIN
+    -- Rows still go away together with their application (e.g. account deletion)
+    IF TG_OP = 'DELETE' AND NOT EXISTS (SELECT 1 FROM applications WHERE id = OLD.application_id) THEN
+        RETURN OLD;
+    END IF;
+    RAISE EXCEPTION 'application_stage_history is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS prevent_stage_history_changes ON application_stage_history;
+CREATE TRIGGER prevent_stage_history_changes
+    BEFORE UPDATE OR DELETE ON application_stage_history
+    FOR EACH ROW
+    EXECUTE FUNCTION prevent_stage_history_changes();
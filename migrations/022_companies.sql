@@ -0,0 +1,129 @@
+-- Migration: Companies and employer teams
+-- Description: Employers used to be individual users with a free-text company_name.
+-- Companies are now records of their own with a profile and members, each an owner,
+-- recruiter or viewer, and job postings belong to a company. employer_id stays on
+-- jobs as the member who posted it. Every existing employer gets a company of their
+-- own; accounts are never grouped by the free-text name, since anyone can type any
+-- company name. Owners invite their teammates afterwards.
+
+CREATE TABLE IF NOT EXISTS companies (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(100) NOT NULL,
+    description TEXT,
+    logo_url VARCHAR(500),
+    website VARCHAR(500),
+    industry VARCHAR(100),
+    size VARCHAR(20) CHECK (size IN ('1-10', '11-50', '51-200', '201-500', '501-1000', '1001-5000', '5001+')),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_companies_name ON companies(LOWER(name));
+
+CREATE TABLE IF NOT EXISTS company_members (
+    company_id INTEGER NOT NULL REFERENCES companies(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    role VARCHAR(20) NOT NULL CHECK (role IN ('owner', 'recruiter', 'viewer')),
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (company_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_company_members_user_id ON company_members(user_id);
+
+-- API keys can belong to a company instead of a single user, for employer
+-- integrations. user_id stays the member who created the key, and the key acts as
+-- them; it only reaches its own company and stops working once they leave it.
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS company_id INTEGER REFERENCES companies(id) ON DELETE CASCADE;
+CREATE INDEX IF NOT EXISTS idx_api_keys_company_id ON api_keys(company_id) WHERE company_id IS NOT NULL;
+COMMENT ON COLUMN api_keys.company_id IS 'Company the key belongs to and is limited to; NULL for personal keys';
+
+-- Owners invite teammates by email; they join once they accept with the emailed
+-- token while signed in to the account with that address
+CREATE TABLE IF NOT EXISTS company_invites (
+    id SERIAL PRIMARY KEY,
+    company_id INTEGER NOT NULL REFERENCES companies(id) ON DELETE CASCADE,
+    email VARCHAR(100) NOT NULL,
+    role VARCHAR(20) NOT NULL CHECK (role IN ('owner', 'recruiter', 'viewer')),
+    token_hash VARCHAR(64) UNIQUE NOT NULL,
+    invited_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    accepted_at TIMESTAMP WITH TIME ZONE,
+    accepted_user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    revoked_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_company_invites_company_id ON company_invites(company_id);
+
+-- One company per existing employer, named after their company_name or, failing
+-- that, their username, with the employer as its owner. Accounts that posted jobs
+-- are included whatever their role, so every job gets a company.
+ALTER TABLE companies ADD COLUMN fold_key TEXT;
+
+INSERT INTO companies (name, fold_key, created_at)
+SELECT COALESCE(NULLIF(TRIM(u.company_name), ''), u.username), 'user:' || u.id, u.created_at
+FROM users u
+WHERE (NULLIF(TRIM(u.company_name), '') IS NOT NULL AND u.role <> 'job_seeker')
+   OR EXISTS (SELECT 1 FROM jobs WHERE employer_id = u.id);
+
+INSERT INTO company_members (company_id, user_id, role, created_at)
+SELECT c.id, u.id, 'owner', u.created_at
+FROM users u
+JOIN companies c ON c.fold_key = 'user:' || u.id
+ON CONFLICT (company_id, user_id) DO NOTHING;
+
+-- Postings move to their employer's company and outlive the account that posted them
+ALTER TABLE jobs ADD COLUMN IF NOT EXISTS company_id INTEGER REFERENCES companies(id) ON DELETE CASCADE;
+
+UPDATE jobs j SET company_id = m.company_id
+FROM company_members m
+WHERE m.user_id = j.employer_id AND j.company_id IS NULL;
+
+ALTER TABLE jobs ALTER COLUMN company_id SET NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_jobs_company_id ON jobs(company_id);
+
+ALTER TABLE companies DROP COLUMN fold_key;
+
+ALTER TABLE jobs ALTER COLUMN employer_id DROP NOT NULL;
+ALTER TABLE jobs DROP CONSTRAINT IF EXISTS jobs_employer_id_fkey;
+ALTER TABLE jobs ADD CONSTRAINT jobs_employer_id_fkey FOREIGN KEY (employer_id) REFERENCES users(id) ON DELETE SET NULL;
+
+-- The search vector now takes the company name from companies
+CREATE OR REPLACE FUNCTION update_job_search_vector()
+RETURNS TRIGGER AS $$
+BEGIN
+    NEW.search_vector := job_search_vector(
+        NEW.title,
+        (SELECT name FROM companies WHERE id = NEW.company_id),
+        NEW.description
+    );
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS update_job_search_vector ON jobs;
+CREATE TRIGGER update_job_search_vector
+    BEFORE INSERT OR UPDATE OF title, description, company_id ON jobs
+    FOR EACH ROW
+    EXECUTE FUNCTION update_job_search_vector();
+
+DROP TRIGGER IF EXISTS update_company_job_search_vectors ON users;
+
+CREATE OR REPLACE FUNCTION update_company_job_search_vectors()
+RETURNS TRIGGER AS $$
+BEGIN
+    UPDATE jobs SET search_vector = job_search_vector(title, NEW.name, description)
+    WHERE company_id = NEW.id;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER update_company_job_search_vectors
+    AFTER UPDATE OF name ON companies
+    FOR EACH ROW
+    WHEN (OLD.name IS DISTINCT FROM NEW.name)
+    EXECUTE FUNCTION update_company_job_search_vectors();
+
+UPDATE jobs j SET search_vector = job_search_vector(j.title, c.name, j.description)
+FROM companies c
+WHERE c.id = j.company_id;
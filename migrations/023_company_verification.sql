@@ -0,0 +1,29 @@
+-- Migration: Company domain verification
+-- Description: Anyone can create a company under any name, so companies can prove
+-- they own the domain of their website, either by confirming a link sent to an
+-- address on that domain or by publishing a DNS TXT record. A verified company
+-- keeps its badge until its website moves to another domain.
+
+ALTER TABLE companies ADD COLUMN IF NOT EXISTS verified_domain VARCHAR(253);
+ALTER TABLE companies ADD COLUMN IF NOT EXISTS verified_at TIMESTAMP WITH TIME ZONE;
+
+-- One row per verification attempt. Email attempts store a hash of the emailed
+-- token; DNS attempts store the token itself, as it is published in DNS anyway.
+CREATE TABLE IF NOT EXISTS company_domain_verifications (
+    id SERIAL PRIMARY KEY,
+    company_id INTEGER NOT NULL REFERENCES companies(id) ON DELETE CASCADE,
+    domain VARCHAR(253) NOT NULL,
+    method VARCHAR(10) NOT NULL CHECK (method IN ('email', 'dns')),
+    email VARCHAR(100),
+    token VARCHAR(64) NOT NULL,
+    requested_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+    used_at TIMESTAMP WITH TIME ZONE,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CHECK ((method = 'email') = (email IS NOT NULL))
+);
+
+CREATE INDEX IF NOT EXISTS idx_company_domain_verifications_company_id
+    ON company_domain_verifications(company_id, created_at DESC);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_company_domain_verifications_email_token
+    ON company_domain_verifications(token) WHERE method = 'email';
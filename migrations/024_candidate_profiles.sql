@@ -0,0 +1,98 @@
+-- Migration: Structured candidate profiles
+-- Description: Career data beyond users.professional_role, kept as ordered child
+-- records of the user: work experience, education, certifications, spoken
+-- languages, skills with a proficiency level, and portfolio links. position is
+-- the order the candidate chose within each section.
+
+CREATE TABLE IF NOT EXISTS candidate_experiences (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    title VARCHAR(100) NOT NULL,
+    company VARCHAR(100) NOT NULL,
+    location VARCHAR(100),
+    employment_type VARCHAR(20) CHECK (employment_type IN ('full_time', 'part_time', 'contract', 'temporary', 'internship', 'freelance')),
+    start_date DATE NOT NULL,
+    end_date DATE, -- NULL while it is the current position
+    description TEXT,
+    position INTEGER NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CHECK (end_date IS NULL OR end_date >= start_date)
+);
+
+CREATE INDEX IF NOT EXISTS idx_candidate_experiences_user_id ON candidate_experiences(user_id, position);
+
+CREATE TABLE IF NOT EXISTS candidate_education (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    institution VARCHAR(150) NOT NULL,
+    degree VARCHAR(100),
+    field_of_study VARCHAR(100),
+    start_date DATE,
+    end_date DATE,
+    grade VARCHAR(50),
+    description TEXT,
+    position INTEGER NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CHECK (end_date IS NULL OR start_date IS NULL OR end_date >= start_date)
+);
+
+CREATE INDEX IF NOT EXISTS idx_candidate_education_user_id ON candidate_education(user_id, position);
+
+CREATE TABLE IF NOT EXISTS candidate_certifications (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(150) NOT NULL,
+    issuer VARCHAR(150),
+    issued_on DATE,
+    expires_on DATE,
+    credential_id VARCHAR(100),
+    credential_url VARCHAR(500),
+    position INTEGER NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CHECK (expires_on IS NULL OR issued_on IS NULL OR expires_on >= issued_on)
+);
+
+CREATE INDEX IF NOT EXISTS idx_candidate_certifications_user_id ON candidate_certifications(user_id, position);
+
+CREATE TABLE IF NOT EXISTS candidate_languages (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    language VARCHAR(50) NOT NULL,
+    proficiency VARCHAR(30) NOT NULL CHECK (proficiency IN ('elementary', 'limited_working', 'professional_working', 'full_professional', 'native')),
+    position INTEGER NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_candidate_languages_user_language ON candidate_languages(user_id, LOWER(language));
+
+-- Skill names are stored lowercase, like the skills of job postings, so the two can be compared
+CREATE TABLE IF NOT EXISTS candidate_skills (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(50) NOT NULL,
+    level VARCHAR(20) NOT NULL CHECK (level IN ('beginner', 'intermediate', 'advanced', 'expert')),
+    years_of_experience SMALLINT CHECK (years_of_experience BETWEEN 0 AND 60),
+    position INTEGER NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (user_id, name)
+);
+
+CREATE INDEX IF NOT EXISTS idx_candidate_skills_name ON candidate_skills(name);
+
+CREATE TABLE IF NOT EXISTS candidate_links (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    kind VARCHAR(20) NOT NULL CHECK (kind IN ('portfolio', 'github', 'linkedin', 'website', 'other')),
+    url VARCHAR(500) NOT NULL,
+    label VARCHAR(100),
+    position INTEGER NOT NULL,
+    created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_candidate_links_user_id ON candidate_links(user_id, position);
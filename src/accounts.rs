@@ -0,0 +1,193 @@
+use actix_web::HttpResponse;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::time::Duration as StdDuration;
+use uuid::Uuid;
+
+use crate::api_keys;
+use crate::audit::{self, SecurityEventType};
+use crate::cache::CacheManager;
+use crate::models::{ApiResponse, User};
+use crate::revocation::RevocationEvent;
+
+/// Why an account can or cannot be used, derived from the columns added in migration 016
+#[derive(Debug, Clone)]
+pub enum AccountStatus {
+    Active,
+    /// Switched off by its owner; signing in through `/auth/reactivate` restores it
+    Deactivated { deletion_scheduled_at: Option<DateTime<Utc>> },
+    /// Switched off by an admin; `until: None` means until an admin lifts it
+    Suspended { reason: String, until: Option<DateTime<Utc>> },
+}
+
+#[derive(sqlx::FromRow)]
+struct StatusRow {
+    is_active: bool,
+    deactivated_at: Option<DateTime<Utc>>,
+    deletion_scheduled_at: Option<DateTime<Utc>>,
+    suspended_at: Option<DateTime<Utc>>,
+    suspended_until: Option<DateTime<Utc>>,
+    suspension_reason: Option<String>,
+}
+
+/// Current status of an account. A suspension whose end date has passed is lifted first.
+pub async fn status(pool: &PgPool, cache: &CacheManager, user_id: i32) -> Result<Option<AccountStatus>, sqlx::Error> {
+    lift_expired_suspensions(pool, cache, Some(user_id)).await?;
+
+    let row = sqlx::query_as::<_, StatusRow>(
+        "SELECT is_active, deactivated_at, deletion_scheduled_at, suspended_at, suspended_until, suspension_reason FROM users WHERE id = $1"
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(|row| {
+        if row.suspended_at.is_some() {
+            AccountStatus::Suspended { reason: row.suspension_reason.unwrap_or_default(), until: row.suspended_until }
+        } else if row.deactivated_at.is_some() || !row.is_active {
+            AccountStatus::Deactivated { deletion_scheduled_at: row.deletion_scheduled_at }
+        } else {
+            AccountStatus::Active
+        }
+    }))
+}
+
+/// 403 explaining why an account that proved who it is still cannot sign in
+pub fn refused(status: &AccountStatus) -> HttpResponse {
+    let message = match status {
+        AccountStatus::Active => "Account is active".to_string(),
+        AccountStatus::Deactivated { deletion_scheduled_at: Some(deletion) } => format!(
+            "Account is deactivated and will be deleted on {}. Sign in through /api/v1/auth/reactivate to keep it",
+            deletion.to_rfc3339()
+        ),
+        AccountStatus::Deactivated { deletion_scheduled_at: None } => {
+            "Account is deactivated. Sign in through /api/v1/auth/reactivate to restore it".to_string()
+        }
+        AccountStatus::Suspended { reason, until: Some(until) } => {
+            format!("Account is suspended until {}: {reason}", until.to_rfc3339())
+        }
+        AccountStatus::Suspended { reason, until: None } => format!("Account is suspended: {reason}"),
+    };
+
+    HttpResponse::Forbidden().json(ApiResponse::<()>::error(&message))
+}
+
+/// Let a user whose credentials checked out sign in only if the account is usable.
+/// Marks `user` active when an expired suspension was just lifted.
+pub async fn ensure_can_sign_in(pool: &PgPool, cache: &CacheManager, user: &mut User) -> Result<(), HttpResponse> {
+    if user.is_active {
+        return Ok(());
+    }
+
+    match status(pool, cache, user.id).await {
+        Ok(Some(AccountStatus::Active)) => {
+            user.is_active = true;
+            Ok(())
+        }
+        Ok(Some(status)) => Err(refused(&status)),
+        Ok(None) => Err(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
+            "Invalid credentials"
+        ))),
+        Err(_) => Err(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            "Database error"
+        ))),
+    }
+}
+
+/// Revoke every token, session, API key and impersonation of a user inside `tx`.
+/// Publish the returned events once the transaction has committed.
+pub async fn revoke_all_access(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: i32,
+) -> Result<Vec<RevocationEvent>, sqlx::Error> {
+    sqlx::query("UPDATE users SET tokens_invalid_before = CURRENT_TIMESTAMP WHERE id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let sessions: Vec<Uuid> = sqlx::query_scalar(
+        "UPDATE user_sessions SET revoked_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND revoked_at IS NULL RETURNING family_id"
+    )
+    .bind(user_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    let impersonations: Vec<Uuid> = sqlx::query_scalar(
+        "UPDATE impersonation_sessions SET ended_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND ended_at IS NULL RETURNING id"
+    )
+    .bind(user_id)
+    .fetch_all(&mut *tx)
+    .await?;
+
+    api_keys::revoke_all(tx, user_id).await?;
+
+    let mut events = vec![RevocationEvent::User(user_id)];
+    events.extend(sessions.into_iter().chain(impersonations).map(RevocationEvent::Session));
+    Ok(events)
+}
+
+/// Reactivate accounts whose suspension has run out, or just `user_id` when given
+pub async fn lift_expired_suspensions(
+    pool: &PgPool,
+    cache: &CacheManager,
+    user_id: Option<i32>,
+) -> Result<(), sqlx::Error> {
+    let lifted: Vec<i32> = sqlx::query_scalar(
+        r#"
+        UPDATE users
+        SET is_active = (deactivated_at IS NULL), suspended_at = NULL, suspended_until = NULL,
+            suspension_reason = NULL, suspended_by = NULL
+        WHERE suspended_until <= CURRENT_TIMESTAMP AND ($1::INTEGER IS NULL OR id = $1)
+        RETURNING id
+        "#
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    for user_id in lifted {
+        cache.invalidate_user(user_id).await;
+        audit::record(pool, SecurityEventType::AccountUnsuspended, Some(user_id), None, None, "Suspension ended").await;
+    }
+    Ok(())
+}
+
+/// Hard-delete accounts whose deletion grace period is over. Everything that
+/// references a user is removed or detached by the `ON DELETE` rules.
+pub async fn purge_scheduled_deletions(pool: &PgPool) -> Result<(), sqlx::Error> {
+    let deleted: Vec<(i32, String)> = sqlx::query_as(
+        "DELETE FROM users WHERE deletion_scheduled_at <= CURRENT_TIMESTAMP RETURNING id, username"
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for (user_id, username) in deleted {
+        // The row is gone, so the id goes into the details rather than user_id
+        audit::record(
+            pool,
+            SecurityEventType::AccountDeleted,
+            None,
+            None,
+            None,
+            &format!("Account {user_id} ({username}) deleted after its grace period"),
+        )
+        .await;
+    }
+    Ok(())
+}
+
+/// Periodically lift expired suspensions and delete accounts past their grace period
+pub fn spawn_maintenance(pool: PgPool, cache: CacheManager, interval_seconds: u64) {
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(StdDuration::from_secs(interval_seconds.max(1)));
+        loop {
+            interval.tick().await;
+            if let Err(e) = lift_expired_suspensions(&pool, &cache, None).await {
+                log::error!("Failed to lift expired suspensions: {e}");
+            }
+            if let Err(e) = purge_scheduled_deletions(&pool).await {
+                log::error!("Failed to delete accounts past their grace period: {e}");
+            }
+        }
+    });
+}
@@ -0,0 +1,235 @@
+use actix_web::http::Method;
+use actix_web::{HttpMessage, HttpRequest};
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::config::Config;
+use crate::models::Claims;
+use crate::utils::{generate_token, hash_token};
+
+/// Every API key starts with this, which is how `AuthMiddleware` tells keys from JWTs
+pub const API_KEY_PREFIX: &str = "co_";
+
+/// Scopes an API key can be granted
+pub const SCOPES: &[&str] = &[
+    "profile:read",
+    "profile:write",
+    "users:read",
+    "jobs:read",
+    "jobs:write",
+    "applications:read",
+    "applications:write",
+];
+
+/// Scopes a company key can be granted: it works on the company's postings and applicants only
+pub const COMPANY_SCOPES: &[&str] = &["jobs:read", "jobs:write", "applications:read", "applications:write"];
+
+/// The scope an API key needs for each endpoint it may call. `{id}` matches one numeric
+/// path segment, so `/users/{id}` does not also cover `/users/sessions`. Endpoints missing here, such as
+/// password, session and API key management, cannot be called with an API key at all.
+const ROUTE_SCOPES: &[(Method, &str, &str)] = &[
+    (Method::GET, "/api/v1/users/profile", "profile:read"),
+    (Method::PUT, "/api/v1/users/profile", "profile:write"),
+    (Method::GET, "/api/v1/users/batch", "users:read"),
+    (Method::GET, "/api/v1/users/{id}", "users:read"),
+    (Method::GET, "/api/v1/users/profile/candidate", "profile:read"),
+    (Method::GET, "/api/v1/users/{id}/candidate-profile", "users:read"),
+    (Method::GET, "/api/v1/companies/mine", "jobs:read"),
+    (Method::GET, "/api/v1/companies/{id}", "jobs:read"),
+    (Method::GET, "/api/v1/jobs", "jobs:read"),
+    (Method::GET, "/api/v1/jobs/search", "jobs:read"),
+    (Method::GET, "/api/v1/jobs/mine", "jobs:read"),
+    (Method::GET, "/api/v1/jobs/{id}", "jobs:read"),
+    (Method::POST, "/api/v1/jobs", "jobs:write"),
+    (Method::PUT, "/api/v1/jobs/{id}", "jobs:write"),
+    (Method::PUT, "/api/v1/jobs/{id}/status", "jobs:write"),
+    (Method::DELETE, "/api/v1/jobs/{id}", "jobs:write"),
+    (Method::GET, "/api/v1/jobs/{id}/applications", "applications:read"),
+    (Method::POST, "/api/v1/jobs/{id}/applications", "applications:write"),
+    (Method::POST, "/api/v1/jobs/{id}/applications/move", "applications:write"),
+    (Method::GET, "/api/v1/jobs/{id}/pipeline", "jobs:read"),
+    (Method::POST, "/api/v1/jobs/{id}/pipeline/stages", "jobs:write"),
+    (Method::PUT, "/api/v1/jobs/{id}/pipeline/stages/{id}", "jobs:write"),
+    (Method::DELETE, "/api/v1/jobs/{id}/pipeline/stages/{id}", "jobs:write"),
+    (Method::GET, "/api/v1/applications/mine", "applications:read"),
+    (Method::GET, "/api/v1/applications/received", "applications:read"),
+    (Method::GET, "/api/v1/applications/{id}", "applications:read"),
+    (Method::GET, "/api/v1/applications/{id}/history", "applications:read"),
+    (Method::PUT, "/api/v1/applications/{id}/stage", "applications:write"),
+    (Method::POST, "/api/v1/applications/{id}/withdraw", "applications:write"),
+];
+
+/// Endpoints where the caller acts as a job seeker. Company keys are refused here even
+/// with the scope, since they act for the company and not for the member who made them.
+const APPLICANT_ROUTES: &[(Method, &str)] = &[
+    (Method::POST, "/api/v1/jobs/{id}/applications"),
+    (Method::GET, "/api/v1/applications/mine"),
+    (Method::POST, "/api/v1/applications/{id}/withdraw"),
+];
+
+fn path_matches(pattern: &str, path: &str) -> bool {
+    let mut pattern_segments = pattern.split('/');
+    let mut path_segments = path.split('/');
+    loop {
+        match (pattern_segments.next(), path_segments.next()) {
+            (None, None) => return true,
+            (Some("{id}"), Some(segment)) if !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit()) => {}
+            (Some(expected), Some(segment)) if expected == segment => {}
+            _ => return false,
+        }
+    }
+}
+
+/// The scope required to call `method path` with an API key, or None if keys may not call it
+pub fn required_scope(method: &Method, path: &str) -> Option<&'static str> {
+    ROUTE_SCOPES.iter()
+        .find(|(route_method, pattern, _)| route_method == method && path_matches(pattern, path))
+        .map(|(_, _, scope)| *scope)
+}
+
+pub fn is_applicant_route(method: &Method, path: &str) -> bool {
+    APPLICANT_ROUTES.iter().any(|(route_method, pattern)| route_method == method && path_matches(pattern, path))
+}
+
+pub fn is_api_key(token: &str) -> bool {
+    token.starts_with(API_KEY_PREFIX)
+}
+
+/// A new key and the parts of it that are stored
+pub struct NewApiKey {
+    pub key: String,
+    pub prefix: String,
+    pub hash: String,
+}
+
+pub fn generate() -> NewApiKey {
+    let key = format!("{API_KEY_PREFIX}{}", generate_token());
+    NewApiKey {
+        prefix: key.chars().take(API_KEY_PREFIX.len() + 8).collect(),
+        hash: hash_token(&key),
+        key,
+    }
+}
+
+/// Attached to the request extensions by `AuthMiddleware` when the caller used an API key
+#[derive(Debug, Clone)]
+pub struct ApiKeyAuth {
+    pub company_id: Option<i32>, // set for company keys, which only reach that company
+}
+
+/// The company the request is limited to, when it was made with a company key
+pub fn key_company(req: &HttpRequest) -> Option<i32> {
+    req.extensions().get::<ApiKeyAuth>().and_then(|auth| auth.company_id)
+}
+
+/// Whether the request may act on `company_id`: always for JWTs and personal keys,
+/// only for its own company with a company key
+pub fn reaches_company(req: &HttpRequest, company_id: i32) -> bool {
+    key_company(req).is_none_or(|key_company| key_company == company_id)
+}
+
+/// Whether the request was authenticated with an API key rather than a user's own token
+pub fn is_key_request(req: &HttpRequest) -> bool {
+    req.extensions().get::<ApiKeyAuth>().is_some()
+}
+
+#[derive(sqlx::FromRow)]
+pub struct AuthenticatedKey {
+    pub company_id: Option<i32>,
+    pub scopes: Vec<String>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub user_id: i32,
+    pub username: String,
+    pub email: String,
+    pub role: String,
+}
+
+impl AuthenticatedKey {
+    /// Claims equivalent to the key, so handlers read the caller the same way for keys and JWTs.
+    /// Keys have no session or token id; endpoints that need them are never mapped to a scope.
+    /// They always carry the role's current permissions version.
+    pub fn claims(&self, config: &Config, permissions_version: i32) -> Claims {
+        Claims {
+            sub: self.user_id.to_string(),
+            username: self.username.clone(),
+            email: self.email.clone(),
+            role: self.role.clone(),
+            sid: Uuid::nil().to_string(),
+            jti: Uuid::nil().to_string(),
+            iss: config.jwt_issuer.clone(),
+            aud: config.jwt_audience.clone(),
+            exp: self.expires_at.timestamp() as usize,
+            iat: self.created_at.timestamp() as usize,
+            pv: permissions_version,
+            act: None,
+        }
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|granted| granted == scope)
+    }
+}
+
+/// Look up a live key for an active user, recording that it was just used.
+/// Company keys also need their creator to still be an owner or recruiter there.
+pub async fn authenticate(
+    pool: &PgPool,
+    key: &str,
+    ip_address: Option<&str>,
+) -> Result<Option<AuthenticatedKey>, sqlx::Error> {
+    sqlx::query_as::<_, AuthenticatedKey>(
+        r#"
+        UPDATE api_keys k SET last_used_at = CURRENT_TIMESTAMP, last_used_ip = $2
+        FROM users u
+        WHERE k.key_hash = $1 AND k.revoked_at IS NULL AND k.expires_at > CURRENT_TIMESTAMP
+          AND u.id = k.user_id AND u.is_active = true
+          AND (k.company_id IS NULL OR EXISTS (
+              SELECT 1 FROM company_members m
+              WHERE m.company_id = k.company_id AND m.user_id = k.user_id AND m.role IN ('owner', 'recruiter')
+          ))
+        RETURNING k.company_id, k.scopes, k.expires_at, k.created_at, u.id AS user_id, u.username, u.email, u.role
+        "#
+    )
+    .bind(hash_token(key))
+    .bind(ip_address)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Revoke every key `user_id` created, personal or for a company, inside `tx`.
+/// Keys act as their creator, so they go whenever the creator's sessions do.
+pub async fn revoke_all(tx: &mut Transaction<'_, Postgres>, user_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE api_keys SET revoked_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND revoked_at IS NULL")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_paths_to_scopes() {
+        assert_eq!(required_scope(&Method::GET, "/api/v1/users/42"), Some("users:read"));
+        assert_eq!(required_scope(&Method::GET, "/api/v1/users/sessions"), None);
+        assert_eq!(required_scope(&Method::PUT, "/api/v1/jobs/7/pipeline/stages/3"), Some("jobs:write"));
+        assert_eq!(required_scope(&Method::DELETE, "/api/v1/users/api-keys/1"), None);
+        assert_eq!(required_scope(&Method::GET, "/api/v1/jobs/"), None);
+    }
+
+    #[test]
+    fn company_scopes_and_applicant_routes() {
+        assert!(COMPANY_SCOPES.iter().all(|scope| SCOPES.contains(scope)));
+        for (method, pattern) in APPLICANT_ROUTES {
+            let path = pattern.replace("{id}", "1");
+            assert!(required_scope(method, &path).is_some_and(|scope| COMPANY_SCOPES.contains(&scope)), "{path}");
+            assert!(is_applicant_route(method, &path));
+        }
+        assert!(!is_applicant_route(&Method::POST, "/api/v1/jobs/1/applications/move"));
+        assert!(!is_applicant_route(&Method::GET, "/api/v1/applications/received"));
+    }
+}
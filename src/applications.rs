@@ -0,0 +1,140 @@
+use sqlx::PgPool;
+
+use crate::models::Application;
+
+const MAX_COVER_LETTER_LENGTH: usize = 10_000;
+const MAX_RESUME_URL_LENGTH: usize = 500;
+
+/// Columns of `Application`, for queries over
+/// `applications a JOIN jobs j ON j.id = a.job_id JOIN users u ON u.id = a.applicant_id
+/// JOIN pipeline_stages s ON s.id = a.stage_id`
+pub const APPLICATION_COLUMNS: &str = r#"
+    a.id, a.job_id, j.title AS job_title, j.company_id, a.applicant_id, u.username AS applicant_username,
+    u.email AS applicant_email, u.first_name AS applicant_first_name, u.last_name AS applicant_last_name,
+    a.cover_letter, a.resume_url, a.stage_id, s.name AS stage_name, a.status, a.status_changed_at, a.created_at, a.updated_at
+"#;
+
+/// How an application stands. Its pipeline stage says how far along it is;
+/// the stage's kind decides whether it is active, hired or rejected. The
+/// applicant can withdraw it while it is active.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplicationStatus {
+    Active,
+    Hired,
+    Rejected,
+    Withdrawn,
+}
+
+impl ApplicationStatus {
+    pub const ALL: &'static [ApplicationStatus] = &[
+        ApplicationStatus::Active,
+        ApplicationStatus::Hired,
+        ApplicationStatus::Rejected,
+        ApplicationStatus::Withdrawn,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApplicationStatus::Active => "active",
+            ApplicationStatus::Hired => "hired",
+            ApplicationStatus::Rejected => "rejected",
+            ApplicationStatus::Withdrawn => "withdrawn",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        ApplicationStatus::ALL.iter().copied().find(|candidate| candidate.as_str() == status)
+    }
+
+    /// Whether no decision has been made yet, so the applicant can still withdraw
+    pub fn is_open(&self) -> bool {
+        matches!(self, ApplicationStatus::Active)
+    }
+}
+
+/// Every status name, for error messages
+pub fn status_names() -> String {
+    ApplicationStatus::ALL.iter().map(ApplicationStatus::as_str).collect::<Vec<_>>().join(", ")
+}
+
+/// Check and tidy an application; the error is the message to show
+pub fn validate(cover_letter: Option<String>, resume_url: &str) -> Result<(Option<String>, String), String> {
+    let cover_letter = cover_letter
+        .map(|letter| letter.trim().to_string())
+        .filter(|letter| !letter.is_empty());
+    if cover_letter.as_ref().is_some_and(|letter| letter.chars().count() > MAX_COVER_LETTER_LENGTH) {
+        return Err(format!("Cover letter must be at most {MAX_COVER_LETTER_LENGTH} characters"));
+    }
+
+    let resume_url = resume_url.trim();
+    let valid_url = url::Url::parse(resume_url)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.host().is_some());
+    if !valid_url || resume_url.len() > MAX_RESUME_URL_LENGTH {
+        return Err(format!("resume_url must be an http(s) URL of at most {MAX_RESUME_URL_LENGTH} characters"));
+    }
+
+    Ok((cover_letter, resume_url.to_string()))
+}
+
+pub async fn find(pool: &PgPool, application_id: i32) -> Result<Option<Application>, sqlx::Error> {
+    sqlx::query_as::<_, Application>(&format!(
+        r#"
+        SELECT {APPLICATION_COLUMNS}
+        FROM applications a
+        JOIN jobs j ON j.id = a.job_id
+        JOIN users u ON u.id = a.applicant_id
+        JOIN pipeline_stages s ON s.id = a.stage_id
+        WHERE a.id = $1
+        "#
+    ))
+    .bind(application_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Which applications to list. Callers always restrict by applicant, company member or job.
+#[derive(Debug, Default)]
+pub struct ApplicationFilter {
+    pub applicant_id: Option<i32>,
+    pub member_id: Option<i32>, // applications to the companies this user belongs to
+    pub company_id: Option<i32>,
+    pub job_id: Option<i32>,
+    pub stage_id: Option<i32>,
+    pub status: Option<String>,
+}
+
+/// Newest first
+pub async fn list(
+    pool: &PgPool,
+    filter: &ApplicationFilter,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<Application>, sqlx::Error> {
+    sqlx::query_as::<_, Application>(&format!(
+        r#"
+        SELECT {APPLICATION_COLUMNS}
+        FROM applications a
+        JOIN jobs j ON j.id = a.job_id
+        JOIN users u ON u.id = a.applicant_id
+        JOIN pipeline_stages s ON s.id = a.stage_id
+        WHERE ($1::INTEGER IS NULL OR a.applicant_id = $1)
+          AND ($2::INTEGER IS NULL OR j.company_id IN (SELECT company_id FROM company_members WHERE user_id = $2))
+          AND ($3::INTEGER IS NULL OR a.job_id = $3)
+          AND ($4::INTEGER IS NULL OR a.stage_id = $4)
+          AND ($5::VARCHAR IS NULL OR a.status = $5)
+          AND ($8::INTEGER IS NULL OR j.company_id = $8)
+        ORDER BY a.created_at DESC, a.id DESC
+        LIMIT $6 OFFSET $7
+        "#
+    ))
+    .bind(filter.applicant_id)
+    .bind(filter.member_id)
+    .bind(filter.job_id)
+    .bind(filter.stage_id)
+    .bind(&filter.status)
+    .bind(limit)
+    .bind(offset)
+    .bind(filter.company_id)
+    .fetch_all(pool)
+    .await
+}
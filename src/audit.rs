@@ -0,0 +1,82 @@
+use sqlx::PgPool;
+
+/// Kinds of security events kept in `security_events` for later review
+#[derive(Debug, Clone, Copy)]
+pub enum SecurityEventType {
+    AccountLocked,
+    IpLocked,
+    AccountUnlocked,
+    IdentityLinked,
+    IdentityUnlinked,
+    ImpersonationStarted,
+    ImpersonationStopped,
+    ApiKeyCreated,
+    ApiKeyRevoked,
+    AccountDeactivated,
+    AccountReactivated,
+    AccountDeletionScheduled,
+    AccountDeleted,
+    AccountSuspended,
+    AccountUnsuspended,
+    RoleCreated,
+    RolePermissionsChanged,
+    RoleDeleted,
+    RoleAssigned,
+}
+
+impl SecurityEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SecurityEventType::AccountLocked => "account_locked",
+            SecurityEventType::IpLocked => "ip_locked",
+            SecurityEventType::AccountUnlocked => "account_unlocked",
+            SecurityEventType::IdentityLinked => "identity_linked",
+            SecurityEventType::IdentityUnlinked => "identity_unlinked",
+            SecurityEventType::ImpersonationStarted => "impersonation_started",
+            SecurityEventType::ImpersonationStopped => "impersonation_stopped",
+            SecurityEventType::ApiKeyCreated => "api_key_created",
+            SecurityEventType::ApiKeyRevoked => "api_key_revoked",
+            SecurityEventType::AccountDeactivated => "account_deactivated",
+            SecurityEventType::AccountReactivated => "account_reactivated",
+            SecurityEventType::AccountDeletionScheduled => "account_deletion_scheduled",
+            SecurityEventType::AccountDeleted => "account_deleted",
+            SecurityEventType::AccountSuspended => "account_suspended",
+            SecurityEventType::AccountUnsuspended => "account_unsuspended",
+            SecurityEventType::RoleCreated => "role_created",
+            SecurityEventType::RolePermissionsChanged => "role_permissions_changed",
+            SecurityEventType::RoleDeleted => "role_deleted",
+            SecurityEventType::RoleAssigned => "role_assigned",
+        }
+    }
+}
+
+/// Record a security event. `user_id` is the affected account and `actor_id`
+/// the user who caused the event, when that is someone else (e.g. an admin).
+/// Failures are logged rather than returned so auditing never breaks a request.
+pub async fn record(
+    pool: &PgPool,
+    event_type: SecurityEventType,
+    user_id: Option<i32>,
+    actor_id: Option<i32>,
+    ip_address: Option<&str>,
+    details: &str,
+) {
+    log::warn!(
+        "Security event {}: user={user_id:?} actor={actor_id:?} ip={ip_address:?} {details}",
+        event_type.as_str()
+    );
+
+    if let Err(e) = sqlx::query(
+        "INSERT INTO security_events (event_type, user_id, actor_id, ip_address, details) VALUES ($1, $2, $3, $4, $5)"
+    )
+    .bind(event_type.as_str())
+    .bind(user_id)
+    .bind(actor_id)
+    .bind(ip_address)
+    .bind(details)
+    .execute(pool)
+    .await
+    {
+        log::error!("Failed to record security event {}: {e}", event_type.as_str());
+    }
+}
@@ -0,0 +1,50 @@
+use sqlx::PgPool;
+
+use crate::password_policy::{PasswordOwner, PasswordPolicy};
+use crate::utils::PasswordHasher;
+
+/// `create-admin <username> <email>`: create the first admin account from the command line.
+/// The password is read from `ADMIN_PASSWORD` so it does not end up in shell history.
+pub async fn create_admin(
+    pool: &PgPool,
+    hasher: &PasswordHasher,
+    policy: &PasswordPolicy,
+    args: &[String],
+) -> anyhow::Result<()> {
+    let (Some(username), Some(email)) = (args.first(), args.get(1)) else {
+        anyhow::bail!("usage: create-admin <username> <email> (password is read from ADMIN_PASSWORD)");
+    };
+
+    let password = std::env::var("ADMIN_PASSWORD")
+        .map_err(|_| anyhow::anyhow!("ADMIN_PASSWORD must be set"))?;
+
+    if username.trim().len() < 3 {
+        anyhow::bail!("Username must be at least 3 characters long");
+    }
+    if !email.contains('@') {
+        anyhow::bail!("Valid email is required");
+    }
+    let owner = PasswordOwner { username, email };
+    if let Err(errors) = policy.check("ADMIN_PASSWORD", &password, owner).await {
+        let reasons: Vec<String> = errors.into_iter().map(|error| error.message).collect();
+        anyhow::bail!("{}", reasons.join("; "));
+    }
+
+    let password_hash = hasher.hash(&password).await?;
+
+    let user_id: i32 = sqlx::query_scalar(
+        r#"
+        INSERT INTO users (username, email, password_hash, role, email_verified)
+        VALUES ($1, $2, $3, 'admin', true)
+        RETURNING id
+        "#
+    )
+    .bind(username.trim())
+    .bind(email.trim())
+    .bind(&password_hash)
+    .fetch_one(pool)
+    .await?;
+
+    log::info!("Created admin account {username} (id {user_id})");
+    Ok(())
+}
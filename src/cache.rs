@@ -1,15 +1,25 @@
 use moka::future::Cache;
 use std::sync::Arc;
 use std::time::Duration;
+use uuid::Uuid;
 use crate::models::UserPublic;
+use crate::permissions::RolePermissions;
 
 pub type UserCache = Arc<Cache<i32, UserPublic>>;
-pub type TokenBlacklist = Arc<Cache<String, ()>>;
+pub type TokenBlacklist = Arc<Cache<Uuid, bool>>;
+pub type UnrevokedTokenCache = Arc<Cache<Uuid, ()>>;
+pub type TokenCutoffCache = Arc<Cache<i32, Option<i64>>>;
+pub type SessionCache = Arc<Cache<Uuid, bool>>;
+pub type RolePermissionCache = Arc<Cache<String, RolePermissions>>;
 
 #[derive(Clone)]
 pub struct CacheManager {
     pub user_cache: UserCache,
     pub token_blacklist: TokenBlacklist,
+    pub unrevoked_tokens: UnrevokedTokenCache,
+    pub token_cutoffs: TokenCutoffCache,
+    pub active_sessions: SessionCache,
+    pub role_permissions: RolePermissionCache,
 }
 
 impl CacheManager {
@@ -22,13 +32,42 @@ impl CacheManager {
                     .time_to_live(Duration::from_secs(3600))
                     .build()
             ),
-            // Token blacklist with 50,000 entries, 24 hour TTL
+            // Revoked tokens keyed by jti, 50,000 entries, 24 hour TTL
             token_blacklist: Arc::new(
                 Cache::builder()
                     .max_capacity(50_000)
                     .time_to_live(Duration::from_secs(86400))
                     .build()
             ),
+            // Tokens found not revoked, 50,000 entries, 5 second TTL so a missed
+            // revocation broadcast only goes unnoticed briefly
+            unrevoked_tokens: Arc::new(
+                Cache::builder()
+                    .max_capacity(50_000)
+                    .time_to_live(Duration::from_secs(5))
+                    .build()
+            ),
+            // Per-user token revocation cutoffs with 10,000 entries, 5 minute TTL
+            token_cutoffs: Arc::new(
+                Cache::builder()
+                    .max_capacity(10_000)
+                    .time_to_live(Duration::from_secs(300))
+                    .build()
+            ),
+            // Session liveness with 50,000 entries, 1 minute TTL (also throttles last_seen_at updates)
+            active_sessions: Arc::new(
+                Cache::builder()
+                    .max_capacity(50_000)
+                    .time_to_live(Duration::from_secs(60))
+                    .build()
+            ),
+            // Permissions granted by each role, 1 minute TTL (there are only a handful of roles)
+            role_permissions: Arc::new(
+                Cache::builder()
+                    .max_capacity(1_000)
+                    .time_to_live(Duration::from_secs(60))
+                    .build()
+            ),
         }
     }
 
@@ -40,17 +79,69 @@ impl CacheManager {
         self.user_cache.insert(user.id, user).await;
     }
 
-    #[allow(dead_code)]
     pub async fn invalidate_user(&self, user_id: i32) {
         self.user_cache.invalidate(&user_id).await;
     }
 
-    pub async fn is_token_blacklisted(&self, token: &str) -> bool {
-        self.token_blacklist.get(token).await.is_some()
+    // None means the cache does not know; check revoked_tokens
+    pub async fn is_token_blacklisted(&self, jti: Uuid) -> Option<bool> {
+        if self.token_blacklist.get(&jti).await.is_some() {
+            return Some(true);
+        }
+        self.unrevoked_tokens.get(&jti).await.map(|_| false)
+    }
+
+    pub async fn blacklist_token(&self, jti: Uuid) {
+        self.unrevoked_tokens.invalidate(&jti).await;
+        self.token_blacklist.insert(jti, true).await;
+    }
+
+    // Revocations are kept for a day, clean results only for a few seconds
+    pub async fn set_token_revoked(&self, jti: Uuid, revoked: bool) {
+        if revoked {
+            self.blacklist_token(jti).await;
+        } else {
+            self.unrevoked_tokens.insert(jti, ()).await;
+        }
+    }
+
+    pub async fn get_token_cutoff(&self, user_id: i32) -> Option<Option<i64>> {
+        self.token_cutoffs.get(&user_id).await
+    }
+
+    pub async fn set_token_cutoff(&self, user_id: i32, cutoff: Option<i64>) {
+        self.token_cutoffs.insert(user_id, cutoff).await;
+    }
+
+    pub async fn get_session_active(&self, session_id: Uuid) -> Option<bool> {
+        self.active_sessions.get(&session_id).await
+    }
+
+    pub async fn set_session_active(&self, session_id: Uuid, active: bool) {
+        self.active_sessions.insert(session_id, active).await;
+    }
+
+    pub async fn invalidate_sessions(&self, session_ids: &[Uuid]) {
+        for session_id in session_ids {
+            self.active_sessions.invalidate(session_id).await;
+        }
+    }
+
+    pub async fn get_role_permissions(&self, role: &str) -> Option<RolePermissions> {
+        self.role_permissions.get(role).await
+    }
+
+    pub async fn cache_role_permissions(&self, permissions: RolePermissions) {
+        self.role_permissions.insert(permissions.role.clone(), permissions).await;
     }
 
-    pub async fn blacklist_token(&self, token: String) {
-        self.token_blacklist.insert(token, ()).await;
+    // Forget everything derived from revocation state, forcing fresh database reads
+    pub fn invalidate_auth_state(&self) {
+        self.token_blacklist.invalidate_all();
+        self.unrevoked_tokens.invalidate_all();
+        self.token_cutoffs.invalidate_all();
+        self.active_sessions.invalidate_all();
+        self.role_permissions.invalidate_all();
     }
 }
 
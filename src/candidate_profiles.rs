@@ -0,0 +1,411 @@
+use chrono::{NaiveDate, Utc};
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::postgres::{PgArguments, PgRow};
+use sqlx::query::Query;
+use sqlx::{FromRow, PgPool, Postgres, Row, Transaction};
+
+use crate::jobs::EMPLOYMENT_TYPES;
+use crate::models::{
+    CandidateProfile, CandidateSkill, Certification, Education, Experience, ProfileEntry, ProfileLink, SpokenLanguage
+};
+
+pub const LANGUAGE_PROFICIENCIES: &[&str] = &["elementary", "limited_working", "professional_working", "full_professional", "native"];
+pub const SKILL_LEVELS: &[&str] = &["beginner", "intermediate", "advanced", "expert"];
+pub const LINK_KINDS: &[&str] = &["portfolio", "github", "linkedin", "website", "other"];
+
+const MAX_DESCRIPTION_LENGTH: usize = 5_000;
+const MAX_URL_LENGTH: usize = 500;
+
+type PgQuery<'q> = Query<'q, Postgres, PgArguments>;
+
+/// One section of a candidate profile, stored as ordered rows of `TABLE` that
+/// belong to the user. Every section gets the same list, create, update,
+/// delete and reorder endpoints under `/users/profile/{NAME}`.
+pub trait ProfileSection: Serialize + DeserializeOwned + for<'r> FromRow<'r, PgRow> + Send + Unpin + 'static {
+    /// Path segment under /users/profile
+    const NAME: &'static str;
+    const TABLE: &'static str;
+    /// Columns holding the fields, in the order `bind` binds them
+    const COLUMNS: &'static [&'static str];
+    const MAX_ENTRIES: i64;
+    /// Shown when a unique constraint of the section is hit
+    const DUPLICATE_MESSAGE: &'static str = "This entry is already on your profile";
+
+    /// Check the fields and tidy them up; the error is the message to show
+    fn validate(self) -> Result<Self, String>;
+
+    /// Bind the fields in the order of `COLUMNS`
+    fn bind<'q>(&'q self, query: PgQuery<'q>) -> PgQuery<'q>;
+}
+
+fn tidy(value: Option<String>) -> Option<String> {
+    value.map(|value| value.trim().to_string()).filter(|value| !value.is_empty())
+}
+
+fn required(field: &str, value: String, max: usize) -> Result<String, String> {
+    let value = value.trim().to_string();
+    if value.is_empty() || value.chars().count() > max {
+        return Err(format!("{field} must be between 1 and {max} characters"));
+    }
+    Ok(value)
+}
+
+fn optional(field: &str, value: Option<String>, max: usize) -> Result<Option<String>, String> {
+    let value = tidy(value);
+    if value.as_ref().is_some_and(|value| value.chars().count() > max) {
+        return Err(format!("{field} must be at most {max} characters"));
+    }
+    Ok(value)
+}
+
+fn one_of(field: &str, value: String, allowed: &[&str]) -> Result<String, String> {
+    let value = value.trim().to_lowercase();
+    if allowed.contains(&value.as_str()) {
+        Ok(value)
+    } else {
+        Err(format!("{field} must be one of: {}", allowed.join(", ")))
+    }
+}
+
+fn url(field: &str, value: String) -> Result<String, String> {
+    let value = value.trim().to_string();
+    let valid = value.len() <= MAX_URL_LENGTH
+        && url::Url::parse(&value).is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.host().is_some());
+    if valid {
+        Ok(value)
+    } else {
+        Err(format!("{field} must be an http(s) URL of at most {MAX_URL_LENGTH} characters"))
+    }
+}
+
+fn date_order(start_field: &str, start: Option<NaiveDate>, end_field: &str, end: Option<NaiveDate>) -> Result<(), String> {
+    match (start, end) {
+        (Some(start), Some(end)) if end < start => Err(format!("{end_field} must not be before {start_field}")),
+        _ => Ok(()),
+    }
+}
+
+fn not_in_future(field: &str, date: Option<NaiveDate>) -> Result<(), String> {
+    if date.is_some_and(|date| date > Utc::now().date_naive()) {
+        Err(format!("{field} must not be in the future"))
+    } else {
+        Ok(())
+    }
+}
+
+impl ProfileSection for Experience {
+    const NAME: &'static str = "experience";
+    const TABLE: &'static str = "candidate_experiences";
+    const COLUMNS: &'static [&'static str] = &[
+        "title", "company", "location", "employment_type", "start_date", "end_date", "description",
+    ];
+    const MAX_ENTRIES: i64 = 50;
+
+    fn validate(mut self) -> Result<Self, String> {
+        self.title = required("title", self.title, 100)?;
+        self.company = required("company", self.company, 100)?;
+        self.location = optional("location", self.location, 100)?;
+        self.employment_type = tidy(self.employment_type)
+            .map(|employment_type| one_of("employment_type", employment_type, EMPLOYMENT_TYPES))
+            .transpose()?;
+        not_in_future("start_date", Some(self.start_date))?;
+        date_order("start_date", Some(self.start_date), "end_date", self.end_date)?;
+        self.description = optional("description", self.description, MAX_DESCRIPTION_LENGTH)?;
+        Ok(self)
+    }
+
+    fn bind<'q>(&'q self, query: PgQuery<'q>) -> PgQuery<'q> {
+        query
+            .bind(&self.title)
+            .bind(&self.company)
+            .bind(&self.location)
+            .bind(&self.employment_type)
+            .bind(self.start_date)
+            .bind(self.end_date)
+            .bind(&self.description)
+    }
+}
+
+impl ProfileSection for Education {
+    const NAME: &'static str = "education";
+    const TABLE: &'static str = "candidate_education";
+    const COLUMNS: &'static [&'static str] = &[
+        "institution", "degree", "field_of_study", "start_date", "end_date", "grade", "description",
+    ];
+    const MAX_ENTRIES: i64 = 20;
+
+    fn validate(mut self) -> Result<Self, String> {
+        self.institution = required("institution", self.institution, 150)?;
+        self.degree = optional("degree", self.degree, 100)?;
+        self.field_of_study = optional("field_of_study", self.field_of_study, 100)?;
+        not_in_future("start_date", self.start_date)?;
+        date_order("start_date", self.start_date, "end_date", self.end_date)?;
+        self.grade = optional("grade", self.grade, 50)?;
+        self.description = optional("description", self.description, MAX_DESCRIPTION_LENGTH)?;
+        Ok(self)
+    }
+
+    fn bind<'q>(&'q self, query: PgQuery<'q>) -> PgQuery<'q> {
+        query
+            .bind(&self.institution)
+            .bind(&self.degree)
+            .bind(&self.field_of_study)
+            .bind(self.start_date)
+            .bind(self.end_date)
+            .bind(&self.grade)
+            .bind(&self.description)
+    }
+}
+
+impl ProfileSection for Certification {
+    const NAME: &'static str = "certifications";
+    const TABLE: &'static str = "candidate_certifications";
+    const COLUMNS: &'static [&'static str] = &[
+        "name", "issuer", "issued_on", "expires_on", "credential_id", "credential_url",
+    ];
+    const MAX_ENTRIES: i64 = 50;
+
+    fn validate(mut self) -> Result<Self, String> {
+        self.name = required("name", self.name, 150)?;
+        self.issuer = optional("issuer", self.issuer, 150)?;
+        not_in_future("issued_on", self.issued_on)?;
+        date_order("issued_on", self.issued_on, "expires_on", self.expires_on)?;
+        self.credential_id = optional("credential_id", self.credential_id, 100)?;
+        self.credential_url = tidy(self.credential_url)
+            .map(|credential_url| url("credential_url", credential_url))
+            .transpose()?;
+        Ok(self)
+    }
+
+    fn bind<'q>(&'q self, query: PgQuery<'q>) -> PgQuery<'q> {
+        query
+            .bind(&self.name)
+            .bind(&self.issuer)
+            .bind(self.issued_on)
+            .bind(self.expires_on)
+            .bind(&self.credential_id)
+            .bind(&self.credential_url)
+    }
+}
+
+impl ProfileSection for SpokenLanguage {
+    const NAME: &'static str = "languages";
+    const TABLE: &'static str = "candidate_languages";
+    const COLUMNS: &'static [&'static str] = &["language", "proficiency"];
+    const MAX_ENTRIES: i64 = 20;
+    const DUPLICATE_MESSAGE: &'static str = "This language is already on your profile";
+
+    fn validate(mut self) -> Result<Self, String> {
+        self.language = required("language", self.language, 50)?;
+        self.proficiency = one_of("proficiency", self.proficiency, LANGUAGE_PROFICIENCIES)?;
+        Ok(self)
+    }
+
+    fn bind<'q>(&'q self, query: PgQuery<'q>) -> PgQuery<'q> {
+        query.bind(&self.language).bind(&self.proficiency)
+    }
+}
+
+impl ProfileSection for CandidateSkill {
+    const NAME: &'static str = "skills";
+    const TABLE: &'static str = "candidate_skills";
+    const COLUMNS: &'static [&'static str] = &["name", "level", "years_of_experience"];
+    const MAX_ENTRIES: i64 = 100;
+    const DUPLICATE_MESSAGE: &'static str = "This skill is already on your profile";
+
+    fn validate(mut self) -> Result<Self, String> {
+        self.name = required("name", self.name, 50)?.to_lowercase();
+        self.level = one_of("level", self.level, SKILL_LEVELS)?;
+        if self.years_of_experience.is_some_and(|years| !(0..=60).contains(&years)) {
+            return Err("years_of_experience must be between 0 and 60".to_string());
+        }
+        Ok(self)
+    }
+
+    fn bind<'q>(&'q self, query: PgQuery<'q>) -> PgQuery<'q> {
+        query.bind(&self.name).bind(&self.level).bind(self.years_of_experience)
+    }
+}
+
+impl ProfileSection for ProfileLink {
+    const NAME: &'static str = "links";
+    const TABLE: &'static str = "candidate_links";
+    const COLUMNS: &'static [&'static str] = &["kind", "url", "label"];
+    const MAX_ENTRIES: i64 = 20;
+
+    fn validate(mut self) -> Result<Self, String> {
+        self.kind = one_of("kind", self.kind, LINK_KINDS)?;
+        self.url = url("url", self.url)?;
+        self.label = optional("label", self.label, 100)?;
+        Ok(self)
+    }
+
+    fn bind<'q>(&'q self, query: PgQuery<'q>) -> PgQuery<'q> {
+        query.bind(&self.kind).bind(&self.url).bind(&self.label)
+    }
+}
+
+fn entry_columns<S: ProfileSection>() -> String {
+    format!("id, {}, position, created_at, updated_at", S::COLUMNS.join(", "))
+}
+
+/// The user's entries of a section, in their order
+pub async fn entries<S: ProfileSection>(pool: &PgPool, user_id: i32) -> Result<Vec<ProfileEntry<S>>, sqlx::Error> {
+    sqlx::query_as::<_, ProfileEntry<S>>(&format!(
+        "SELECT {} FROM {} WHERE user_id = $1 ORDER BY position, id",
+        entry_columns::<S>(),
+        S::TABLE
+    ))
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
+
+pub async fn find<S: ProfileSection>(pool: &PgPool, user_id: i32, entry_id: i32) -> Result<Option<ProfileEntry<S>>, sqlx::Error> {
+    sqlx::query_as::<_, ProfileEntry<S>>(&format!(
+        "SELECT {} FROM {} WHERE id = $1 AND user_id = $2",
+        entry_columns::<S>(),
+        S::TABLE
+    ))
+    .bind(entry_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Lock the user's profile so concurrent edits of a section are counted and
+/// ordered one at a time
+async fn lock_profile(tx: &mut Transaction<'_, Postgres>, user_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT id FROM users WHERE id = $1 FOR NO KEY UPDATE")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    Ok(())
+}
+
+/// Add an entry at the end of the section. Returns None when the section is full.
+pub async fn create<S: ProfileSection>(pool: &PgPool, user_id: i32, fields: &S) -> Result<Option<i32>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    lock_profile(&mut tx, user_id).await?;
+
+    let count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM {} WHERE user_id = $1", S::TABLE))
+        .bind(user_id)
+        .fetch_one(&mut tx)
+        .await?;
+    if count >= S::MAX_ENTRIES {
+        return Ok(None);
+    }
+
+    let placeholders: Vec<String> = (2..S::COLUMNS.len() + 2).map(|n| format!("${n}")).collect();
+    let sql = format!(
+        r#"
+        INSERT INTO {table} (user_id, position, {columns})
+        VALUES ($1, (SELECT COALESCE(MAX(position), 0) + 1 FROM {table} WHERE user_id = $1), {placeholders})
+        RETURNING id
+        "#,
+        table = S::TABLE,
+        columns = S::COLUMNS.join(", "),
+        placeholders = placeholders.join(", ")
+    );
+    let row = fields.bind(sqlx::query(&sql).bind(user_id)).fetch_one(&mut tx).await?;
+    let entry_id: i32 = row.try_get("id")?;
+
+    tx.commit().await?;
+    Ok(Some(entry_id))
+}
+
+/// Replace the fields of one of the user's entries. Returns false if there is no such entry.
+pub async fn update<S: ProfileSection>(pool: &PgPool, user_id: i32, entry_id: i32, fields: &S) -> Result<bool, sqlx::Error> {
+    let assignments: Vec<String> = S::COLUMNS.iter()
+        .enumerate()
+        .map(|(i, column)| format!("{column} = ${}", i + 3))
+        .collect();
+    let sql = format!(
+        "UPDATE {} SET {}, updated_at = CURRENT_TIMESTAMP WHERE id = $1 AND user_id = $2",
+        S::TABLE,
+        assignments.join(", ")
+    );
+    let result = fields.bind(sqlx::query(&sql).bind(entry_id).bind(user_id)).execute(pool).await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Returns false if the user has no such entry
+pub async fn delete<S: ProfileSection>(pool: &PgPool, user_id: i32, entry_id: i32) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(&format!("DELETE FROM {} WHERE id = $1 AND user_id = $2", S::TABLE))
+        .bind(entry_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Put the section in the order of `entry_ids`, which must list each of the
+/// user's entries exactly once. Returns false, changing nothing, when it does not.
+pub async fn reorder<S: ProfileSection>(pool: &PgPool, user_id: i32, entry_ids: &[i32]) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    lock_profile(&mut tx, user_id).await?;
+
+    let mut current: Vec<i32> = sqlx::query_scalar(&format!("SELECT id FROM {} WHERE user_id = $1", S::TABLE))
+        .bind(user_id)
+        .fetch_all(&mut tx)
+        .await?;
+    let mut requested = entry_ids.to_vec();
+    current.sort_unstable();
+    requested.sort_unstable();
+    if current != requested {
+        return Ok(false);
+    }
+
+    sqlx::query(&format!(
+        r#"
+        UPDATE {} e SET position = o.position::INTEGER, updated_at = CURRENT_TIMESTAMP
+        FROM UNNEST($1::INTEGER[]) WITH ORDINALITY AS o(id, position)
+        WHERE e.id = o.id AND e.user_id = $2
+        "#,
+        S::TABLE
+    ))
+    .bind(entry_ids)
+    .bind(user_id)
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(true)
+}
+
+/// Every section of the user's profile in one document, or None if there is no such active user
+pub async fn profile(pool: &PgPool, user_id: i32) -> Result<Option<CandidateProfile>, sqlx::Error> {
+    let user = sqlx::query(
+        "SELECT username, first_name, last_name, professional_role FROM users WHERE id = $1 AND is_active = true"
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(user) = user else {
+        return Ok(None);
+    };
+
+    let (experience, education, certifications, languages, skills, links) = futures_util::try_join!(
+        entries::<Experience>(pool, user_id),
+        entries::<Education>(pool, user_id),
+        entries::<Certification>(pool, user_id),
+        entries::<SpokenLanguage>(pool, user_id),
+        entries::<CandidateSkill>(pool, user_id),
+        entries::<ProfileLink>(pool, user_id),
+    )?;
+
+    Ok(Some(CandidateProfile {
+        user_id,
+        username: user.try_get("username")?,
+        first_name: user.try_get("first_name")?,
+        last_name: user.try_get("last_name")?,
+        professional_role: user.try_get("professional_role")?,
+        experience,
+        education,
+        certifications,
+        languages,
+        skills,
+        links,
+    }))
+}
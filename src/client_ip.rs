@@ -0,0 +1,146 @@
+use actix_web::http::header::HeaderMap;
+use actix_web::{web, HttpRequest};
+use std::net::{IpAddr, SocketAddr};
+
+use crate::config::Config;
+
+/// A proxy address or network from `TRUSTED_PROXIES`, e.g. `10.0.0.5` or `10.0.0.0/8`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TrustedProxy {
+    network: IpAddr,
+    prefix: u8,
+}
+
+impl TrustedProxy {
+    pub fn parse(value: &str) -> Option<Self> {
+        let (address, prefix) = match value.trim().split_once('/') {
+            Some((address, prefix)) => (address, Some(prefix.parse::<u8>().ok()?)),
+            None => (value.trim(), None),
+        };
+        let network: IpAddr = address.parse().ok()?;
+        let max = if network.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(max);
+        (prefix <= max).then_some(Self { network, prefix })
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.network, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - u32::from(self.prefix)).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - u32::from(self.prefix)).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+fn parse_hop(hop: &str) -> Option<IpAddr> {
+    let hop = hop.trim();
+    hop.parse::<IpAddr>()
+        .ok()
+        .or_else(|| hop.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+/// The client behind `peer`. `X-Forwarded-For` is only believed for hops added
+/// by trusted proxies: walking it from the nearest hop back, the first address
+/// that is not a trusted proxy is the client. Anything a client puts in the
+/// header itself sits further back and is never reached.
+pub fn resolve(peer: IpAddr, headers: &HeaderMap, trusted: &[TrustedProxy]) -> IpAddr {
+    let is_trusted = |ip: IpAddr| trusted.iter().any(|proxy| proxy.contains(ip));
+    if !is_trusted(peer) {
+        return peer;
+    }
+
+    let hops: Vec<&str> = headers.get_all("X-Forwarded-For")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect();
+
+    let mut client = peer;
+    for hop in hops.iter().rev() {
+        match parse_hop(hop) {
+            Some(ip) => {
+                client = ip;
+                if !is_trusted(ip) {
+                    break;
+                }
+            }
+            None => break,
+        }
+    }
+    client
+}
+
+/// The address of the client making the request, for throttling, sessions and audit logs
+pub fn client_ip(req: &HttpRequest) -> Option<String> {
+    let peer = req.peer_addr()?.ip();
+    let trusted = req.app_data::<web::Data<Config>>()
+        .map(|config| config.trusted_proxies.as_slice())
+        .unwrap_or_default();
+    Some(resolve(peer, req.headers(), trusted).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::header::{HeaderName, HeaderValue};
+
+    fn forwarded_for(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(HeaderName::from_static("x-forwarded-for"), HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    fn proxies(values: &[&str]) -> Vec<TrustedProxy> {
+        values.iter().map(|value| TrustedProxy::parse(value).unwrap()).collect()
+    }
+
+    #[test]
+    fn ignores_forwarded_for_from_untrusted_peers() {
+        let headers = forwarded_for(&["203.0.113.7"]);
+        assert_eq!(resolve(ip("198.51.100.1"), &headers, &[]), ip("198.51.100.1"));
+        assert_eq!(resolve(ip("198.51.100.1"), &headers, &proxies(&["10.0.0.0/8"])), ip("198.51.100.1"));
+    }
+
+    #[test]
+    fn takes_the_hop_before_trusted_proxies() {
+        let trusted = proxies(&["10.0.0.0/8"]);
+        let headers = forwarded_for(&["6.6.6.6, 203.0.113.7, 10.1.2.3"]);
+        assert_eq!(resolve(ip("10.0.0.1"), &headers, &trusted), ip("203.0.113.7"));
+
+        // Values from several headers are one list
+        let headers = forwarded_for(&["6.6.6.6", "203.0.113.7:4711"]);
+        assert_eq!(resolve(ip("10.0.0.1"), &headers, &trusted), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn stops_at_unparsable_hops() {
+        let trusted = proxies(&["10.0.0.0/8"]);
+        let headers = forwarded_for(&["203.0.113.7, garbage, 10.1.2.3"]);
+        assert_eq!(resolve(ip("10.0.0.1"), &headers, &trusted), ip("10.1.2.3"));
+        assert_eq!(resolve(ip("10.0.0.1"), &HeaderMap::new(), &trusted), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn parses_addresses_and_networks() {
+        assert!(TrustedProxy::parse("10.0.0.5").unwrap().contains(ip("10.0.0.5")));
+        assert!(!TrustedProxy::parse("10.0.0.5").unwrap().contains(ip("10.0.0.6")));
+        assert!(TrustedProxy::parse("172.16.0.0/12").unwrap().contains(ip("172.31.255.1")));
+        assert!(!TrustedProxy::parse("172.16.0.0/12").unwrap().contains(ip("172.32.0.1")));
+        assert!(TrustedProxy::parse("fd00::/8").unwrap().contains(ip("fd12::1")));
+        assert!(TrustedProxy::parse("0.0.0.0/0").unwrap().contains(ip("8.8.8.8")));
+        assert!(!TrustedProxy::parse("10.0.0.0/8").unwrap().contains(ip("::1")));
+        assert_eq!(TrustedProxy::parse("10.0.0.0/33"), None);
+        assert_eq!(TrustedProxy::parse("proxy.local"), None);
+    }
+}
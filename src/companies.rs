@@ -0,0 +1,134 @@
+use sqlx::PgPool;
+
+use crate::models::{Company, CreateCompanyRequest, UpdateCompanyRequest};
+
+pub const COMPANY_SIZES: &[&str] = &["1-10", "11-50", "51-200", "201-500", "501-1000", "1001-5000", "5001+"];
+
+const MAX_DESCRIPTION_LENGTH: usize = 5_000;
+const MAX_URL_LENGTH: usize = 500;
+
+/// Columns of `Company`, for queries over `companies c`
+pub const COMPANY_COLUMNS: &str = r#"
+    c.id, c.name, c.description, c.logo_url, c.website, c.industry, c.size,
+    c.verified_at IS NOT NULL AS verified, c.verified_domain, c.verified_at,
+    (SELECT COUNT(*) FROM company_members WHERE company_id = c.id) AS member_count,
+    c.created_at, c.updated_at
+"#;
+
+/// A member's role in a company, in order of what it allows. Viewers see the
+/// company's postings and applicants, recruiters also manage them, and owners
+/// also manage the profile and the team.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CompanyRole {
+    Viewer,
+    Recruiter,
+    Owner,
+}
+
+impl CompanyRole {
+    pub const ALL: &'static [CompanyRole] = &[CompanyRole::Owner, CompanyRole::Recruiter, CompanyRole::Viewer];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CompanyRole::Viewer => "viewer",
+            CompanyRole::Recruiter => "recruiter",
+            CompanyRole::Owner => "owner",
+        }
+    }
+
+    pub fn parse(role: &str) -> Option<Self> {
+        CompanyRole::ALL.iter().copied().find(|candidate| candidate.as_str() == role)
+    }
+
+    /// Whether the member may edit postings and move applicants through pipelines
+    pub fn can_manage_jobs(&self) -> bool {
+        *self >= CompanyRole::Recruiter
+    }
+}
+
+fn tidy(value: Option<String>) -> Option<String> {
+    value.map(|value| value.trim().to_string()).filter(|value| !value.is_empty())
+}
+
+fn check_url(field: &str, value: &Option<String>) -> Result<(), String> {
+    let valid = value.as_deref().is_none_or(|value| {
+        value.len() <= MAX_URL_LENGTH
+            && url::Url::parse(value).is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.host().is_some())
+    });
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("{field} must be an http(s) URL of at most {MAX_URL_LENGTH} characters"))
+    }
+}
+
+/// Check a company profile and tidy it up; the error is the message to show
+pub fn validate(mut company: CreateCompanyRequest) -> Result<CreateCompanyRequest, String> {
+    company.name = company.name.trim().to_string();
+    if company.name.is_empty() || company.name.chars().count() > 100 {
+        return Err("Name must be between 1 and 100 characters".to_string());
+    }
+
+    company.description = tidy(company.description);
+    if company.description.as_ref().is_some_and(|description| description.chars().count() > MAX_DESCRIPTION_LENGTH) {
+        return Err(format!("Description must be at most {MAX_DESCRIPTION_LENGTH} characters"));
+    }
+
+    company.logo_url = tidy(company.logo_url);
+    check_url("logo_url", &company.logo_url)?;
+    company.website = tidy(company.website);
+    check_url("website", &company.website)?;
+
+    company.industry = tidy(company.industry);
+    if company.industry.as_ref().is_some_and(|industry| industry.chars().count() > 100) {
+        return Err("Industry must be at most 100 characters".to_string());
+    }
+
+    company.size = tidy(company.size);
+    if company.size.as_deref().is_some_and(|size| !COMPANY_SIZES.contains(&size)) {
+        return Err(format!("size must be one of: {}", COMPANY_SIZES.join(", ")));
+    }
+
+    Ok(company)
+}
+
+/// The profile `current` would become with `changes` applied, still to be validated
+pub fn merge(current: &Company, changes: UpdateCompanyRequest) -> CreateCompanyRequest {
+    CreateCompanyRequest {
+        name: changes.name.unwrap_or_else(|| current.name.clone()),
+        description: changes.description.or_else(|| current.description.clone()),
+        logo_url: changes.logo_url.or_else(|| current.logo_url.clone()),
+        website: changes.website.or_else(|| current.website.clone()),
+        industry: changes.industry.or_else(|| current.industry.clone()),
+        size: changes.size.or_else(|| current.size.clone()),
+    }
+}
+
+pub async fn find(pool: &PgPool, company_id: i32) -> Result<Option<Company>, sqlx::Error> {
+    sqlx::query_as::<_, Company>(&format!("SELECT {COMPANY_COLUMNS} FROM companies c WHERE c.id = $1"))
+        .bind(company_id)
+        .fetch_optional(pool)
+        .await
+}
+
+/// The user's role in the company, or None if they are not a member
+pub async fn member_role(pool: &PgPool, company_id: i32, user_id: i32) -> Result<Option<CompanyRole>, sqlx::Error> {
+    let role: Option<String> = sqlx::query_scalar(
+        "SELECT role FROM company_members WHERE company_id = $1 AND user_id = $2"
+    )
+    .bind(company_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+    Ok(role.as_deref().and_then(CompanyRole::parse))
+}
+
+/// The companies the user may post jobs for
+pub async fn posting_companies(pool: &PgPool, user_id: i32) -> Result<Vec<i32>, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT company_id FROM company_members WHERE user_id = $1 AND role IN ('owner', 'recruiter') ORDER BY company_id"
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await
+}
@@ -32,11 +32,12 @@ pub async fn get_user_by_id_optimized(pool: &PgPool, user_id: i32) -> Result<Opt
     .await
 }
 
+// Inactive accounts are included so login can tell their owners why they cannot sign in
 pub async fn get_user_by_email_or_username_optimized(pool: &PgPool, identifier: &str) -> Result<Option<sqlx::postgres::PgRow>, sqlx::Error> {
     sqlx::query(
         "SELECT id, username, email, password_hash, first_name, last_name, phone, role, professional_role, company_name, is_active, email_verified, created_at, updated_at
          FROM users 
-         WHERE (username = $1 OR email = $1) AND is_active = true
+         WHERE username = $1 OR email = $1
          LIMIT 1"
     )
     .bind(identifier)
@@ -57,6 +58,61 @@ pub async fn get_multiple_users(pool: &PgPool, user_ids: &[i32]) -> Result<Vec<s
     .await
 }
 
+// Timestamp (unix seconds) before which the user's JWTs are considered revoked
+pub async fn get_tokens_invalid_before(pool: &PgPool, user_id: i32) -> Result<Option<i64>, sqlx::Error> {
+    let cutoff: Option<Option<chrono::DateTime<chrono::Utc>>> = sqlx::query_scalar(
+        "SELECT tokens_invalid_before FROM users WHERE id = $1"
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(cutoff.flatten().map(|ts| ts.timestamp()))
+}
+
+// Check that a session is still live and record that it was just used
+pub async fn touch_session(pool: &PgPool, session_id: uuid::Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE user_sessions SET last_seen_at = CURRENT_TIMESTAMP
+         WHERE family_id = $1 AND revoked_at IS NULL AND expires_at > CURRENT_TIMESTAMP"
+    )
+    .bind(session_id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Check that an impersonation session is still open and its admin may still impersonate
+pub async fn touch_impersonation(pool: &PgPool, session_id: uuid::Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE impersonation_sessions s SET last_seen_at = CURRENT_TIMESTAMP
+         FROM users a
+         JOIN role_permissions rp ON rp.role = a.role AND rp.permission = 'users:impersonate'
+         WHERE s.id = $1 AND s.ended_at IS NULL AND s.expires_at > CURRENT_TIMESTAMP
+           AND a.id = s.admin_id AND a.is_active = true"
+    )
+    .bind(session_id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Revoke every refresh token in a session family; returns false if the session is not the user's
+pub async fn revoke_session(pool: &PgPool, user_id: i32, session_id: uuid::Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE user_sessions SET revoked_at = CURRENT_TIMESTAMP
+         WHERE user_id = $1 AND family_id = $2 AND revoked_at IS NULL"
+    )
+    .bind(user_id)
+    .bind(session_id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
 // Transaction helper for atomic operations
 #[allow(dead_code)]
 pub async fn begin_transaction(pool: &PgPool) -> Result<Transaction<'_, Postgres>, sqlx::Error> {
@@ -0,0 +1,309 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use futures_util::future::BoxFuture;
+use reqwest::Client;
+use serde::Deserialize;
+use sqlx::{Postgres, Transaction};
+
+use crate::config::Config;
+
+/// Prefix of the TXT record value a company publishes on its domain
+pub const TXT_RECORD_PREFIX: &str = "connecting-opportunities-verification=";
+
+/// Mailboxes only a domain's administrators should receive mail for, as reserved by
+/// RFC 2142 and by certificate authorities for the same kind of check
+const ADMIN_MAILBOXES: &[&str] = &["admin", "hostmaster", "postmaster", "webmaster"];
+
+/// Domains many unrelated people get addresses or sites on. Neither the domain
+/// itself nor any subdomain of it says who runs a company.
+const SHARED_DOMAINS: &[&str] = &[
+    // Free mail
+    "gmail.com", "googlemail.com", "outlook.com", "hotmail.com", "live.com", "msn.com",
+    "yahoo.com", "ymail.com", "aol.com", "icloud.com", "me.com", "mac.com", "proton.me",
+    "protonmail.com", "gmx.com", "gmx.net", "gmx.de", "web.de", "mail.com", "yandex.com",
+    "yandex.ru", "mail.ru", "zoho.com", "fastmail.com", "tutanota.com", "qq.com", "163.com",
+    // Shared hosting and site builders
+    "github.io", "gitlab.io", "pages.dev", "workers.dev", "vercel.app", "netlify.app",
+    "herokuapp.com", "onrender.com", "fly.dev", "web.app", "firebaseapp.com", "appspot.com",
+    "azurewebsites.net", "cloudfront.net", "amazonaws.com", "blogspot.com", "wordpress.com",
+    "wixsite.com", "squarespace.com", "weebly.com", "webflow.io", "carrd.co", "notion.site",
+    "sites.google.com",
+];
+
+/// Public suffixes under which anyone can register a domain. Domains registered under
+/// them are fine; the suffix itself is not a company's.
+const PUBLIC_SUFFIXES: &[&str] = &[
+    "co.uk", "org.uk", "me.uk", "ltd.uk", "plc.uk", "com.au", "net.au", "org.au", "co.nz",
+    "co.jp", "ne.jp", "co.kr", "com.br", "com.cn", "com.mx", "com.ar", "com.tr", "co.in",
+    "co.za", "com.sg", "com.hk", "eu.org", "us.com", "uk.com",
+];
+
+fn is_shared_domain(domain: &str) -> bool {
+    PUBLIC_SUFFIXES.contains(&domain)
+        || SHARED_DOMAINS.iter().any(|shared| domain == *shared || domain.ends_with(&format!(".{shared}")))
+}
+
+/// The domain a company can verify: the host of its website, without `www.`.
+/// None for IP addresses, single labels, public suffixes and shared domains.
+pub fn website_domain(website: &str) -> Option<String> {
+    let url = url::Url::parse(website).ok()?;
+    let url::Host::Domain(host) = url.host()? else {
+        return None; // IP addresses cannot carry an email address or a TXT record
+    };
+    let host = host.trim_end_matches('.').to_lowercase();
+    let domain = host.strip_prefix("www.").unwrap_or(&host);
+    (domain.contains('.') && !is_shared_domain(domain)).then(|| domain.to_string())
+}
+
+/// Whether `email` is one of the administrative mailboxes of `domain` itself
+pub fn is_admin_mailbox(email: &str, domain: &str) -> bool {
+    let Some((local, host)) = email.trim().rsplit_once('@') else {
+        return false;
+    };
+    ADMIN_MAILBOXES.contains(&local.to_lowercase().as_str()) && host.to_lowercase() == domain
+}
+
+/// Looks up TXT records. Behind a trait so development setups and tests can
+/// answer from a fixed table instead of the real DNS.
+pub trait TxtResolver: Send + Sync {
+    fn txt_records<'a>(&'a self, domain: &'a str) -> BoxFuture<'a, anyhow::Result<Vec<String>>>;
+}
+
+/// The configured resolver: the fixed records from `DNS_TXT_RECORDS` when set,
+/// DNS-over-HTTPS otherwise
+pub fn resolver_from_config(config: &Config) -> Arc<dyn TxtResolver> {
+    match &config.dns_txt_records {
+        Some(records) => {
+            log::warn!("DNS_TXT_RECORDS is set, TXT lookups will not query DNS");
+            Arc::new(StaticTxtResolver::parse(records))
+        }
+        None => Arc::new(DohTxtResolver::new(&config.dns_resolver_url)),
+    }
+}
+
+/// Whether `domain` publishes the TXT record for `token`
+pub async fn has_token(resolver: &dyn TxtResolver, domain: &str, token: &str) -> anyhow::Result<bool> {
+    let expected = format!("{TXT_RECORD_PREFIX}{token}");
+    let records = resolver.txt_records(domain).await?;
+    Ok(records.iter().any(|record| record.trim() == expected))
+}
+
+/// Resolves through a DNS-over-HTTPS JSON API, as offered by Cloudflare and Google
+pub struct DohTxtResolver {
+    client: Client,
+    url: String,
+}
+
+#[derive(Deserialize)]
+struct DohResponse {
+    #[serde(rename = "Status")]
+    status: u32,
+    #[serde(rename = "Answer", default)]
+    answer: Vec<DohAnswer>,
+}
+
+#[derive(Deserialize)]
+struct DohAnswer {
+    #[serde(rename = "type")]
+    record_type: u16,
+    data: String,
+}
+
+const DNS_TYPE_TXT: u16 = 16;
+const DNS_STATUS_NXDOMAIN: u32 = 3;
+
+impl DohTxtResolver {
+    pub fn new(url: &str) -> Self {
+        Self {
+            client: Client::new(),
+            url: url.to_string(),
+        }
+    }
+
+    async fn lookup(&self, domain: &str) -> anyhow::Result<Vec<String>> {
+        let response: DohResponse = self.client
+            .get(&self.url)
+            .query(&[("name", domain), ("type", "TXT")])
+            .header("accept", "application/dns-json")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        match response.status {
+            0 => {}
+            DNS_STATUS_NXDOMAIN => return Ok(Vec::new()),
+            status => anyhow::bail!("DNS lookup for {domain} failed with status {status}"),
+        }
+
+        Ok(response.answer
+            .into_iter()
+            .filter(|answer| answer.record_type == DNS_TYPE_TXT)
+            .map(|answer| unquote_txt(&answer.data))
+            .collect())
+    }
+}
+
+impl TxtResolver for DohTxtResolver {
+    fn txt_records<'a>(&'a self, domain: &'a str) -> BoxFuture<'a, anyhow::Result<Vec<String>>> {
+        Box::pin(self.lookup(domain))
+    }
+}
+
+/// TXT data comes as one or more quoted strings, e.g. `"part one" "part two"`,
+/// which make up a single value
+fn unquote_txt(data: &str) -> String {
+    if !data.starts_with('"') {
+        return data.to_string();
+    }
+
+    let mut value = String::new();
+    let mut quoted = false;
+    let mut chars = data.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' if quoted => value.extend(chars.next()),
+            c if quoted => value.push(c),
+            _ => {}
+        }
+    }
+    value
+}
+
+/// Answers from a fixed table, configured as comma-separated `domain=value` pairs
+pub struct StaticTxtResolver {
+    records: HashMap<String, Vec<String>>,
+}
+
+impl StaticTxtResolver {
+    pub fn parse(records: &str) -> Self {
+        let mut table: HashMap<String, Vec<String>> = HashMap::new();
+        for (domain, value) in records.split(',').filter_map(|record| record.split_once('=')) {
+            table.entry(domain.trim().to_lowercase()).or_default().push(value.trim().to_string());
+        }
+        Self { records: table }
+    }
+}
+
+impl TxtResolver for StaticTxtResolver {
+    fn txt_records<'a>(&'a self, domain: &'a str) -> BoxFuture<'a, anyhow::Result<Vec<String>>> {
+        let records = self.records.get(domain).cloned().unwrap_or_default();
+        Box::pin(async move { Ok(records) })
+    }
+}
+
+/// Mark the company as owning `domain` and use up its pending verifications.
+/// Returns false, changing nothing, when the website has moved to another domain
+/// since the verification was requested.
+pub async fn mark_verified(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: i32,
+    domain: &str,
+) -> Result<bool, sqlx::Error> {
+    let website: Option<Option<String>> = sqlx::query_scalar(
+        "SELECT website FROM companies WHERE id = $1 FOR UPDATE"
+    )
+    .bind(company_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if website.flatten().as_deref().and_then(website_domain).as_deref() != Some(domain) {
+        return Ok(false);
+    }
+
+    sqlx::query(
+        r#"
+        UPDATE companies SET verified_domain = $1, verified_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+        WHERE id = $2
+        "#
+    )
+    .bind(domain)
+    .bind(company_id)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        "UPDATE company_domain_verifications SET used_at = CURRENT_TIMESTAMP WHERE company_id = $1 AND used_at IS NULL"
+    )
+    .bind(company_id)
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn website_domain_takes_the_host_without_www() {
+        assert_eq!(website_domain("https://www.Example.com/careers").as_deref(), Some("example.com"));
+        assert_eq!(website_domain("http://jobs.example.co.uk:8080").as_deref(), Some("jobs.example.co.uk"));
+        assert_eq!(website_domain("https://example.com.").as_deref(), Some("example.com"));
+        // Only a leading www. is dropped
+        assert_eq!(website_domain("https://www.www.example.com").as_deref(), Some("www.example.com"));
+    }
+
+    #[test]
+    fn website_domain_refuses_hosts_that_name_no_company() {
+        assert_eq!(website_domain("https://192.0.2.10/"), None);
+        assert_eq!(website_domain("https://[2001:db8::1]/"), None);
+        assert_eq!(website_domain("http://localhost:3000"), None);
+        assert_eq!(website_domain("https://www.intranet"), None);
+        assert_eq!(website_domain("not a url"), None);
+        assert_eq!(website_domain("mailto:admin@example.com"), None);
+        assert_eq!(website_domain("https://gmail.com"), None);
+        assert_eq!(website_domain("https://acme.github.io/"), None);
+        assert_eq!(website_domain("https://www.co.uk"), None);
+    }
+
+    #[test]
+    fn only_admin_mailboxes_of_the_domain_itself() {
+        for mailbox in ["admin", "hostmaster", "postmaster", "webmaster"] {
+            assert!(is_admin_mailbox(&format!("{mailbox}@example.com"), "example.com"), "{mailbox}");
+        }
+        assert!(is_admin_mailbox(" Admin@Example.COM ", "example.com"));
+        assert!(!is_admin_mailbox("jane@example.com", "example.com"));
+        assert!(!is_admin_mailbox("admin@mail.example.com", "example.com"));
+        assert!(!is_admin_mailbox("admin@example.com.evil.net", "example.com"));
+        assert!(!is_admin_mailbox("admin@notexample.com", "example.com"));
+        assert!(!is_admin_mailbox("admin", "example.com"));
+        assert!(!is_admin_mailbox("@example.com", "example.com"));
+    }
+
+    #[test]
+    fn unquotes_txt_data() {
+        assert_eq!(unquote_txt("\"plain\""), "plain");
+        assert_eq!(unquote_txt("\"part one \" \"part two\""), "part one part two");
+        assert_eq!(unquote_txt(r#""say \"hi\" \\ there""#), r#"say "hi" \ there"#);
+        assert_eq!(unquote_txt("unquoted value"), "unquoted value");
+        assert_eq!(unquote_txt("\"\""), "");
+    }
+
+    #[actix_rt::test]
+    async fn has_token_matches_the_whole_record() {
+        let resolver = StaticTxtResolver::parse(
+            "example.com=v=spf1 -all, example.com=connecting-opportunities-verification=abc123,other.org=connecting-opportunities-verification=zzz"
+        );
+
+        assert!(has_token(&resolver, "example.com", "abc123").await.unwrap());
+        // Another domain's record, a different token, or an unknown domain
+        assert!(!has_token(&resolver, "example.com", "zzz").await.unwrap());
+        assert!(!has_token(&resolver, "example.com", "def456").await.unwrap());
+        assert!(!has_token(&resolver, "missing.net", "abc123").await.unwrap());
+        // Prefixes of the token, or the token with extra characters, do not count
+        assert!(!has_token(&resolver, "example.com", "abc").await.unwrap());
+        assert!(!has_token(&resolver, "example.com", "abc1234").await.unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn has_token_needs_the_record_prefix() {
+        let resolver = StaticTxtResolver::parse("example.com=abc123,other.org=verification=abc123");
+        assert!(!has_token(&resolver, "example.com", "abc123").await.unwrap());
+        assert!(!has_token(&resolver, "other.org", "abc123").await.unwrap());
+    }
+}
@@ -0,0 +1,381 @@
+use actix_web::{web, HttpResponse, HttpRequest, HttpMessage, Result};
+use sqlx::PgPool;
+use chrono::{DateTime, Duration, Utc};
+
+use crate::client_ip::client_ip;
+use crate::models::{
+    AccountDeletionResponse, ApiResponse, Claims, ConfirmPasswordRequest, LoginRequest, SuspendUserRequest, User,
+};
+use crate::utils::PasswordHasher;
+use crate::config::Config;
+use crate::cache::CacheManager;
+use crate::keys::KeyStore;
+use crate::accounts::{self, AccountStatus};
+use crate::audit::{self, SecurityEventType};
+use crate::revocation::{self, RevocationEvent};
+use crate::handlers::auth::{finish_login, verify_credentials, SessionDevice};
+
+fn user_id(req: &HttpRequest) -> i32 {
+    let extensions = req.extensions();
+    let claims = extensions.get::<Claims>().unwrap();
+    claims.sub.parse().unwrap()
+}
+
+/// Check the signed-in user's password before an account-wide change
+async fn confirm_password(
+    pool: &PgPool,
+    hasher: &PasswordHasher,
+    user_id: i32,
+    password: &str,
+) -> Result<(), HttpResponse> {
+    let current_hash: Option<String> = sqlx::query_scalar(
+        "SELECT password_hash FROM users WHERE id = $1 AND is_active = true"
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(|_| HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+        "Database error"
+    )))?;
+
+    let Some(current_hash) = current_hash else {
+        return Err(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+            "User not found"
+        )));
+    };
+
+    match hasher.verify(password, &current_hash).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Password is incorrect"
+        ))),
+        Err(_) => Err(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            "Password verification failed"
+        ))),
+    }
+}
+
+/// Deactivate the user's own account and sign it out everywhere, optionally
+/// scheduling its deletion
+async fn deactivate(
+    pool: &PgPool,
+    cache: &CacheManager,
+    user_id: i32,
+    deletion_scheduled_at: Option<DateTime<Utc>>,
+) -> Result<(), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        r#"
+        UPDATE users
+        SET is_active = false, deactivated_at = COALESCE(deactivated_at, CURRENT_TIMESTAMP), deletion_scheduled_at = $2
+        WHERE id = $1
+        "#
+    )
+    .bind(user_id)
+    .bind(deletion_scheduled_at)
+    .execute(&mut tx)
+    .await?;
+
+    let events = accounts::revoke_all_access(&mut tx, user_id).await?;
+    tx.commit().await?;
+
+    revocation::publish(pool, cache, &events).await;
+    Ok(())
+}
+
+// Deactivate the authenticated user's account; signing in through /auth/reactivate undoes it
+pub async fn deactivate_account(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheManager>,
+    hasher: web::Data<PasswordHasher>,
+    req: HttpRequest,
+    request_data: web::Json<ConfirmPasswordRequest>,
+) -> Result<HttpResponse> {
+    let user_id = user_id(&req);
+
+    if let Err(error_response) = confirm_password(&pool, &hasher, user_id, &request_data.password).await {
+        return Ok(error_response);
+    }
+
+    if deactivate(&pool, &cache, user_id, None).await.is_err() {
+        return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            "Failed to deactivate account"
+        )));
+    }
+
+    audit::record(
+        &pool,
+        SecurityEventType::AccountDeactivated,
+        Some(user_id),
+        None,
+        client_ip(&req).as_deref(),
+        "Account deactivated by its owner",
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+        "Account deactivated. Sign in through /api/v1/auth/reactivate to restore it"
+    )))
+}
+
+// Deactivate the authenticated user's account and delete it once the grace period is over
+pub async fn delete_account(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    cache: web::Data<CacheManager>,
+    hasher: web::Data<PasswordHasher>,
+    req: HttpRequest,
+    request_data: web::Json<ConfirmPasswordRequest>,
+) -> Result<HttpResponse> {
+    let user_id = user_id(&req);
+
+    if let Err(error_response) = confirm_password(&pool, &hasher, user_id, &request_data.password).await {
+        return Ok(error_response);
+    }
+
+    let deletion_scheduled_at = Utc::now() + Duration::days(config.account_deletion_grace_days);
+    if deactivate(&pool, &cache, user_id, Some(deletion_scheduled_at)).await.is_err() {
+        return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            "Failed to delete account"
+        )));
+    }
+
+    audit::record(
+        &pool,
+        SecurityEventType::AccountDeletionScheduled,
+        Some(user_id),
+        None,
+        client_ip(&req).as_deref(),
+        &format!("Account deletion scheduled for {}", deletion_scheduled_at.to_rfc3339()),
+    )
+    .await;
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(
+        "Account scheduled for deletion. Sign in through /api/v1/auth/reactivate before then to keep it",
+        AccountDeletionResponse { deletion_scheduled_at }
+    )))
+}
+
+// Public endpoint: sign in to a deactivated account, restoring it and cancelling any scheduled deletion
+pub async fn reactivate_account(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    cache: web::Data<CacheManager>,
+    keys: web::Data<KeyStore>,
+    hasher: web::Data<PasswordHasher>,
+    req: HttpRequest,
+    login_data: web::Json<LoginRequest>,
+) -> Result<HttpResponse> {
+    let device = SessionDevice::from_request(&req, login_data.device_label.clone());
+
+    let user = match verify_credentials(&pool, &config, &hasher, &login_data, &device).await {
+        Ok(user) => user,
+        Err(error_response) => return Ok(error_response),
+    };
+
+    match accounts::status(&pool, &cache, user.id).await {
+        Ok(Some(AccountStatus::Deactivated { .. })) => {}
+        Ok(Some(AccountStatus::Active)) => {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+                "Account is not deactivated"
+            )));
+        }
+        Ok(Some(status)) => return Ok(accounts::refused(&status)),
+        Ok(None) => {
+            return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
+                "Invalid credentials"
+            )));
+        }
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    }
+
+    // A suspension placed in the meantime wins
+    let reactivated = sqlx::query_as::<_, User>(
+        r#"
+        UPDATE users SET is_active = true, deactivated_at = NULL, deletion_scheduled_at = NULL
+        WHERE id = $1 AND suspended_at IS NULL
+        RETURNING id, username, email, password_hash, first_name, last_name, phone, role, professional_role, company_name, is_active, email_verified, created_at, updated_at
+        "#
+    )
+    .bind(user.id)
+    .fetch_optional(pool.get_ref())
+    .await;
+
+    let user = match reactivated {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(
+                "Account could not be reactivated"
+            )));
+        }
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to reactivate account"
+            )));
+        }
+    };
+
+    cache.invalidate_user(user.id).await;
+    audit::record(
+        &pool,
+        SecurityEventType::AccountReactivated,
+        Some(user.id),
+        None,
+        device.ip_address.as_deref(),
+        "Account reactivated by its owner",
+    )
+    .await;
+
+    Ok(finish_login(&pool, &config, &keys, user, device).await)
+}
+
+// Admin endpoint: suspend an account, revoking all of its tokens and sessions right away
+pub async fn suspend_user(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheManager>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+    request_data: web::Json<SuspendUserRequest>,
+) -> Result<HttpResponse> {
+    let admin_id = user_id(&req);
+    let user_id = path.into_inner();
+
+    let reason = request_data.reason.trim();
+    if reason.is_empty() || reason.len() > 500 {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "A reason of at most 500 characters is required"
+        )));
+    }
+
+    if request_data.until.is_some_and(|until| until <= Utc::now()) {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "until must be in the future"
+        )));
+    }
+
+    if user_id == admin_id {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "You cannot suspend yourself"
+        )));
+    }
+
+    let result: Result<Option<Vec<RevocationEvent>>, sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+
+        let updated = sqlx::query(
+            r#"
+            UPDATE users
+            SET is_active = false, suspended_at = COALESCE(suspended_at, CURRENT_TIMESTAMP),
+                suspended_until = $2, suspension_reason = $3, suspended_by = $4
+            WHERE id = $1
+            "#
+        )
+        .bind(user_id)
+        .bind(request_data.until)
+        .bind(reason)
+        .bind(admin_id)
+        .execute(&mut tx)
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let events = accounts::revoke_all_access(&mut tx, user_id).await?;
+        tx.commit().await?;
+        Ok(Some(events))
+    }
+    .await;
+
+    match result {
+        Ok(Some(events)) => {
+            revocation::publish(&pool, &cache, &events).await;
+
+            let until = request_data.until
+                .map(|until| until.to_rfc3339())
+                .unwrap_or_else(|| "lifted".to_string());
+            audit::record(
+                &pool,
+                SecurityEventType::AccountSuspended,
+                Some(user_id),
+                Some(admin_id),
+                client_ip(&req).as_deref(),
+                &format!("Suspended until {until}: {reason}"),
+            )
+            .await;
+
+            Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "User suspended successfully"
+            )))
+        }
+        Ok(None) => {
+            Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+                "User not found"
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to suspend user"
+            )))
+        }
+    }
+}
+
+// Admin endpoint: lift a suspension before its end date
+pub async fn unsuspend_user(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheManager>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let admin_id = user_id(&req);
+    let user_id = path.into_inner();
+
+    // Accounts their owner deactivated stay deactivated
+    let lifted: Result<Option<i32>, sqlx::Error> = sqlx::query_scalar(
+        r#"
+        UPDATE users
+        SET is_active = (deactivated_at IS NULL), suspended_at = NULL, suspended_until = NULL,
+            suspension_reason = NULL, suspended_by = NULL
+        WHERE id = $1 AND suspended_at IS NOT NULL
+        RETURNING id
+        "#
+    )
+    .bind(user_id)
+    .fetch_optional(pool.get_ref())
+    .await;
+
+    match lifted {
+        Ok(Some(_)) => {
+            cache.invalidate_user(user_id).await;
+            audit::record(
+                &pool,
+                SecurityEventType::AccountUnsuspended,
+                Some(user_id),
+                Some(admin_id),
+                client_ip(&req).as_deref(),
+                "Suspension lifted by an admin",
+            )
+            .await;
+
+            Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "Suspension lifted successfully"
+            )))
+        }
+        Ok(None) => {
+            Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+                "No suspended user with this id"
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to lift suspension"
+            )))
+        }
+    }
+}
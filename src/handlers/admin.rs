@@ -0,0 +1,553 @@
+use actix_web::{web, HttpResponse, HttpRequest, HttpMessage, Resource, Result, Scope};
+use actix_web::body::{BoxBody, EitherBody};
+use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+use sqlx::PgPool;
+use chrono::{Duration, Utc};
+
+use crate::client_ip::client_ip;
+use crate::models::{
+    AcceptAdminInviteRequest, AdminInvite, ApiResponse, Claims, CreateAdminInviteRequest,
+    SecurityEvent, SecurityEventQuery, UpdateRoleRequest, User,
+};
+use crate::middleware::RequirePermission;
+use crate::permissions::{self, Permission, RolePermissions};
+use crate::utils::{generate_token, hash_token, PasswordHasher};
+use crate::config::Config;
+use crate::cache::CacheManager;
+use crate::mailer::Mailer;
+use crate::revocation::{self, RevocationEvent};
+use crate::audit::{self, SecurityEventType};
+use crate::throttle::{self, ThrottleKey};
+use crate::keys::KeyStore;
+use crate::password_policy::{self, PasswordOwner, PasswordPolicy};
+use crate::handlers::auth::{cleanup_sessions, create_session, SessionDevice};
+use crate::handlers::{accounts, impersonation, keys, roles};
+
+// Invites are valid for 7 days
+const ADMIN_INVITE_EXPIRATION_DAYS: i64 = 7;
+
+// Role given to accounts created from an invite
+const ADMIN_ROLE: &str = "admin";
+
+fn admin_id(req: &HttpRequest) -> i32 {
+    let extensions = req.extensions();
+    let claims = extensions.get::<Claims>().unwrap();
+    claims.sub.parse().unwrap()
+}
+
+pub async fn create_invite(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    cache: web::Data<CacheManager>,
+    mailer: web::Data<Mailer>,
+    req: HttpRequest,
+    invite_data: web::Json<CreateAdminInviteRequest>,
+) -> Result<HttpResponse> {
+    let email = invite_data.email.trim();
+    if email.is_empty() || !email.contains('@') {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Valid email is required"
+        )));
+    }
+
+    // An invite creates an admin, so only someone with every admin permission may send one
+    let caller = req.extensions().get::<RolePermissions>().cloned().unwrap();
+    match permissions::load(&pool, &cache, ADMIN_ROLE).await {
+        Ok(Some(admin)) if caller.missing(&admin.permissions).is_empty() => {}
+        Ok(_) => {
+            return Ok(HttpResponse::Forbidden().json(ApiResponse::<()>::error(
+                "Only users with every admin permission can invite admins"
+            )));
+        }
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    }
+
+    let token = generate_token();
+    let expires_at = Utc::now() + Duration::days(ADMIN_INVITE_EXPIRATION_DAYS);
+
+    let invite_result = sqlx::query_as::<_, AdminInvite>(
+        r#"
+        INSERT INTO admin_invites (email, token_hash, invited_by, expires_at)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id, email, invited_by, expires_at, created_at
+        "#
+    )
+    .bind(email)
+    .bind(hash_token(&token))
+    .bind(admin_id(&req))
+    .bind(expires_at)
+    .fetch_one(pool.get_ref())
+    .await;
+
+    let invite = match invite_result {
+        Ok(invite) => invite,
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to create invite"
+            )));
+        }
+    };
+
+    let invite_link = format!("{}/accept-invite?token={token}", config.app_base_url);
+    let body = format!(
+        "You have been invited to become an administrator.\n\n\
+         Use the link below to create your admin account. It expires in {ADMIN_INVITE_EXPIRATION_DAYS} days.\n\n\
+         {invite_link}"
+    );
+    if let Err(e) = mailer.send(email, "You're invited to become an administrator", &body).await {
+        log::error!("Failed to send admin invite {}: {e}", invite.id);
+        return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            "Failed to send invite email"
+        )));
+    }
+
+    log::info!("Admin {} invited {} to become an admin", admin_id(&req), invite.email);
+
+    Ok(HttpResponse::Created().json(ApiResponse::success(
+        "Invite sent successfully",
+        invite
+    )))
+}
+
+pub async fn list_invites(pool: web::Data<PgPool>) -> Result<HttpResponse> {
+    let invites_result = sqlx::query_as::<_, AdminInvite>(
+        r#"
+        SELECT id, email, invited_by, expires_at, created_at
+        FROM admin_invites
+        WHERE accepted_at IS NULL AND revoked_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+        ORDER BY created_at DESC
+        "#
+    )
+    .fetch_all(pool.get_ref())
+    .await;
+
+    match invites_result {
+        Ok(invites) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Invites retrieved successfully",
+                invites
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )))
+        }
+    }
+}
+
+pub async fn revoke_invite(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let revoke_result = sqlx::query(
+        "UPDATE admin_invites SET revoked_at = CURRENT_TIMESTAMP WHERE id = $1 AND accepted_at IS NULL AND revoked_at IS NULL"
+    )
+    .bind(path.into_inner())
+    .execute(pool.get_ref())
+    .await;
+
+    match revoke_result {
+        Ok(result) if result.rows_affected() > 0 => {
+            Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "Invite revoked successfully"
+            )))
+        }
+        Ok(_) => {
+            Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+                "Invite not found"
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to revoke invite"
+            )))
+        }
+    }
+}
+
+// Public endpoint: create an admin account from an invite token
+pub async fn accept_invite(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    keys: web::Data<KeyStore>,
+    hasher: web::Data<PasswordHasher>,
+    policy: web::Data<PasswordPolicy>,
+    req: HttpRequest,
+    invite_data: web::Json<AcceptAdminInviteRequest>,
+) -> Result<HttpResponse> {
+    if invite_data.username.trim().is_empty() || invite_data.username.len() < 3 {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Username must be at least 3 characters long"
+        )));
+    }
+
+    // Look the invite up first so a rejected password leaves it usable
+    let invite_email: Option<String> = match sqlx::query_scalar(
+        "SELECT email FROM admin_invites WHERE token_hash = $1 AND accepted_at IS NULL AND revoked_at IS NULL AND expires_at > CURRENT_TIMESTAMP"
+    )
+    .bind(hash_token(&invite_data.token))
+    .fetch_optional(pool.get_ref())
+    .await
+    {
+        Ok(email) => email,
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    };
+
+    let Some(invite_email) = invite_email else {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Invalid or expired invite"
+        )));
+    };
+
+    let owner = PasswordOwner { username: &invite_data.username, email: &invite_email };
+    if let Err(errors) = policy.check("password", &invite_data.password, owner).await {
+        return Ok(password_policy::rejected(errors));
+    }
+
+    let password_hash = match hasher.hash(&invite_data.password).await {
+        Ok(hash) => hash,
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to hash password"
+            )));
+        }
+    };
+
+    let result: Result<Option<User>, sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+
+        let invite: Option<(i32, String)> = sqlx::query_as(
+            r#"
+            UPDATE admin_invites SET accepted_at = CURRENT_TIMESTAMP
+            WHERE token_hash = $1 AND accepted_at IS NULL AND revoked_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+            RETURNING id, email
+            "#
+        )
+        .bind(hash_token(&invite_data.token))
+        .fetch_optional(&mut tx)
+        .await?;
+
+        let Some((invite_id, email)) = invite else {
+            return Ok(None);
+        };
+
+        // The invite was delivered to this address, so it counts as verified
+        let user = sqlx::query_as::<_, User>(
+            r#"
+            INSERT INTO users (username, email, password_hash, first_name, last_name, role, email_verified)
+            VALUES ($1, $2, $3, $4, $5, $6, true)
+            RETURNING id, username, email, password_hash, first_name, last_name, phone, role, professional_role, company_name, is_active, email_verified, created_at, updated_at
+            "#
+        )
+        .bind(invite_data.username.trim())
+        .bind(&email)
+        .bind(&password_hash)
+        .bind(&invite_data.first_name)
+        .bind(&invite_data.last_name)
+        .bind(ADMIN_ROLE)
+        .fetch_one(&mut tx)
+        .await?;
+
+        sqlx::query("UPDATE admin_invites SET accepted_user_id = $1 WHERE id = $2")
+            .bind(user.id)
+            .bind(invite_id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(Some(user))
+    }
+    .await;
+
+    match result {
+        Ok(Some(user)) => {
+            log::info!("Admin invite accepted by user {}", user.id);
+
+            match create_session(&pool, &config, &keys, user, SessionDevice::from_request(&req, None)).await {
+                Ok(response) => {
+                    Ok(HttpResponse::Created().json(ApiResponse::success(
+                        "Admin account created successfully",
+                        response
+                    )))
+                }
+                Err(error_response) => Ok(error_response),
+            }
+        }
+        Ok(None) => {
+            Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+                "Invalid or expired invite"
+            )))
+        }
+        Err(sqlx::Error::Database(db_err)) if db_err.constraint().is_some() => {
+            Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(
+                "Username or email already exists"
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to accept invite"
+            )))
+        }
+    }
+}
+
+// Change a user's role; existing tokens carrying the old role are revoked
+pub async fn update_user_role(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheManager>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+    role_data: web::Json<UpdateRoleRequest>,
+) -> Result<HttpResponse> {
+    let user_id = path.into_inner();
+    let caller = req.extensions().get::<RolePermissions>().cloned().unwrap();
+
+    let role = match permissions::load(&pool, &cache, &role_data.role).await {
+        Ok(Some(role)) => role,
+        Ok(None) => {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+                "Unknown role"
+            )));
+        }
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    };
+
+    // Stop admins from locking themselves out
+    if user_id == admin_id(&req) && !role.grants(Permission::RolesAssign) {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "You cannot give up your own permission to assign roles"
+        )));
+    }
+
+    let missing = caller.missing(&role.permissions);
+    if !missing.is_empty() {
+        return Ok(HttpResponse::Forbidden().json(ApiResponse::<()>::error(&format!(
+            "You cannot assign a role with permissions you do not have: {}",
+            missing.join(", ")
+        ))));
+    }
+
+    // Nor demote someone who can do more than the caller
+    let current_role: Option<String> = match sqlx::query_scalar("SELECT role FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(pool.get_ref())
+        .await
+    {
+        Ok(current_role) => current_role,
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    };
+    let Some(current_role) = current_role else {
+        return Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+            "User not found"
+        )));
+    };
+    if let Ok(Some(current)) = permissions::load(&pool, &cache, &current_role).await {
+        if !caller.missing(&current.permissions).is_empty() {
+            return Ok(HttpResponse::Forbidden().json(ApiResponse::<()>::error(
+                "You cannot change the role of a user who has permissions you do not have"
+            )));
+        }
+    }
+
+    let update_result = sqlx::query(
+        "UPDATE users SET role = $1, tokens_invalid_before = CURRENT_TIMESTAMP WHERE id = $2"
+    )
+    .bind(&role.role)
+    .bind(user_id)
+    .execute(pool.get_ref())
+    .await;
+
+    match update_result {
+        Ok(result) if result.rows_affected() > 0 => {
+            revocation::publish(&pool, &cache, &[RevocationEvent::User(user_id)]).await;
+            let ip_address = client_ip(&req);
+            audit::record(
+                &pool,
+                SecurityEventType::RoleAssigned,
+                Some(user_id),
+                Some(admin_id(&req)),
+                ip_address.as_deref(),
+                &format!("Role changed from {current_role} to {}", role.role),
+            )
+            .await;
+
+            Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "Role updated successfully"
+            )))
+        }
+        Ok(_) => {
+            Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+                "User not found"
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to update role"
+            )))
+        }
+    }
+}
+
+// Lift a login lockout early, e.g. after the owner confirmed their identity
+pub async fn unlock_user(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let user_id = path.into_inner();
+
+    let exists: Result<bool, sqlx::Error> = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM users WHERE id = $1)")
+        .bind(user_id)
+        .fetch_one(pool.get_ref())
+        .await;
+
+    match exists {
+        Ok(true) => {}
+        Ok(false) => {
+            return Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+                "User not found"
+            )));
+        }
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    }
+
+    match throttle::clear(&pool, &ThrottleKey::User(user_id)).await {
+        Ok(_) => {
+            let ip_address = client_ip(&req);
+            audit::record(
+                &pool,
+                SecurityEventType::AccountUnlocked,
+                Some(user_id),
+                Some(admin_id(&req)),
+                ip_address.as_deref(),
+                "Login lockout cleared by an admin",
+            )
+            .await;
+
+            Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "Account unlocked successfully"
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to unlock account"
+            )))
+        }
+    }
+}
+
+pub async fn list_security_events(
+    pool: web::Data<PgPool>,
+    query: web::Query<SecurityEventQuery>,
+) -> Result<HttpResponse> {
+    let limit = query.limit.unwrap_or(100).clamp(1, 500);
+
+    let events_result = sqlx::query_as::<_, SecurityEvent>(
+        r#"
+        SELECT id, event_type, user_id, actor_id, ip_address, details, created_at
+        FROM security_events
+        WHERE ($1::INTEGER IS NULL OR user_id = $1) AND ($2::VARCHAR IS NULL OR event_type = $2)
+        ORDER BY created_at DESC
+        LIMIT $3
+        "#
+    )
+    .bind(query.user_id)
+    .bind(&query.event_type)
+    .bind(limit)
+    .fetch_all(pool.get_ref())
+    .await;
+
+    match events_result {
+        Ok(events) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Security events retrieved successfully",
+                events
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )))
+        }
+    }
+}
+
+pub fn admin_routes() -> Scope {
+    web::scope("/admin")
+        .service(guarded("/invites", Permission::InvitesManage)
+            .route(web::post().to(create_invite))
+            .route(web::get().to(list_invites)))
+        .service(guarded("/invites/{id}", Permission::InvitesManage)
+            .route(web::delete().to(revoke_invite)))
+        .service(guarded("/roles", Permission::RolesManage)
+            .route(web::get().to(roles::list_roles))
+            .route(web::post().to(roles::create_role)))
+        .service(guarded("/roles/{name}", Permission::RolesManage)
+            .route(web::delete().to(roles::delete_role)))
+        .service(guarded("/roles/{name}/permissions", Permission::RolesManage)
+            .route(web::put().to(roles::update_role_permissions)))
+        .service(guarded("/permissions", Permission::RolesManage)
+            .route(web::get().to(roles::list_permissions)))
+        .service(guarded("/users/{id}/role", Permission::RolesAssign)
+            .route(web::put().to(update_user_role)))
+        .service(guarded("/users/{id}/permissions/{permission}", Permission::RolesManage)
+            .route(web::get().to(roles::check_user_permission)))
+        .service(guarded("/users/{id}/unlock", Permission::UsersUnlock)
+            .route(web::post().to(unlock_user)))
+        .service(guarded("/users/{id}/suspend", Permission::UsersSuspend)
+            .route(web::post().to(accounts::suspend_user)))
+        .service(guarded("/users/{id}/unsuspend", Permission::UsersSuspend)
+            .route(web::post().to(accounts::unsuspend_user)))
+        .service(guarded("/users/{id}/impersonate", Permission::UsersImpersonate)
+            .route(web::post().to(impersonation::start_impersonation)))
+        .service(guarded("/impersonations", Permission::AuditRead)
+            .route(web::get().to(impersonation::list_impersonations)))
+        .service(guarded("/impersonations/{id}", Permission::UsersImpersonate)
+            .route(web::delete().to(impersonation::stop_impersonation)))
+        .service(guarded("/impersonations/{id}/events", Permission::AuditRead)
+            .route(web::get().to(impersonation::list_impersonation_events)))
+        .service(guarded("/security-events", Permission::AuditRead)
+            .route(web::get().to(list_security_events)))
+        .service(guarded("/signing-keys/rotate", Permission::SigningKeysRotate)
+            .route(web::post().to(keys::rotate_signing_key)))
+        .service(guarded("/cleanup-sessions", Permission::MaintenanceRun)
+            .route(web::post().to(cleanup_sessions)))
+}
+
+/// A resource only users whose role grants `permission` can reach. Every method
+/// on one path shares a permission, since actix matches the path before the method.
+fn guarded(
+    path: &str,
+    permission: Permission,
+) -> Resource<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<EitherBody<BoxBody>>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    web::resource(path).wrap(RequirePermission::new(permission))
+}
@@ -0,0 +1,276 @@
+use actix_web::{web, HttpResponse, HttpRequest, HttpMessage, Result};
+use sqlx::PgPool;
+use chrono::{Duration, Utc};
+
+use crate::client_ip::client_ip;
+use crate::models::{ApiKeyInfo, ApiResponse, Claims, CreateApiKeyRequest, CreatedApiKey};
+use crate::config::Config;
+use crate::api_keys::{self, COMPANY_SCOPES, SCOPES};
+use crate::audit::{self, SecurityEventType};
+use crate::handlers::companies::owned_company;
+
+/// Active keys a user can hold for themselves, and a company can hold
+const MAX_KEYS_PER_OWNER: i64 = 25;
+
+const KEY_COLUMNS: &str =
+    "id, company_id, user_id AS created_by, name, key_prefix, scopes, expires_at, last_used_at, last_used_ip, created_at";
+
+fn user_id(req: &HttpRequest) -> i32 {
+    let extensions = req.extensions();
+    let claims = extensions.get::<Claims>().unwrap();
+    claims.sub.parse().unwrap()
+}
+
+fn database_error() -> HttpResponse {
+    HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+        "Database error"
+    ))
+}
+
+/// Create a key for the caller, or for `company_id` when given; the key is only ever returned here
+async fn create_key(
+    pool: &PgPool,
+    config: &Config,
+    req: &HttpRequest,
+    company_id: Option<i32>,
+    key_data: CreateApiKeyRequest,
+) -> HttpResponse {
+    let user_id = user_id(req);
+
+    let name = key_data.name.trim();
+    if name.is_empty() || name.chars().count() > 100 {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Name must be between 1 and 100 characters"
+        ));
+    }
+
+    if key_data.scopes.is_empty() {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "At least one scope is required"
+        ));
+    }
+    let allowed_scopes = if company_id.is_some() { COMPANY_SCOPES } else { SCOPES };
+    if let Some(unknown) = key_data.scopes.iter().find(|scope| !allowed_scopes.contains(&scope.as_str())) {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(&format!(
+            "Unknown scope {unknown}; must be one of: {}",
+            allowed_scopes.join(", ")
+        )));
+    }
+    let mut scopes = key_data.scopes.clone();
+    scopes.sort();
+    scopes.dedup();
+
+    let lifetime_days = key_data.expires_in_days.unwrap_or(config.api_key_default_lifetime_days);
+    if lifetime_days < 1 || lifetime_days > config.api_key_max_lifetime_days {
+        return HttpResponse::BadRequest().json(ApiResponse::<()>::error(&format!(
+            "expires_in_days must be between 1 and {}",
+            config.api_key_max_lifetime_days
+        )));
+    }
+
+    let active_keys: i64 = match sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) FROM api_keys
+        WHERE (CASE WHEN $2::INTEGER IS NULL THEN user_id = $1 AND company_id IS NULL ELSE company_id = $2 END)
+          AND revoked_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+        "#
+    )
+    .bind(user_id)
+    .bind(company_id)
+    .fetch_one(pool)
+    .await
+    {
+        Ok(count) => count,
+        Err(_) => return database_error(),
+    };
+
+    if active_keys >= MAX_KEYS_PER_OWNER {
+        return HttpResponse::Conflict().json(ApiResponse::<()>::error(&format!(
+            "There can be at most {MAX_KEYS_PER_OWNER} active API keys; revoke one first"
+        )));
+    }
+
+    let new_key = api_keys::generate();
+    let expires_at = Utc::now() + Duration::days(lifetime_days);
+
+    let info_result = sqlx::query_as::<_, ApiKeyInfo>(&format!(
+        r#"
+        INSERT INTO api_keys (user_id, company_id, name, key_prefix, key_hash, scopes, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING {KEY_COLUMNS}
+        "#
+    ))
+    .bind(user_id)
+    .bind(company_id)
+    .bind(name)
+    .bind(&new_key.prefix)
+    .bind(&new_key.hash)
+    .bind(&scopes)
+    .bind(expires_at)
+    .fetch_one(pool)
+    .await;
+
+    match info_result {
+        Ok(info) => {
+            let owner = company_id.map(|company_id| format!(" for company {company_id}")).unwrap_or_default();
+            audit::record(
+                pool,
+                SecurityEventType::ApiKeyCreated,
+                Some(user_id),
+                None,
+                client_ip(req).as_deref(),
+                &format!("API key {} ({}) created{owner} with scopes {}", info.id, info.key_prefix, scopes.join(" ")),
+            )
+            .await;
+
+            HttpResponse::Created().json(ApiResponse::success(
+                "API key created. Copy it now; it will not be shown again",
+                CreatedApiKey { info, key: new_key.key }
+            ))
+        }
+        Err(_) => {
+            HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to create API key"
+            ))
+        }
+    }
+}
+
+async fn list_keys(pool: &PgPool, user_id: i32, company_id: Option<i32>) -> HttpResponse {
+    let keys_result = sqlx::query_as::<_, ApiKeyInfo>(&format!(
+        r#"
+        SELECT {KEY_COLUMNS}
+        FROM api_keys
+        WHERE (CASE WHEN $2::INTEGER IS NULL THEN user_id = $1 AND company_id IS NULL ELSE company_id = $2 END)
+          AND revoked_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+        ORDER BY created_at DESC
+        "#
+    ))
+    .bind(user_id)
+    .bind(company_id)
+    .fetch_all(pool)
+    .await;
+
+    match keys_result {
+        Ok(keys) => {
+            HttpResponse::Ok().json(ApiResponse::success(
+                "API keys retrieved successfully",
+                keys
+            ))
+        }
+        Err(_) => database_error(),
+    }
+}
+
+/// Revoke one of the caller's own keys, or one of `company_id`'s; it stops working on the next request
+async fn revoke_key(pool: &PgPool, req: &HttpRequest, company_id: Option<i32>, key_id: i32) -> HttpResponse {
+    let user_id = user_id(req);
+
+    let result = sqlx::query(
+        r#"
+        UPDATE api_keys SET revoked_at = CURRENT_TIMESTAMP
+        WHERE id = $1 AND revoked_at IS NULL
+          AND (CASE WHEN $3::INTEGER IS NULL THEN user_id = $2 AND company_id IS NULL ELSE company_id = $3 END)
+        "#
+    )
+    .bind(key_id)
+    .bind(user_id)
+    .bind(company_id)
+    .execute(pool)
+    .await;
+
+    match result {
+        Ok(result) if result.rows_affected() > 0 => {
+            let owner = company_id.map(|company_id| format!(" of company {company_id}")).unwrap_or_default();
+            audit::record(
+                pool,
+                SecurityEventType::ApiKeyRevoked,
+                Some(user_id),
+                None,
+                client_ip(req).as_deref(),
+                &format!("API key {key_id}{owner} revoked"),
+            )
+            .await;
+
+            HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "API key revoked"
+            ))
+        }
+        Ok(_) => {
+            HttpResponse::NotFound().json(ApiResponse::<()>::error(
+                "API key not found"
+            ))
+        }
+        Err(_) => {
+            HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to revoke API key"
+            ))
+        }
+    }
+}
+
+// Create a personal API key; the key is only ever returned in this response
+pub async fn create_api_key(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    key_data: web::Json<CreateApiKeyRequest>,
+) -> Result<HttpResponse> {
+    Ok(create_key(&pool, &config, &req, None, key_data.into_inner()).await)
+}
+
+// List the caller's active personal API keys
+pub async fn list_api_keys(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    Ok(list_keys(&pool, user_id(&req), None).await)
+}
+
+// Revoke one of the caller's personal API keys
+pub async fn revoke_api_key(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    Ok(revoke_key(&pool, &req, None, path.into_inner()).await)
+}
+
+// Create a key for a company's integrations; it acts as the caller, within the company only
+pub async fn create_company_api_key(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+    key_data: web::Json<CreateApiKeyRequest>,
+) -> Result<HttpResponse> {
+    match owned_company(&pool, &req, path.into_inner()).await {
+        Ok(company) => Ok(create_key(&pool, &config, &req, Some(company.id), key_data.into_inner()).await),
+        Err(response) => Ok(response),
+    }
+}
+
+// A company's active API keys, whoever of its owners created them
+pub async fn list_company_api_keys(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    match owned_company(&pool, &req, path.into_inner()).await {
+        Ok(company) => Ok(list_keys(&pool, user_id(&req), Some(company.id)).await),
+        Err(response) => Ok(response),
+    }
+}
+
+// Revoke one of a company's API keys; any owner can
+pub async fn revoke_company_api_key(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse> {
+    let (company_id, key_id) = path.into_inner();
+    match owned_company(&pool, &req, company_id).await {
+        Ok(company) => Ok(revoke_key(&pool, &req, Some(company.id), key_id).await),
+        Err(response) => Ok(response),
+    }
+}
@@ -0,0 +1,356 @@
+use actix_web::{web, HttpResponse, HttpRequest, HttpMessage, Result, Scope};
+use sqlx::PgPool;
+use chrono::Utc;
+
+use crate::models::{
+    ApiResponse, Application, ApplicationListQuery, Claims, CreateApplicationRequest
+};
+use crate::api_keys;
+use crate::config::Config;
+use crate::cache::CacheManager;
+use crate::applications::{self, ApplicationFilter, ApplicationStatus};
+use crate::companies::{self, CompanyRole};
+use crate::jobs::{self, JobStatus};
+use crate::pipelines::{self, StageKind, StageMove};
+use crate::middleware::require_permission;
+use crate::permissions::{Permission, RolePermissions};
+use crate::handlers::jobs::visible_job;
+use crate::handlers::pipelines as pipeline_handlers;
+use crate::handlers::verification::{self, VerifiedAction};
+
+fn user_id(req: &HttpRequest) -> i32 {
+    let extensions = req.extensions();
+    let claims = extensions.get::<Claims>().unwrap();
+    claims.sub.parse().unwrap()
+}
+
+/// Whether the caller may read applications to any job, e.g. an admin
+pub fn can_read_any(req: &HttpRequest) -> bool {
+    api_keys::key_company(req).is_none() && req.extensions()
+        .get::<RolePermissions>()
+        .is_some_and(|permissions| permissions.grants(Permission::ApplicationsReadAny))
+}
+
+fn not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(ApiResponse::<()>::error(
+        "Application not found"
+    ))
+}
+
+fn database_error() -> HttpResponse {
+    HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+        "Database error"
+    ))
+}
+
+/// Validate the list query into a filter on top of `filter`, with the limit and offset
+fn list_filter(
+    query: &ApplicationListQuery,
+    filter: ApplicationFilter,
+) -> Result<(ApplicationFilter, i64, i64), HttpResponse> {
+    if query.status.as_deref().is_some_and(|status| ApplicationStatus::parse(status).is_none()) {
+        return Err(HttpResponse::BadRequest().json(ApiResponse::<()>::error(&format!(
+            "status must be one of: {}",
+            applications::status_names()
+        ))));
+    }
+
+    let filter = ApplicationFilter { stage_id: query.stage_id, status: query.status.clone(), ..filter };
+    Ok((filter, query.limit.unwrap_or(50).clamp(1, 200), query.offset.unwrap_or(0).max(0)))
+}
+
+async fn list_response(pool: &PgPool, filter: ApplicationFilter, limit: i64, offset: i64) -> HttpResponse {
+    match applications::list(pool, &filter, limit, offset).await {
+        Ok(applications) => HttpResponse::Ok().json(ApiResponse::success(
+            "Applications retrieved successfully",
+            applications
+        )),
+        Err(_) => database_error(),
+    }
+}
+
+/// The application if the caller is its applicant or a member of the company it was
+/// sent to, with their role there. Anyone else gets 404, so applications cannot be probed for.
+pub async fn involved_application(
+    pool: &PgPool,
+    req: &HttpRequest,
+    application_id: i32,
+) -> Result<(Application, Option<CompanyRole>), HttpResponse> {
+    let application = match applications::find(pool, application_id).await {
+        Ok(Some(application)) => application,
+        Ok(None) => return Err(not_found()),
+        Err(_) => return Err(database_error()),
+    };
+
+    // A company key only sees applications to its own company, never its creator's own
+    let key_company = api_keys::key_company(req);
+    let role = match key_company {
+        Some(company_id) if company_id != application.company_id => None,
+        _ => match companies::member_role(pool, application.company_id, user_id(req)).await {
+            Ok(role) => role,
+            Err(_) => return Err(database_error()),
+        },
+    };
+    let is_applicant = key_company.is_none() && application.applicant_id == user_id(req);
+    if role.is_some() || is_applicant || can_read_any(req) {
+        Ok((application, role))
+    } else {
+        Err(not_found())
+    }
+}
+
+// Apply to a published job
+pub async fn apply_to_job(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheManager>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+    application_data: web::Json<CreateApplicationRequest>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_permission(&req, Permission::JobsApply) {
+        return Ok(response);
+    }
+
+    let applicant_id = user_id(&req);
+    if let Err(response) = verification::ensure_email_verified(
+        &pool, &cache, &config, applicant_id, VerifiedAction::ApplyToJobs,
+    ).await {
+        return Ok(response);
+    }
+
+    let application_data = application_data.into_inner();
+    let (cover_letter, resume_url) = match applications::validate(application_data.cover_letter, &application_data.resume_url) {
+        Ok(application) => application,
+        Err(message) => return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(&message))),
+    };
+
+    let job = match jobs::find(&pool, path.into_inner()).await {
+        Ok(Some(job)) if job.status != JobStatus::Draft.as_str() => job,
+        Ok(_) => {
+            return Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+                "Job not found"
+            )));
+        }
+        Err(_) => return Ok(database_error()),
+    };
+
+    let accepting = job.status == JobStatus::Published.as_str()
+        && job.expires_at.is_none_or(|expires_at| expires_at > Utc::now());
+    if !accepting {
+        return Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(
+            "This job is no longer accepting applications"
+        )));
+    }
+    match companies::member_role(&pool, job.company_id, applicant_id).await {
+        Ok(None) => {}
+        Ok(Some(_)) => {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+                "You cannot apply to a job of your own company"
+            )));
+        }
+        Err(_) => return Ok(database_error()),
+    }
+
+    // The unique (job_id, applicant_id) constraint settles concurrent duplicates
+    let insert_result: Result<Option<i32>, sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+        // Removing a pipeline's last active stage is refused, so this always finds one
+        let Some((stage_id, stage_name)) = pipelines::entry_stage(&mut tx, job.id).await? else {
+            return Err(sqlx::Error::RowNotFound);
+        };
+
+        let application_id: Option<i32> = sqlx::query_scalar(
+            r#"
+            INSERT INTO applications (job_id, applicant_id, cover_letter, resume_url, stage_id)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (job_id, applicant_id) DO NOTHING
+            RETURNING id
+            "#
+        )
+        .bind(job.id)
+        .bind(applicant_id)
+        .bind(&cover_letter)
+        .bind(&resume_url)
+        .bind(stage_id)
+        .fetch_optional(&mut tx)
+        .await?;
+
+        if let Some(application_id) = application_id {
+            let entry = StageMove {
+                stage_id,
+                stage_name: &stage_name,
+                kind: StageKind::Active,
+                moved_by: applicant_id,
+                reason: None,
+            };
+            pipelines::record_entry(&mut tx, application_id, &entry).await?;
+        }
+        tx.commit().await?;
+        Ok(application_id)
+    }
+    .await;
+
+    let application_id = match insert_result {
+        Ok(Some(application_id)) => application_id,
+        Ok(None) => {
+            return Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(
+                "You have already applied to this job"
+            )));
+        }
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to submit application"
+            )));
+        }
+    };
+
+    match applications::find(&pool, application_id).await {
+        Ok(Some(application)) => {
+            Ok(HttpResponse::Created().json(ApiResponse::success(
+                "Application submitted",
+                application
+            )))
+        }
+        _ => Ok(database_error()),
+    }
+}
+
+// Applications to one job, for members of its company or an admin
+pub async fn list_job_applications(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+    query: web::Query<ApplicationListQuery>,
+) -> Result<HttpResponse> {
+    let job = match visible_job(&pool, &req, path.into_inner()).await {
+        Ok(job) => job,
+        Err(response) => return Ok(response),
+    };
+
+    let filter = ApplicationFilter { job_id: Some(job.id), ..Default::default() };
+    match list_filter(&query, filter) {
+        Ok((filter, limit, offset)) => Ok(list_response(&pool, filter, limit, offset).await),
+        Err(response) => Ok(response),
+    }
+}
+
+// Applications to the jobs of any of the caller's companies
+pub async fn list_received_applications(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    query: web::Query<ApplicationListQuery>,
+) -> Result<HttpResponse> {
+    let filter = ApplicationFilter {
+        member_id: Some(user_id(&req)),
+        company_id: api_keys::key_company(&req),
+        job_id: query.job_id,
+        ..Default::default()
+    };
+    match list_filter(&query, filter) {
+        Ok((filter, limit, offset)) => Ok(list_response(&pool, filter, limit, offset).await),
+        Err(response) => Ok(response),
+    }
+}
+
+// The caller's own applications and where they stand
+pub async fn list_my_applications(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    query: web::Query<ApplicationListQuery>,
+) -> Result<HttpResponse> {
+    let filter = ApplicationFilter { applicant_id: Some(user_id(&req)), ..Default::default() };
+    match list_filter(&query, filter) {
+        Ok((filter, limit, offset)) => Ok(list_response(&pool, filter, limit, offset).await),
+        Err(response) => Ok(response),
+    }
+}
+
+// One application, for its applicant, members of the company or an admin
+pub async fn get_application(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    match involved_application(&pool, &req, path.into_inner()).await {
+        Ok((application, _)) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Application found",
+                application
+            )))
+        }
+        Err(response) => Ok(response),
+    }
+}
+
+/// Move an application from `from` to `to`, unless its status changed in the meantime
+async fn set_status(
+    pool: &PgPool,
+    application_id: i32,
+    from: ApplicationStatus,
+    to: ApplicationStatus,
+) -> HttpResponse {
+    let update_result = sqlx::query(
+        r#"
+        UPDATE applications
+        SET status = $1, status_changed_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+        WHERE id = $2 AND status = $3
+        "#
+    )
+    .bind(to.as_str())
+    .bind(application_id)
+    .bind(from.as_str())
+    .execute(pool)
+    .await;
+
+    match update_result {
+        Ok(result) if result.rows_affected() == 0 => {
+            HttpResponse::Conflict().json(ApiResponse::<()>::error(
+                "The application changed in the meantime; reload it and try again"
+            ))
+        }
+        Ok(_) => match applications::find(pool, application_id).await {
+            Ok(Some(application)) => HttpResponse::Ok().json(ApiResponse::success(
+                &format!("Application {}", to.as_str()),
+                application
+            )),
+            _ => database_error(),
+        },
+        Err(_) => HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            "Failed to update application"
+        )),
+    }
+}
+
+// Withdraw one of the caller's applications before a decision is made
+pub async fn withdraw_application(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let application = match involved_application(&pool, &req, path.into_inner()).await {
+        Ok((application, _)) if application.applicant_id == user_id(&req) => application,
+        Ok(_) => return Ok(not_found()),
+        Err(response) => return Ok(response),
+    };
+
+    let Some(from) = ApplicationStatus::parse(&application.status).filter(ApplicationStatus::is_open) else {
+        return Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(&format!(
+            "A {} application can no longer be withdrawn",
+            application.status
+        ))));
+    };
+
+    Ok(set_status(&pool, application.id, from, ApplicationStatus::Withdrawn).await)
+}
+
+pub fn application_routes() -> Scope {
+    web::scope("/applications")
+        // Registered before /{id} so "mine" and "received" are not read as ids
+        .route("/mine", web::get().to(list_my_applications))
+        .route("/received", web::get().to(list_received_applications))
+        .route("/{id}", web::get().to(get_application))
+        .route("/{id}/stage", web::put().to(pipeline_handlers::move_application))
+        .route("/{id}/history", web::get().to(pipeline_handlers::application_history))
+        .route("/{id}/withdraw", web::post().to(withdraw_application))
+}
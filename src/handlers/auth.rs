@@ -1,19 +1,37 @@
-use actix_web::{web, HttpResponse, Result, Scope};
+use actix_web::{web, HttpResponse, HttpRequest, Result, Scope};
 use sqlx::{PgPool, Row};
+use validator::Validate;
+use crate::client_ip::client_ip;
 use crate::models::{
-    ApiResponse, LoginRequest, LoginResponse, CreateUserRequest, User, UserPublic, UserSession
+    ApiResponse, LoginRequest, LoginResponse, CreateUserRequest, User, UserSession,
+    ForgotPasswordRequest, ResetPasswordRequest, RefreshTokenRequest, RefreshTokenResponse, TwoFactorChallengeResponse, DEFAULT_ROLE
 };
-use crate::utils::{hash_password, verify_password, generate_jwt};
+use crate::utils::{generate_jwt, generate_challenge_token, generate_token, hash_token, PasswordHasher};
 use crate::config::Config;
 use crate::cache::CacheManager;
 use crate::database;
-use chrono::{Duration, Utc};
+use crate::accounts;
+use crate::api_keys;
+use crate::mailer::Mailer;
+use crate::handlers::{accounts as account_handlers, admin, company_verification, magic_link, oauth, two_factor, verification};
+use crate::revocation::{self, RevocationEvent};
+use crate::throttle::{self, ThrottleKey};
+use crate::keys::KeyStore;
+use crate::permissions;
+use crate::password_policy::{self, PasswordOwner, PasswordPolicy};
+use chrono::{DateTime, Duration, Utc};
 use uuid::Uuid;
 
+#[allow(clippy::too_many_arguments)] // actix extractors
 pub async fn register(
     pool: web::Data<PgPool>,
     cache: web::Data<CacheManager>,
     config: web::Data<Config>,
+    keys: web::Data<KeyStore>,
+    mailer: web::Data<Mailer>,
+    hasher: web::Data<PasswordHasher>,
+    policy: web::Data<PasswordPolicy>,
+    req: HttpRequest,
     user_data: web::Json<CreateUserRequest>,
 ) -> Result<HttpResponse> {
     // Input validation
@@ -29,22 +47,31 @@ pub async fn register(
         )));
     }
     
-    if user_data.password.len() < 8 {
-        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
-            "Password must be at least 8 characters long"
-        )));
+    let owner = PasswordOwner { username: &user_data.username, email: &user_data.email };
+    if let Err(errors) = policy.check("password", &user_data.password, owner).await {
+        return Ok(password_policy::rejected(errors));
     }
     
+    // Roles with more than self-service access (admins and the like) are assigned, never chosen
     if let Some(role) = &user_data.role {
-        if !crate::models::user::is_valid_role(role) {
-            return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
-                "Role must be one of: job_seeker, employer, admin"
-            )));
+        match permissions::self_assignable_roles(&pool).await {
+            Ok(roles) if roles.contains(role) => {}
+            Ok(roles) => {
+                return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(&format!(
+                    "Role must be one of: {}",
+                    roles.join(", ")
+                ))));
+            }
+            Err(_) => {
+                return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                    "Database error"
+                )));
+            }
         }
     }
 
     // Hash password
-    let password_hash = match hash_password(&user_data.password) {
+    let password_hash = match hasher.hash(&user_data.password).await {
         Ok(hash) => hash,
         Err(_) => {
             return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
@@ -67,7 +94,7 @@ pub async fn register(
     .bind(&user_data.first_name)
     .bind(&user_data.last_name)
     .bind(&user_data.phone)
-    .bind(user_data.role.as_deref().unwrap_or("job_seeker"))
+    .bind(user_data.role.as_deref().unwrap_or(DEFAULT_ROLE))
     .bind(&user_data.professional_role)
     .bind(&user_data.company_name)
     .fetch_one(pool.get_ref())
@@ -75,58 +102,22 @@ pub async fn register(
 
     match user_result {
         Ok(user) => {
-            // Generate JWT token for automatic login
-            match generate_jwt(&user, &config.jwt_secret, config.jwt_expiration) {
-                Ok(token) => {
-                    // Create session in database
-                    let session_token = Uuid::new_v4().to_string();
-                    let expires_at = Utc::now() + Duration::seconds(config.jwt_expiration);
-                    
-                    let session_result = sqlx::query_as::<_, UserSession>(
-                        r#"
-                        INSERT INTO user_sessions (user_id, session_token, expires_at)
-                        VALUES ($1, $2, $3)
-                        RETURNING id, user_id, session_token, expires_at, created_at
-                        "#
-                    )
-                    .bind(user.id)
-                    .bind(&session_token)
-                    .bind(expires_at)
-                    .fetch_one(pool.get_ref())
-                    .await;
-
-                    match session_result {
-                        Ok(_) => {
-                            let user_public: UserPublic = user.into();
-                            
-                            // Cache the new user for future requests
-                            cache.cache_user(user_public.clone()).await;
-                            
-                            // Return LoginResponse instead of just UserPublic
-                            let response = LoginResponse {
-                                access_token: token,
-                                token_type: "Bearer".to_string(),
-                                expires_in: config.jwt_expiration,
-                                user: user_public,
-                            };
-
-                            Ok(HttpResponse::Created().json(ApiResponse::success(
-                                "User registered and logged in successfully",
-                                response
-                            )))
-                        }
-                        Err(_) => {
-                            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                                "Failed to create session"
-                            )))
-                        }
-                    }
-                }
-                Err(_) => {
-                    Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                        "Failed to generate token"
+            verification::spawn_verification_email(
+                pool.clone(), config.clone(), mailer.clone(), user.id, user.email.clone()
+            );
+
+            // Start a session so the user is logged in automatically
+            match create_session(&pool, &config, &keys, user, SessionDevice::from_request(&req, None)).await {
+                Ok(response) => {
+                    // Cache the new user for future requests
+                    cache.cache_user(response.user.clone()).await;
+
+                    Ok(HttpResponse::Created().json(ApiResponse::success(
+                        "User registered and logged in successfully",
+                        response
                     )))
                 }
+                Err(error_response) => Ok(error_response),
             }
         }
         Err(sqlx::Error::Database(db_err)) if db_err.constraint().is_some() => {
@@ -144,28 +135,61 @@ pub async fn register(
 
 pub async fn login(
     pool: web::Data<PgPool>,
-    _config: web::Data<Config>,
+    config: web::Data<Config>,
+    cache: web::Data<CacheManager>,
+    keys: web::Data<KeyStore>,
+    hasher: web::Data<PasswordHasher>,
+    req: HttpRequest,
     login_data: web::Json<LoginRequest>,
 ) -> Result<HttpResponse> {
+    let device = SessionDevice::from_request(&req, login_data.device_label.clone());
+
+    let mut user = match verify_credentials(&pool, &config, &hasher, &login_data, &device).await {
+        Ok(user) => user,
+        Err(error_response) => return Ok(error_response),
+    };
+
+    // Only the owner of the password learns that the account is deactivated or suspended
+    if let Err(error_response) = accounts::ensure_can_sign_in(&pool, &cache, &mut user).await {
+        return Ok(error_response);
+    }
+
+    Ok(finish_login(&pool, &config, &keys, user, device).await)
+}
+
+/// Check a username or email and password, with brute-force throttling.
+/// Inactive accounts are returned too; callers decide what they may do.
+pub(crate) async fn verify_credentials(
+    pool: &web::Data<PgPool>,
+    config: &Config,
+    hasher: &web::Data<PasswordHasher>,
+    login_data: &LoginRequest,
+    device: &SessionDevice,
+) -> Result<User, HttpResponse> {
     // Input validation
     if login_data.username_or_email.trim().is_empty() {
-        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+        return Err(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
             "Username or email is required"
         )));
     }
     
     if login_data.password.trim().is_empty() {
-        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+        return Err(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
             "Password is required"
         )));
     }
 
+    let ip_key = device.ip_address.clone().map(ThrottleKey::Ip);
+
+    // Refuse throttled sources before doing any work
+    throttle::check(pool, ip_key.as_slice()).await?;
+
     // Use optimized database query
-    let user_result = database::get_user_by_email_or_username_optimized(&pool, &login_data.username_or_email).await;
+    let user_result = database::get_user_by_email_or_username_optimized(pool, &login_data.username_or_email).await;
 
     let user = match user_result {
         Ok(Some(row)) => {
-            User {
+            Some(User {
                 id: row.get("id"),
                 username: row.get("username"),
                 email: row.get("email"),
@@ -180,114 +204,390 @@ pub async fn login(
                 email_verified: row.get("email_verified"),
                 created_at: row.get("created_at"),
                 updated_at: row.get("updated_at"),
-            }
-        }
-        Ok(None) => {
-            return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
-                "Invalid credentials"
-            )));
+            })
         }
+        Ok(None) => None,
         Err(_) => {
-            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            return Err(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
                 "Database error"
             )));
         }
     };
 
-    // Check if user is active
-    if !user.is_active {
-        return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
-            "Account is deactivated"
+    let account_key = match &user {
+        Some(user) => ThrottleKey::User(user.id),
+        None => ThrottleKey::login(&login_data.username_or_email),
+    };
+    let throttle_keys: Vec<ThrottleKey> = std::iter::once(account_key.clone()).chain(ip_key).collect();
+
+    // Checked before hashing so a locked account costs nothing to refuse
+    throttle::check(pool, &throttle_keys).await?;
+
+    let Some(user) = user else {
+        throttle::record_failure(pool, config, &throttle_keys, None, device.ip_address.as_deref()).await;
+        return Err(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
+            "Invalid credentials"
         )));
-    }
+    };
 
     // Verify password
-    match verify_password(&login_data.password, &user.password_hash) {
+    match hasher.verify(&login_data.password, &user.password_hash).await {
         Ok(true) => {
-            // Generate JWT token
-            match generate_jwt(&user, &_config.jwt_secret, _config.jwt_expiration) {
-                Ok(token) => {
-                    // Create session in database
-                    let session_token = Uuid::new_v4().to_string();
-                    let expires_at = Utc::now() + Duration::seconds(_config.jwt_expiration);
-                    
-                    let session_result = sqlx::query_as::<_, UserSession>(
-                        r#"
-                        INSERT INTO user_sessions (user_id, session_token, expires_at)
-                        VALUES ($1, $2, $3)
-                        RETURNING id, user_id, session_token, expires_at, created_at
-                        "#
-                    )
-                    .bind(user.id)
-                    .bind(&session_token)
-                    .bind(expires_at)
-                    .fetch_one(pool.get_ref())
-                    .await;
-
-                    match session_result {
-                        Ok(_) => {
-                            let user_public: UserPublic = user.into();
-                            let response = LoginResponse {
-                                access_token: token,
-                                token_type: "Bearer".to_string(),
-                                expires_in: _config.jwt_expiration,
-                                user: user_public,
-                            };
-
-                            Ok(HttpResponse::Ok().json(ApiResponse::success(
-                                "Login successful",
-                                response
-                            )))
-                        }
-                        Err(_) => {
-                            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                                "Failed to create session"
-                            )))
+            if let Err(e) = throttle::clear(pool, &account_key).await {
+                log::error!("Failed to reset login throttle for user {}: {e}", user.id);
+            }
+            if hasher.needs_rehash(&user.password_hash) {
+                spawn_rehash(pool.clone(), hasher.clone(), user.id, login_data.password.clone(), user.password_hash.clone());
+            }
+            Ok(user)
+        }
+        Ok(false) => {
+            throttle::record_failure(pool, config, &throttle_keys, Some(user.id), device.ip_address.as_deref()).await;
+            Err(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
+                "Invalid credentials"
+            )))
+        }
+        Err(_) => {
+            Err(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Password verification failed"
+            )))
+        }
+    }
+}
+
+/// Upgrade an outdated password hash now that the plain password is known.
+/// Runs in the background so the login does not wait for a second hash; the
+/// update only applies if the password has not changed in the meantime.
+fn spawn_rehash(pool: web::Data<PgPool>, hasher: web::Data<PasswordHasher>, user_id: i32, password: String, old_hash: String) {
+    actix_web::rt::spawn(async move {
+        let result: anyhow::Result<()> = async {
+            let new_hash = hasher.hash(&password).await?;
+            sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2 AND password_hash = $3")
+                .bind(&new_hash)
+                .bind(user_id)
+                .bind(&old_hash)
+                .execute(pool.get_ref())
+                .await?;
+            Ok(())
+        }
+        .await;
+
+        if let Err(e) = result {
+            log::error!("Failed to upgrade password hash for user {user_id}: {e}");
+        }
+    });
+}
+
+/// Device details recorded with a session so users can tell their sessions apart
+pub(crate) struct SessionDevice {
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub device_label: Option<String>,
+}
+
+impl SessionDevice {
+    pub fn from_request(req: &HttpRequest, device_label: Option<String>) -> Self {
+        let user_agent = req.headers()
+            .get("User-Agent")
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(512).collect());
+        let ip_address = client_ip(req);
+        let device_label = device_label
+            .map(|label| label.trim().chars().take(100).collect::<String>())
+            .filter(|label| !label.is_empty());
+
+        Self { user_agent, ip_address, device_label }
+    }
+}
+
+/// Complete a login whose first factor has been checked. Users with two-factor
+/// authentication get a short-lived challenge token instead of a session.
+pub(crate) async fn finish_login(
+    pool: &PgPool,
+    config: &Config,
+    keys: &KeyStore,
+    user: User,
+    device: SessionDevice,
+) -> HttpResponse {
+    match two_factor::is_enabled(pool, user.id).await {
+        Ok(true) => {
+            match generate_challenge_token(user.id, device.device_label, keys, config) {
+                Ok(challenge_token) => {
+                    HttpResponse::Ok().json(ApiResponse::success(
+                        "Two-factor authentication required",
+                        TwoFactorChallengeResponse {
+                            two_factor_required: true,
+                            challenge_token,
+                            expires_in: config.two_factor_challenge_expiration,
                         }
-                    }
+                    ))
                 }
                 Err(_) => {
+                    HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                        "Failed to generate token"
+                    ))
+                }
+            }
+        }
+        Ok(false) => {
+            match create_session(pool, config, keys, user, device).await {
+                Ok(response) => {
+                    HttpResponse::Ok().json(ApiResponse::success(
+                        "Login successful",
+                        response
+                    ))
+                }
+                Err(error_response) => error_response,
+            }
+        }
+        Err(_) => {
+            HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            ))
+        }
+    }
+}
+
+/// Issue an access token and start a new refresh token family for `user`.
+/// Every way of logging in goes through here so sessions are created the same way.
+pub(crate) async fn create_session(
+    pool: &PgPool,
+    config: &Config,
+    keys: &KeyStore,
+    user: User,
+    device: SessionDevice,
+) -> Result<LoginResponse, HttpResponse> {
+    let session_id = Uuid::new_v4();
+    let permissions_version = permissions::version(pool, &user.role)
+        .await
+        .map_err(|_| HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            "Database error"
+        )))?;
+    let access_token = generate_jwt(&user, permissions_version, session_id, keys, config)
+        .map_err(|_| HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            "Failed to generate token"
+        )))?;
+
+    let refresh_token = generate_token();
+    let expires_at = Utc::now() + Duration::seconds(config.refresh_token_expiration);
+
+    sqlx::query(
+        r#"
+        INSERT INTO user_sessions (user_id, session_token, family_id, expires_at, user_agent, ip_address, device_label)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#
+    )
+    .bind(user.id)
+    .bind(hash_token(&refresh_token))
+    .bind(session_id)
+    .bind(expires_at)
+    .bind(&device.user_agent)
+    .bind(&device.ip_address)
+    .bind(&device.device_label)
+    .execute(pool)
+    .await
+    .map_err(|_| HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+        "Failed to create session"
+    )))?;
+
+    Ok(LoginResponse {
+        access_token,
+        token_type: "Bearer".to_string(),
+        expires_in: config.jwt_expiration,
+        refresh_token,
+        user: user.into(),
+    })
+}
+
+enum RefreshOutcome {
+    Rotated(Box<User>, Uuid, String),
+    Invalid,
+    Reused,
+}
+
+// Exchange a refresh token for a new access token, rotating the refresh token
+pub async fn refresh(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    keys: web::Data<KeyStore>,
+    req: HttpRequest,
+    refresh_data: web::Json<RefreshTokenRequest>,
+) -> Result<HttpResponse> {
+    if refresh_data.refresh_token.trim().is_empty() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Refresh token is required"
+        )));
+    }
+
+    let outcome: Result<RefreshOutcome, sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+
+        // Lock the row so two concurrent refreshes cannot both rotate the same token
+        let session = sqlx::query_as::<_, UserSession>(
+            r#"
+            SELECT id, user_id, session_token, family_id, expires_at, revoked_at, replaced_by, created_at
+            FROM user_sessions
+            WHERE session_token = $1
+            FOR UPDATE
+            "#
+        )
+        .bind(hash_token(&refresh_data.refresh_token))
+        .fetch_optional(&mut tx)
+        .await?;
+
+        let Some(session) = session else {
+            return Ok(RefreshOutcome::Invalid);
+        };
+
+        if session.revoked_at.is_some() {
+            // A retired token was presented again: assume it was stolen and kill the whole family
+            if session.replaced_by.is_some() {
+                sqlx::query(
+                    "UPDATE user_sessions SET revoked_at = CURRENT_TIMESTAMP WHERE family_id = $1 AND revoked_at IS NULL"
+                )
+                .bind(session.family_id)
+                .execute(&mut tx)
+                .await?;
+                tx.commit().await?;
+
+                log::warn!(
+                    "Refresh token reuse detected for user {} (family {}), session revoked",
+                    session.user_id, session.family_id
+                );
+                return Ok(RefreshOutcome::Reused);
+            }
+            return Ok(RefreshOutcome::Invalid);
+        }
+
+        if session.expires_at <= Utc::now() {
+            return Ok(RefreshOutcome::Invalid);
+        }
+
+        let user = sqlx::query_as::<_, User>(
+            r#"
+            SELECT id, username, email, password_hash, first_name, last_name, phone, role, professional_role, company_name, is_active, email_verified, created_at, updated_at
+            FROM users
+            WHERE id = $1 AND is_active = true
+            "#
+        )
+        .bind(session.user_id)
+        .fetch_optional(&mut tx)
+        .await?;
+
+        let Some(user) = user else {
+            return Ok(RefreshOutcome::Invalid);
+        };
+
+        let refresh_token = generate_token();
+        let expires_at = Utc::now() + Duration::seconds(config.refresh_token_expiration);
+
+        // The new token carries the device details forward; the label is kept from the original login
+        let device = SessionDevice::from_request(&req, None);
+        let new_session_id: i32 = sqlx::query_scalar(
+            r#"
+            INSERT INTO user_sessions (user_id, session_token, family_id, expires_at, user_agent, ip_address, device_label)
+            SELECT $1, $2, $3, $4, COALESCE($5, user_agent), COALESCE($6, ip_address), device_label
+            FROM user_sessions WHERE id = $7
+            RETURNING id
+            "#
+        )
+        .bind(user.id)
+        .bind(hash_token(&refresh_token))
+        .bind(session.family_id)
+        .bind(expires_at)
+        .bind(&device.user_agent)
+        .bind(&device.ip_address)
+        .bind(session.id)
+        .fetch_one(&mut tx)
+        .await?;
+
+        sqlx::query(
+            "UPDATE user_sessions SET revoked_at = CURRENT_TIMESTAMP, replaced_by = $1 WHERE id = $2"
+        )
+        .bind(new_session_id)
+        .bind(session.id)
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(RefreshOutcome::Rotated(Box::new(user), session.family_id, refresh_token))
+    }
+    .await;
+
+    match outcome {
+        Ok(RefreshOutcome::Rotated(user, session_id, refresh_token)) => {
+            // Picks up the role's current permissions version, so refreshing renews stale tokens
+            let access_token = match permissions::version(&pool, &user.role).await {
+                Ok(permissions_version) => generate_jwt(&user, permissions_version, session_id, &keys, &config).ok(),
+                Err(_) => None,
+            };
+            match access_token {
+                Some(access_token) => {
+                    let response = RefreshTokenResponse {
+                        access_token,
+                        token_type: "Bearer".to_string(),
+                        expires_in: config.jwt_expiration,
+                        refresh_token,
+                    };
+
+                    Ok(HttpResponse::Ok().json(ApiResponse::success(
+                        "Token refreshed successfully",
+                        response
+                    )))
+                }
+                None => {
                     Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
                         "Failed to generate token"
                     )))
                 }
             }
         }
-        Ok(false) => {
+        Ok(RefreshOutcome::Invalid) => {
             Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
-                "Invalid credentials"
+                "Invalid or expired refresh token"
+            )))
+        }
+        Ok(RefreshOutcome::Reused) => {
+            Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
+                "Refresh token has already been used; please log in again"
             )))
         }
         Err(_) => {
             Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
-                "Password verification failed"
+                "Failed to refresh token"
             )))
         }
     }
 }
 
-// Updated logout endpoint to clean up sessions
+// Log out the current session only; other devices stay signed in
 pub async fn logout(
     pool: web::Data<PgPool>,
     config: web::Data<Config>,
+    keys: web::Data<KeyStore>,
     cache: web::Data<CacheManager>,
-    req: actix_web::HttpRequest,
+    req: HttpRequest,
 ) -> Result<HttpResponse> {
     // Extract token from Authorization header
     if let Some(auth_header) = req.headers().get("Authorization") {
         if let Ok(auth_str) = auth_header.to_str() {
             if let Some(token) = auth_str.strip_prefix("Bearer ") {
-                // Add token to blacklist
-                cache.blacklist_token(token.to_string()).await;
-                
-                // Extract user claims to get user_id for session cleanup
-                if let Ok(claims) = crate::utils::jwt::decode_jwt(token, &config.jwt_secret) {
-                    if let Ok(user_id) = claims.sub.parse::<i32>() {
-                        // Delete user sessions from database
-                        let _ = sqlx::query("DELETE FROM user_sessions WHERE user_id = $1")
-                            .bind(user_id)
-                            .execute(pool.get_ref())
-                            .await;
+                // Revoke the token and the session it belongs to, which also retires its refresh token
+                if let Ok(claims) = crate::utils::jwt::decode_jwt(token, &keys, &config) {
+                    if let (Ok(user_id), Ok(session_id), Ok(jti)) = (
+                        claims.sub.parse::<i32>(),
+                        claims.sid.parse::<Uuid>(),
+                        claims.jti.parse::<Uuid>(),
+                    ) {
+                        let expires_at = DateTime::<Utc>::from_timestamp(claims.exp as i64, 0)
+                            .unwrap_or_else(Utc::now);
+                        if revocation::revoke_token(&pool, &cache, jti, user_id, expires_at).await.is_err() {
+                            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                                "Failed to log out"
+                            )));
+                        }
+                        let _ = database::revoke_session(&pool, user_id, session_id).await;
+                        revocation::publish(&pool, &cache, &[RevocationEvent::Session(session_id)]).await;
                     }
                 }
                 
@@ -304,7 +604,40 @@ pub async fn logout(
 }
 
 // New endpoint to clean up expired sessions
-pub async fn cleanup_sessions(pool: web::Data<PgPool>) -> Result<HttpResponse> {
+pub async fn cleanup_sessions(pool: web::Data<PgPool>, config: web::Data<Config>) -> Result<HttpResponse> {
+    // Revocation records are only needed until the token would have expired anyway
+    if sqlx::query("DELETE FROM revoked_tokens WHERE expires_at < CURRENT_TIMESTAMP")
+        .execute(pool.get_ref())
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            "Failed to cleanup sessions"
+        )));
+    }
+
+    if sqlx::query("DELETE FROM oauth_states WHERE expires_at < CURRENT_TIMESTAMP")
+        .execute(pool.get_ref())
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            "Failed to cleanup sessions"
+        )));
+    }
+
+    if magic_link::cleanup(&pool, &config).await.is_err() {
+        return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            "Failed to cleanup sessions"
+        )));
+    }
+
+    if throttle::cleanup(&pool, &config).await.is_err() {
+        return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            "Failed to cleanup sessions"
+        )));
+    }
+
     match sqlx::query("DELETE FROM user_sessions WHERE expires_at < CURRENT_TIMESTAMP")
         .execute(pool.get_ref())
         .await
@@ -323,10 +656,225 @@ pub async fn cleanup_sessions(pool: web::Data<PgPool>) -> Result<HttpResponse> {
     }
 }
 
+// Response shared by every forgot-password request so it cannot be used to probe for accounts
+const FORGOT_PASSWORD_MESSAGE: &str = "If an account with that email exists, a password reset link has been sent";
+
+pub async fn forgot_password(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    mailer: web::Data<Mailer>,
+    request_data: web::Json<ForgotPasswordRequest>,
+) -> Result<HttpResponse> {
+    if request_data.validate().is_err() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Valid email is required"
+        )));
+    }
+
+    // Do the lookup and delivery in the background so the response time is
+    // the same whether or not the account exists
+    let email = request_data.into_inner().email;
+    actix_web::rt::spawn(async move {
+        if let Err(e) = send_password_reset(&pool, &config, &mailer, &email).await {
+            log::error!("Failed to process password reset request: {e}");
+        }
+    });
+
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(FORGOT_PASSWORD_MESSAGE)))
+}
+
+async fn send_password_reset(
+    pool: &PgPool,
+    config: &Config,
+    mailer: &Mailer,
+    email: &str,
+) -> anyhow::Result<()> {
+    let user_id: Option<i32> = sqlx::query_scalar(
+        "SELECT id FROM users WHERE email = $1 AND is_active = true"
+    )
+    .bind(email)
+    .fetch_optional(pool)
+    .await?;
+
+    let Some(user_id) = user_id else {
+        return Ok(());
+    };
+
+    let token = generate_token();
+    let expires_at = Utc::now() + Duration::seconds(config.password_reset_expiration);
+
+    let mut tx = pool.begin().await?;
+
+    // Only the most recently requested link stays usable
+    sqlx::query(
+        "UPDATE password_reset_tokens SET used_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND used_at IS NULL"
+    )
+    .bind(user_id)
+    .execute(&mut tx)
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO password_reset_tokens (user_id, token_hash, expires_at) VALUES ($1, $2, $3)"
+    )
+    .bind(user_id)
+    .bind(hash_token(&token))
+    .bind(expires_at)
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let reset_link = format!("{}/reset-password?token={token}", config.app_base_url);
+    let body = format!(
+        "We received a request to reset your password.\n\n\
+         Use the link below to choose a new one. It expires in {} minutes and can only be used once.\n\n\
+         {reset_link}\n\n\
+         If you did not request this, you can ignore this email.",
+        config.password_reset_expiration / 60
+    );
+    mailer.send(email, "Reset your password", &body).await?;
+
+    Ok(())
+}
+
+pub async fn reset_password(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheManager>,
+    hasher: web::Data<PasswordHasher>,
+    policy: web::Data<PasswordPolicy>,
+    reset_data: web::Json<ResetPasswordRequest>,
+) -> Result<HttpResponse> {
+    // Look the owner up first so a rejected password leaves the link usable
+    let owner: Option<(String, String)> = match sqlx::query_as(
+        r#"
+        SELECT u.username, u.email
+        FROM password_reset_tokens t
+        JOIN users u ON u.id = t.user_id
+        WHERE t.token_hash = $1 AND t.used_at IS NULL AND t.expires_at > CURRENT_TIMESTAMP
+        "#
+    )
+    .bind(hash_token(&reset_data.token))
+    .fetch_optional(pool.get_ref())
+    .await
+    {
+        Ok(owner) => owner,
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    };
+
+    let Some((username, email)) = owner else {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Invalid or expired reset token"
+        )));
+    };
+
+    let owner = PasswordOwner { username: &username, email: &email };
+    if let Err(errors) = policy.check("new_password", &reset_data.new_password, owner).await {
+        return Ok(password_policy::rejected(errors));
+    }
+
+    let password_hash = match hasher.hash(&reset_data.new_password).await {
+        Ok(hash) => hash,
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to hash password"
+            )));
+        }
+    };
+
+    let result: Result<Option<(i32, Vec<Uuid>)>, sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+
+        // Consume the token; the used_at check makes it single-use even under concurrent requests
+        let user_id: Option<i32> = sqlx::query_scalar(
+            r#"
+            UPDATE password_reset_tokens SET used_at = CURRENT_TIMESTAMP
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+            RETURNING user_id
+            "#
+        )
+        .bind(hash_token(&reset_data.token))
+        .fetch_optional(&mut tx)
+        .await?;
+
+        let Some(user_id) = user_id else {
+            return Ok(None);
+        };
+
+        // Update the password and revoke every JWT issued so far
+        sqlx::query(
+            "UPDATE users SET password_hash = $1, has_password = true, tokens_invalid_before = CURRENT_TIMESTAMP WHERE id = $2"
+        )
+        .bind(&password_hash)
+        .bind(user_id)
+        .execute(&mut tx)
+        .await?;
+
+        let revoked_sessions: Vec<Uuid> = sqlx::query_scalar(
+            "DELETE FROM user_sessions WHERE user_id = $1 RETURNING family_id"
+        )
+        .bind(user_id)
+        .fetch_all(&mut tx)
+        .await?;
+
+        api_keys::revoke_all(&mut tx, user_id).await?;
+
+        // Any other outstanding reset links are no longer needed
+        sqlx::query(
+            "UPDATE password_reset_tokens SET used_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND used_at IS NULL"
+        )
+        .bind(user_id)
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok(Some((user_id, revoked_sessions)))
+    }
+    .await;
+
+    match result {
+        Ok(Some((user_id, revoked_sessions))) => {
+            // Make every replica drop the cached cutoff and sessions so the revocation takes effect immediately
+            let mut events = vec![RevocationEvent::User(user_id)];
+            events.extend(revoked_sessions.into_iter().map(RevocationEvent::Session));
+            revocation::publish(&pool, &cache, &events).await;
+
+            Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "Password has been reset successfully"
+            )))
+        }
+        Ok(None) => {
+            Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+                "Invalid or expired reset token"
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to reset password"
+            )))
+        }
+    }
+}
+
 pub fn auth_routes() -> Scope {
     web::scope("/auth")
         .route("/register", web::post().to(register))
         .route("/login", web::post().to(login))
+        .route("/login/2fa", web::post().to(two_factor::login_two_factor))
+        .route("/reactivate", web::post().to(account_handlers::reactivate_account))
         .route("/logout", web::post().to(logout))
-        .route("/cleanup-sessions", web::post().to(cleanup_sessions))
+        .route("/refresh", web::post().to(refresh))
+        .route("/forgot-password", web::post().to(forgot_password))
+        .route("/reset-password", web::post().to(reset_password))
+        .route("/magic-link", web::post().to(magic_link::request_magic_link))
+        .route("/magic-link/login", web::post().to(magic_link::login_with_magic_link))
+        .route("/verify-email", web::post().to(verification::verify_email))
+        .route("/verify-company", web::post().to(company_verification::confirm_email_verification))
+        .route("/accept-invite", web::post().to(admin::accept_invite))
+        .route("/oauth/providers", web::get().to(oauth::list_providers))
+        .route("/oauth/{provider}/authorize", web::post().to(oauth::authorize))
+        .route("/oauth/{provider}/callback", web::post().to(oauth::callback))
 } 
\ No newline at end of file
@@ -0,0 +1,254 @@
+use actix_web::{web, HttpResponse, HttpRequest, HttpMessage, Result, Scope};
+use serde_json::{Map, Value};
+use sqlx::PgPool;
+
+use crate::candidate_profiles::{self, ProfileSection};
+use crate::middleware::require_permission;
+use crate::models::{
+    ApiResponse, CandidateSkill, Certification, Claims, Education, Experience, ProfileLink, ReorderEntriesRequest,
+    SpokenLanguage
+};
+use crate::permissions::{Permission, RolePermissions};
+
+fn user_id(req: &HttpRequest) -> i32 {
+    let extensions = req.extensions();
+    let claims = extensions.get::<Claims>().unwrap();
+    claims.sub.parse().unwrap()
+}
+
+fn entry_not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(ApiResponse::<()>::error(
+        "Profile entry not found"
+    ))
+}
+
+fn database_error() -> HttpResponse {
+    HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+        "Database error"
+    ))
+}
+
+/// 409 for entries that duplicate another entry of the section, 500 for anything else
+fn save_error<S: ProfileSection>(error: sqlx::Error) -> HttpResponse {
+    match error {
+        // unique_violation; check constraints are validated up front, so anything else is unexpected
+        sqlx::Error::Database(db_err) if db_err.code().as_deref() == Some("23505") => {
+            HttpResponse::Conflict().json(ApiResponse::<()>::error(S::DUPLICATE_MESSAGE))
+        }
+        _ => database_error(),
+    }
+}
+
+async fn entry_response<S: ProfileSection>(
+    pool: &PgPool,
+    user_id: i32,
+    entry_id: i32,
+    created: bool,
+) -> HttpResponse {
+    match candidate_profiles::find::<S>(pool, user_id, entry_id).await {
+        Ok(Some(entry)) if created => HttpResponse::Created().json(ApiResponse::success("Profile entry added", entry)),
+        Ok(Some(entry)) => HttpResponse::Ok().json(ApiResponse::success("Profile entry updated", entry)),
+        Ok(None) => entry_not_found(),
+        Err(_) => database_error(),
+    }
+}
+
+// The caller's entries of one profile section, in order
+pub async fn list_entries<S: ProfileSection>(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    match candidate_profiles::entries::<S>(&pool, user_id(&req)).await {
+        Ok(entries) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Profile entries retrieved successfully",
+                entries
+            )))
+        }
+        Err(_) => Ok(database_error()),
+    }
+}
+
+// Add an entry at the end of one of the caller's profile sections
+pub async fn create_entry<S: ProfileSection>(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    entry_data: web::Json<S>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_permission(&req, Permission::JobsApply) {
+        return Ok(response);
+    }
+
+    let fields = match entry_data.into_inner().validate() {
+        Ok(fields) => fields,
+        Err(message) => return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(&message))),
+    };
+
+    let user_id = user_id(&req);
+    match candidate_profiles::create(&pool, user_id, &fields).await {
+        Ok(Some(entry_id)) => Ok(entry_response::<S>(&pool, user_id, entry_id, true).await),
+        Ok(None) => {
+            Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(&format!(
+                "A profile can have at most {} {} entries",
+                S::MAX_ENTRIES,
+                S::NAME
+            ))))
+        }
+        Err(e) => Ok(save_error::<S>(e)),
+    }
+}
+
+// Edit one of the caller's entries; fields left out are kept and null clears optional ones
+pub async fn update_entry<S: ProfileSection>(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+    entry_data: web::Json<Map<String, Value>>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_permission(&req, Permission::JobsApply) {
+        return Ok(response);
+    }
+
+    let user_id = user_id(&req);
+    let entry_id = path.into_inner();
+    let current = match candidate_profiles::find::<S>(&pool, user_id, entry_id).await {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return Ok(entry_not_found()),
+        Err(_) => return Ok(database_error()),
+    };
+
+    // Lay the changes over the current fields and read the result back as a whole entry
+    let merged = match serde_json::to_value(&current.fields) {
+        Ok(Value::Object(mut fields)) => {
+            fields.extend(entry_data.into_inner());
+            serde_json::from_value::<S>(Value::Object(fields))
+        }
+        _ => return Ok(database_error()),
+    };
+
+    let fields = match merged.map_err(|e| e.to_string()).and_then(S::validate) {
+        Ok(fields) => fields,
+        Err(message) => return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(&message))),
+    };
+
+    match candidate_profiles::update(&pool, user_id, entry_id, &fields).await {
+        Ok(true) => Ok(entry_response::<S>(&pool, user_id, entry_id, false).await),
+        Ok(false) => Ok(entry_not_found()),
+        Err(e) => Ok(save_error::<S>(e)),
+    }
+}
+
+pub async fn delete_entry<S: ProfileSection>(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_permission(&req, Permission::JobsApply) {
+        return Ok(response);
+    }
+
+    match candidate_profiles::delete::<S>(&pool, user_id(&req), path.into_inner()).await {
+        Ok(true) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "Profile entry deleted"
+            )))
+        }
+        Ok(false) => Ok(entry_not_found()),
+        Err(_) => Ok(database_error()),
+    }
+}
+
+// Put one of the caller's profile sections in a new order
+pub async fn reorder_entries<S: ProfileSection>(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    order_data: web::Json<ReorderEntriesRequest>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_permission(&req, Permission::JobsApply) {
+        return Ok(response);
+    }
+
+    let user_id = user_id(&req);
+    match candidate_profiles::reorder::<S>(&pool, user_id, &order_data.ids).await {
+        Ok(true) => match candidate_profiles::entries::<S>(&pool, user_id).await {
+            Ok(entries) => Ok(HttpResponse::Ok().json(ApiResponse::success("Profile entries reordered", entries))),
+            Err(_) => Ok(database_error()),
+        },
+        Ok(false) => {
+            Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(&format!(
+                "ids must list each of your {} entries exactly once",
+                S::NAME
+            ))))
+        }
+        Err(_) => Ok(database_error()),
+    }
+}
+
+async fn profile_response(pool: &PgPool, user_id: i32) -> HttpResponse {
+    match candidate_profiles::profile(pool, user_id).await {
+        Ok(Some(profile)) => {
+            HttpResponse::Ok().json(ApiResponse::success(
+                "Candidate profile retrieved successfully",
+                profile
+            ))
+        }
+        Ok(None) => {
+            HttpResponse::NotFound().json(ApiResponse::<()>::error(
+                "User not found"
+            ))
+        }
+        Err(_) => database_error(),
+    }
+}
+
+// The caller's whole candidate profile
+pub async fn get_my_candidate_profile(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    Ok(profile_response(&pool, user_id(&req)).await)
+}
+
+// Someone's whole candidate profile, for employers reviewing candidates
+pub async fn get_candidate_profile(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let candidate_id = path.into_inner();
+    let allowed = candidate_id == user_id(&req)
+        || req.extensions().get::<RolePermissions>().is_some_and(|permissions| {
+            permissions.grants(Permission::JobsPost) || permissions.grants(Permission::ApplicationsReadAny)
+        });
+    if !allowed {
+        return Ok(HttpResponse::Forbidden().json(ApiResponse::<()>::error(
+            "Only employers can view other candidates' profiles"
+        )));
+    }
+
+    Ok(profile_response(&pool, candidate_id).await)
+}
+
+fn section<S: ProfileSection>(scope: Scope) -> Scope {
+    let base = format!("/profile/{}", S::NAME);
+    scope
+        .route(&base, web::get().to(list_entries::<S>))
+        .route(&base, web::post().to(create_entry::<S>))
+        // Registered before /{id} so "order" is not read as an id
+        .route(&format!("{base}/order"), web::put().to(reorder_entries::<S>))
+        .route(&format!("{base}/{{id}}"), web::put().to(update_entry::<S>))
+        .route(&format!("{base}/{{id}}"), web::delete().to(delete_entry::<S>))
+}
+
+/// Candidate profile routes, added to the /users scope
+pub fn candidate_profile_routes(scope: Scope) -> Scope {
+    let scope = scope
+        .route("/profile/candidate", web::get().to(get_my_candidate_profile))
+        .route("/{id}/candidate-profile", web::get().to(get_candidate_profile));
+    let scope = section::<Experience>(scope);
+    let scope = section::<Education>(scope);
+    let scope = section::<Certification>(scope);
+    let scope = section::<SpokenLanguage>(scope);
+    let scope = section::<CandidateSkill>(scope);
+    section::<ProfileLink>(scope)
+}
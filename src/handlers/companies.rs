@@ -0,0 +1,703 @@
+use actix_web::{web, HttpResponse, HttpRequest, HttpMessage, Result, Scope};
+use chrono::{Duration, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+
+use crate::models::{
+    AcceptCompanyInviteRequest, ApiResponse, Claims, Company, CompanyInvite, CompanyMember, CreateCompanyInviteRequest,
+    CreateCompanyRequest, MyCompany, UpdateCompanyMemberRequest, UpdateCompanyRequest
+};
+use crate::api_keys;
+use crate::companies::{self, CompanyRole, COMPANY_COLUMNS};
+use crate::config::Config;
+use crate::domain_verification;
+use crate::handlers::{api_keys as api_key_handlers, company_verification};
+use crate::mailer::Mailer;
+use crate::middleware::require_permission;
+use crate::permissions::Permission;
+use crate::utils::{generate_token, hash_token};
+
+// Invites are valid for 7 days
+const COMPANY_INVITE_EXPIRATION_DAYS: i64 = 7;
+
+fn user_id(req: &HttpRequest) -> i32 {
+    let extensions = req.extensions();
+    let claims = extensions.get::<Claims>().unwrap();
+    claims.sub.parse().unwrap()
+}
+
+fn company_not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(ApiResponse::<()>::error(
+        "Company not found"
+    ))
+}
+
+fn database_error() -> HttpResponse {
+    HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+        "Database error"
+    ))
+}
+
+fn owners_only() -> HttpResponse {
+    HttpResponse::Forbidden().json(ApiResponse::<()>::error(
+        "Only owners can manage the company"
+    ))
+}
+
+fn invalid_role() -> HttpResponse {
+    HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+        "role must be one of: owner, recruiter, viewer"
+    ))
+}
+
+/// The caller's role in the company, or 404 if they are not a member
+async fn caller_role(pool: &PgPool, req: &HttpRequest, company_id: i32) -> Result<CompanyRole, HttpResponse> {
+    if !api_keys::reaches_company(req, company_id) {
+        return Err(company_not_found());
+    }
+    match companies::member_role(pool, company_id, user_id(req)).await {
+        Ok(Some(role)) => Ok(role),
+        Ok(None) => Err(company_not_found()),
+        Err(_) => Err(database_error()),
+    }
+}
+
+/// A company the caller owns: 404 for non-members, 403 for other roles
+pub async fn owned_company(pool: &PgPool, req: &HttpRequest, company_id: i32) -> Result<Company, HttpResponse> {
+    if caller_role(pool, req, company_id).await? != CompanyRole::Owner {
+        return Err(owners_only());
+    }
+
+    match companies::find(pool, company_id).await {
+        Ok(Some(company)) => Ok(company),
+        Ok(None) => Err(company_not_found()),
+        Err(_) => Err(database_error()),
+    }
+}
+
+/// Lock the company's team and return the role of `user_id` in it, if any.
+/// Membership changes go through here so two owners cannot both step down at once.
+async fn lock_member(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: i32,
+    user_id: i32,
+) -> Result<Option<CompanyRole>, sqlx::Error> {
+    sqlx::query("SELECT id FROM companies WHERE id = $1 FOR UPDATE")
+        .bind(company_id)
+        .execute(&mut *tx)
+        .await?;
+
+    let role: Option<String> = sqlx::query_scalar(
+        "SELECT role FROM company_members WHERE company_id = $1 AND user_id = $2"
+    )
+    .bind(company_id)
+    .bind(user_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    Ok(role.as_deref().and_then(CompanyRole::parse))
+}
+
+async fn other_owners(tx: &mut Transaction<'_, Postgres>, company_id: i32, user_id: i32) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT COUNT(*) FROM company_members WHERE company_id = $1 AND role = 'owner' AND user_id <> $2"
+    )
+    .bind(company_id)
+    .bind(user_id)
+    .fetch_one(&mut *tx)
+    .await
+}
+
+fn last_owner() -> HttpResponse {
+    HttpResponse::Conflict().json(ApiResponse::<()>::error(
+        "A company needs at least one owner; make someone else an owner first"
+    ))
+}
+
+async fn members_response(pool: &PgPool, company_id: i32, message: &str) -> HttpResponse {
+    let members_result = sqlx::query_as::<_, CompanyMember>(
+        r#"
+        SELECT m.user_id, u.username, u.email, u.first_name, u.last_name, m.role, m.created_at
+        FROM company_members m
+        JOIN users u ON u.id = m.user_id
+        WHERE m.company_id = $1
+        ORDER BY CASE m.role WHEN 'owner' THEN 0 WHEN 'recruiter' THEN 1 ELSE 2 END, u.username
+        "#
+    )
+    .bind(company_id)
+    .fetch_all(pool)
+    .await;
+
+    match members_result {
+        Ok(members) => HttpResponse::Ok().json(ApiResponse::success(message, members)),
+        Err(_) => database_error(),
+    }
+}
+
+// Create a company with the caller as its first owner
+pub async fn create_company(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    company_data: web::Json<CreateCompanyRequest>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_permission(&req, Permission::JobsPost) {
+        return Ok(response);
+    }
+
+    let company = match companies::validate(company_data.into_inner()) {
+        Ok(company) => company,
+        Err(message) => return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(&message))),
+    };
+
+    let owner_id = user_id(&req);
+    let insert_result: Result<i32, sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+        let company_id: i32 = sqlx::query_scalar(
+            r#"
+            INSERT INTO companies (name, description, logo_url, website, industry, size)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING id
+            "#
+        )
+        .bind(&company.name)
+        .bind(&company.description)
+        .bind(&company.logo_url)
+        .bind(&company.website)
+        .bind(&company.industry)
+        .bind(&company.size)
+        .fetch_one(&mut tx)
+        .await?;
+
+        sqlx::query("INSERT INTO company_members (company_id, user_id, role) VALUES ($1, $2, 'owner')")
+            .bind(company_id)
+            .bind(owner_id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(company_id)
+    }
+    .await;
+
+    let created = match insert_result {
+        Ok(company_id) => companies::find(&pool, company_id).await,
+        Err(e) => Err(e),
+    };
+
+    match created {
+        Ok(Some(company)) => {
+            Ok(HttpResponse::Created().json(ApiResponse::success(
+                "Company created",
+                company
+            )))
+        }
+        _ => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to create company"
+            )))
+        }
+    }
+}
+
+// The companies the caller belongs to, with their role in each
+pub async fn list_my_companies(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let companies_result = sqlx::query_as::<_, MyCompany>(&format!(
+        r#"
+        SELECT {COMPANY_COLUMNS}, m.role
+        FROM company_members m
+        JOIN companies c ON c.id = m.company_id
+        WHERE m.user_id = $1 AND ($2::INTEGER IS NULL OR c.id = $2)
+        ORDER BY c.name, c.id
+        "#
+    ))
+    .bind(user_id(&req))
+    .bind(api_keys::key_company(&req))
+    .fetch_all(pool.get_ref())
+    .await;
+
+    match companies_result {
+        Ok(companies) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Companies retrieved successfully",
+                companies
+            )))
+        }
+        Err(_) => Ok(database_error()),
+    }
+}
+
+// A company's public profile
+pub async fn get_company(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    match companies::find(&pool, path.into_inner()).await {
+        Ok(Some(company)) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Company found",
+                company
+            )))
+        }
+        Ok(None) => Ok(company_not_found()),
+        Err(_) => Ok(database_error()),
+    }
+}
+
+// Edit the profile of a company the caller owns; omitted fields are kept
+pub async fn update_company(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+    company_data: web::Json<UpdateCompanyRequest>,
+) -> Result<HttpResponse> {
+    let company_id = path.into_inner();
+    let current = match owned_company(&pool, &req, company_id).await {
+        Ok(company) => company,
+        Err(response) => return Ok(response),
+    };
+
+    let company = match companies::validate(companies::merge(&current, company_data.into_inner())) {
+        Ok(company) => company,
+        Err(message) => return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(&message))),
+    };
+
+    // Moving the website to another domain takes the verified badge away
+    let domain = company.website.as_deref().and_then(domain_verification::website_domain);
+    let update_result = sqlx::query(
+        r#"
+        UPDATE companies
+        SET name = $1, description = $2, logo_url = $3, website = $4, industry = $5, size = $6,
+            verified_at = CASE WHEN verified_domain = $8 THEN verified_at END,
+            verified_domain = CASE WHEN verified_domain = $8 THEN verified_domain END,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = $7
+        "#
+    )
+    .bind(&company.name)
+    .bind(&company.description)
+    .bind(&company.logo_url)
+    .bind(&company.website)
+    .bind(&company.industry)
+    .bind(&company.size)
+    .bind(company_id)
+    .bind(&domain)
+    .execute(pool.get_ref())
+    .await;
+
+    if update_result.is_err() {
+        return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            "Failed to update company"
+        )));
+    }
+
+    match companies::find(&pool, company_id).await {
+        Ok(Some(company)) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Company updated",
+                company
+            )))
+        }
+        _ => Ok(database_error()),
+    }
+}
+
+// The team of a company the caller belongs to
+pub async fn list_members(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let company_id = path.into_inner();
+    if let Err(response) = caller_role(&pool, &req, company_id).await {
+        return Ok(response);
+    }
+
+    Ok(members_response(&pool, company_id, "Members retrieved successfully").await)
+}
+
+// Invite someone by email to join a company the caller owns. The response is the
+// same whether or not the address has an account; nobody joins without accepting.
+pub async fn create_invite(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    mailer: web::Data<Mailer>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+    invite_data: web::Json<CreateCompanyInviteRequest>,
+) -> Result<HttpResponse> {
+    let company = match owned_company(&pool, &req, path.into_inner()).await {
+        Ok(company) => company,
+        Err(response) => return Ok(response),
+    };
+
+    let Some(role) = CompanyRole::parse(&invite_data.role) else {
+        return Ok(invalid_role());
+    };
+
+    let email = invite_data.email.trim();
+    if email.is_empty() || !email.contains('@') {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Valid email is required"
+        )));
+    }
+
+    let token = generate_token();
+    let expires_at = Utc::now() + Duration::days(COMPANY_INVITE_EXPIRATION_DAYS);
+
+    let invite_result = sqlx::query_as::<_, CompanyInvite>(
+        r#"
+        INSERT INTO company_invites (company_id, email, role, token_hash, invited_by, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id, email, role, invited_by, expires_at, created_at
+        "#
+    )
+    .bind(company.id)
+    .bind(email)
+    .bind(role.as_str())
+    .bind(hash_token(&token))
+    .bind(user_id(&req))
+    .bind(expires_at)
+    .fetch_one(pool.get_ref())
+    .await;
+
+    let invite = match invite_result {
+        Ok(invite) => invite,
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to create invite"
+            )));
+        }
+    };
+
+    let invite_link = format!("{}/accept-company-invite?token={token}", config.app_base_url);
+    let body = format!(
+        "You have been invited to join {} as {} on Connecting Opportunities.\n\n\
+         Sign in with this email address and open the link below to accept. It expires in {COMPANY_INVITE_EXPIRATION_DAYS} days.\n\n\
+         {invite_link}\n\n\
+         If you do not want to join, ignore this email.",
+        company.name,
+        role.as_str()
+    );
+    if let Err(e) = mailer.send(email, &format!("You're invited to join {}", company.name), &body).await {
+        log::error!("Failed to send company invite {}: {e}", invite.id);
+        return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            "Failed to send invite email"
+        )));
+    }
+
+    Ok(HttpResponse::Created().json(ApiResponse::success(
+        "Invite sent",
+        invite
+    )))
+}
+
+// Pending invites of a company the caller owns
+pub async fn list_invites(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let company = match owned_company(&pool, &req, path.into_inner()).await {
+        Ok(company) => company,
+        Err(response) => return Ok(response),
+    };
+
+    let invites_result = sqlx::query_as::<_, CompanyInvite>(
+        r#"
+        SELECT id, email, role, invited_by, expires_at, created_at
+        FROM company_invites
+        WHERE company_id = $1 AND accepted_at IS NULL AND revoked_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+        ORDER BY created_at DESC
+        "#
+    )
+    .bind(company.id)
+    .fetch_all(pool.get_ref())
+    .await;
+
+    match invites_result {
+        Ok(invites) => Ok(HttpResponse::Ok().json(ApiResponse::success("Invites retrieved successfully", invites))),
+        Err(_) => Ok(database_error()),
+    }
+}
+
+// Withdraw a pending invite of a company the caller owns
+pub async fn revoke_invite(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse> {
+    let (company_id, invite_id) = path.into_inner();
+    let company = match owned_company(&pool, &req, company_id).await {
+        Ok(company) => company,
+        Err(response) => return Ok(response),
+    };
+
+    let revoke_result = sqlx::query(
+        r#"
+        UPDATE company_invites SET revoked_at = CURRENT_TIMESTAMP
+        WHERE id = $1 AND company_id = $2 AND accepted_at IS NULL AND revoked_at IS NULL
+        "#
+    )
+    .bind(invite_id)
+    .bind(company.id)
+    .execute(pool.get_ref())
+    .await;
+
+    match revoke_result {
+        Ok(result) if result.rows_affected() > 0 => {
+            Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "Invite revoked"
+            )))
+        }
+        Ok(_) => {
+            Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+                "Invite not found"
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to revoke invite"
+            )))
+        }
+    }
+}
+
+// Join a company with the token from an invite sent to the caller's email address
+pub async fn accept_invite(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    invite_data: web::Json<AcceptCompanyInviteRequest>,
+) -> Result<HttpResponse> {
+    let user_id = user_id(&req);
+    let token_hash = hash_token(&invite_data.token);
+
+    let invite: Option<(i32, String, bool)> = match sqlx::query_as(
+        r#"
+        SELECT i.company_id, i.role, LOWER(i.email) = LOWER(u.email)
+        FROM company_invites i, users u
+        WHERE i.token_hash = $1 AND i.accepted_at IS NULL AND i.revoked_at IS NULL AND i.expires_at > CURRENT_TIMESTAMP
+          AND u.id = $2
+        "#
+    )
+    .bind(&token_hash)
+    .bind(user_id)
+    .fetch_optional(pool.get_ref())
+    .await
+    {
+        Ok(invite) => invite,
+        Err(_) => return Ok(database_error()),
+    };
+
+    let Some((company_id, role, email_matches)) = invite else {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Invalid or expired invite"
+        )));
+    };
+    if !email_matches {
+        return Ok(HttpResponse::Forbidden().json(ApiResponse::<()>::error(
+            "This invite was sent to another email address; sign in with that account to accept it"
+        )));
+    }
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return Ok(database_error()),
+    };
+    match lock_member(&mut tx, company_id, user_id).await {
+        Ok(None) => {}
+        Ok(Some(_)) => {
+            return Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(
+                "You are already a member of this company"
+            )));
+        }
+        Err(_) => return Ok(database_error()),
+    }
+
+    // The pending check makes the token single-use even under concurrent requests
+    let accept_result: Result<bool, sqlx::Error> = async {
+        let accepted = sqlx::query(
+            r#"
+            UPDATE company_invites SET accepted_at = CURRENT_TIMESTAMP, accepted_user_id = $2
+            WHERE token_hash = $1 AND accepted_at IS NULL AND revoked_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+            "#
+        )
+        .bind(&token_hash)
+        .bind(user_id)
+        .execute(&mut tx)
+        .await?;
+        if accepted.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query("INSERT INTO company_members (company_id, user_id, role) VALUES ($1, $2, $3)")
+            .bind(company_id)
+            .bind(user_id)
+            .bind(&role)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+    .await;
+
+    match accept_result {
+        Ok(true) => {}
+        Ok(false) => {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+                "Invalid or expired invite"
+            )));
+        }
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to accept invite"
+            )));
+        }
+    }
+
+    log::info!("User {user_id} joined company {company_id} as {role}");
+
+    match companies::find(&pool, company_id).await {
+        Ok(Some(company)) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Invite accepted",
+                MyCompany { company, role }
+            )))
+        }
+        Ok(None) => Ok(company_not_found()),
+        Err(_) => Ok(database_error()),
+    }
+}
+
+// Change a member's role in a company the caller owns
+pub async fn update_member(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<(i32, i32)>,
+    member_data: web::Json<UpdateCompanyMemberRequest>,
+) -> Result<HttpResponse> {
+    let (company_id, member_id) = path.into_inner();
+    match caller_role(&pool, &req, company_id).await {
+        Ok(CompanyRole::Owner) => {}
+        Ok(_) => return Ok(owners_only()),
+        Err(response) => return Ok(response),
+    }
+
+    let Some(role) = CompanyRole::parse(&member_data.role) else {
+        return Ok(invalid_role());
+    };
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return Ok(database_error()),
+    };
+    let current = match lock_member(&mut tx, company_id, member_id).await {
+        Ok(Some(current)) => current,
+        Ok(None) => {
+            return Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+                "Member not found"
+            )));
+        }
+        Err(_) => return Ok(database_error()),
+    };
+
+    if current == CompanyRole::Owner && role != CompanyRole::Owner {
+        match other_owners(&mut tx, company_id, member_id).await {
+            Ok(0) => return Ok(last_owner()),
+            Ok(_) => {}
+            Err(_) => return Ok(database_error()),
+        }
+    }
+
+    let update_result = sqlx::query("UPDATE company_members SET role = $1 WHERE company_id = $2 AND user_id = $3")
+        .bind(role.as_str())
+        .bind(company_id)
+        .bind(member_id)
+        .execute(&mut tx)
+        .await;
+
+    if update_result.is_err() || tx.commit().await.is_err() {
+        return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            "Failed to update member"
+        )));
+    }
+
+    Ok(members_response(&pool, company_id, "Member updated").await)
+}
+
+// Remove a member from a company the caller owns, or leave one
+pub async fn remove_member(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse> {
+    let (company_id, member_id) = path.into_inner();
+    match caller_role(&pool, &req, company_id).await {
+        Ok(CompanyRole::Owner) => {}
+        Ok(_) if member_id == user_id(&req) => {}
+        Ok(_) => return Ok(owners_only()),
+        Err(response) => return Ok(response),
+    }
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return Ok(database_error()),
+    };
+    let current = match lock_member(&mut tx, company_id, member_id).await {
+        Ok(Some(current)) => current,
+        Ok(None) => {
+            return Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+                "Member not found"
+            )));
+        }
+        Err(_) => return Ok(database_error()),
+    };
+
+    // Postings stay with the company; only the membership goes
+    if current == CompanyRole::Owner {
+        match other_owners(&mut tx, company_id, member_id).await {
+            Ok(0) => return Ok(last_owner()),
+            Ok(_) => {}
+            Err(_) => return Ok(database_error()),
+        }
+    }
+
+    let delete_result = sqlx::query("DELETE FROM company_members WHERE company_id = $1 AND user_id = $2")
+        .bind(company_id)
+        .bind(member_id)
+        .execute(&mut tx)
+        .await;
+
+    if delete_result.is_err() || tx.commit().await.is_err() {
+        return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            "Failed to remove member"
+        )));
+    }
+
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+        "Member removed"
+    )))
+}
+
+pub fn company_routes() -> Scope {
+    web::scope("/companies")
+        .route("", web::post().to(create_company))
+        // Registered before /{id} so "mine" is not read as an id
+        .route("/mine", web::get().to(list_my_companies))
+        .route("/invites/accept", web::post().to(accept_invite))
+        .route("/{id}", web::get().to(get_company))
+        .route("/{id}", web::put().to(update_company))
+        .route("/{id}/members", web::get().to(list_members))
+        .route("/{id}/members/{user_id}", web::put().to(update_member))
+        .route("/{id}/members/{user_id}", web::delete().to(remove_member))
+        .route("/{id}/invites", web::get().to(list_invites))
+        .route("/{id}/invites", web::post().to(create_invite))
+        .route("/{id}/invites/{invite_id}", web::delete().to(revoke_invite))
+        .route("/{id}/api-keys", web::get().to(api_key_handlers::list_company_api_keys))
+        .route("/{id}/api-keys", web::post().to(api_key_handlers::create_company_api_key))
+        .route("/{id}/api-keys/{key_id}", web::delete().to(api_key_handlers::revoke_company_api_key))
+        .route("/{id}/verification", web::get().to(company_verification::get_verification))
+        .route("/{id}/verification/email", web::post().to(company_verification::request_email_verification))
+        .route("/{id}/verification/dns", web::post().to(company_verification::request_dns_verification))
+        .route("/{id}/verification/dns/check", web::post().to(company_verification::check_dns_verification))
+}
@@ -0,0 +1,439 @@
+use actix_web::{web, HttpResponse, HttpRequest, HttpMessage, Result};
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+
+use crate::models::{
+    ApiResponse, Claims, Company, CompanyEmailVerificationRequest, CompanyVerificationStatus,
+    ConfirmCompanyVerificationRequest, DnsVerificationChallenge, PendingEmailVerification
+};
+use crate::companies;
+use crate::config::Config;
+use crate::domain_verification::{self, TxtResolver, TXT_RECORD_PREFIX};
+use crate::handlers::companies::owned_company;
+use crate::mailer::Mailer;
+use crate::utils::{generate_token, hash_token};
+
+fn user_id(req: &HttpRequest) -> i32 {
+    let extensions = req.extensions();
+    let claims = extensions.get::<Claims>().unwrap();
+    claims.sub.parse().unwrap()
+}
+
+fn database_error() -> HttpResponse {
+    HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+        "Database error"
+    ))
+}
+
+/// The domain the company can verify, or 400 when its website gives none
+fn company_domain(company: &Company) -> Result<String, HttpResponse> {
+    company.website.as_deref().and_then(domain_verification::website_domain).ok_or_else(|| {
+        HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Set the company's website to an address on its own domain before verifying it"
+        ))
+    })
+}
+
+/// 409 when the company has already verified `domain`
+fn ensure_unverified(company: &Company, domain: &str) -> Result<(), HttpResponse> {
+    if company.verified && company.verified_domain.as_deref() == Some(domain) {
+        Err(HttpResponse::Conflict().json(ApiResponse::<()>::error(&format!(
+            "The company has already verified {domain}"
+        ))))
+    } else {
+        Ok(())
+    }
+}
+
+fn challenge(domain: &str, token: &str, expires_at: DateTime<Utc>) -> DnsVerificationChallenge {
+    DnsVerificationChallenge {
+        record_name: domain.to_string(),
+        record_type: "TXT",
+        record_value: format!("{TXT_RECORD_PREFIX}{token}"),
+        expires_at,
+    }
+}
+
+/// The latest unexpired DNS token issued for the company's current domain
+async fn pending_dns_token(
+    pool: &PgPool,
+    company_id: i32,
+    domain: &str,
+) -> Result<Option<(String, DateTime<Utc>)>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT token, expires_at FROM company_domain_verifications
+        WHERE company_id = $1 AND domain = $2 AND method = 'dns'
+          AND used_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+        ORDER BY created_at DESC
+        LIMIT 1
+        "#
+    )
+    .bind(company_id)
+    .bind(domain)
+    .fetch_optional(pool)
+    .await
+}
+
+// Verification status of a company the caller owns, with any attempt still pending
+pub async fn get_verification(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let company = match owned_company(&pool, &req, path.into_inner()).await {
+        Ok(company) => company,
+        Err(response) => return Ok(response),
+    };
+
+    let domain = company.website.as_deref().and_then(domain_verification::website_domain);
+    let (pending_email, pending_dns) = match &domain {
+        Some(domain) => {
+            let pending_email = sqlx::query_as::<_, (String, DateTime<Utc>)>(
+                r#"
+                SELECT email, expires_at FROM company_domain_verifications
+                WHERE company_id = $1 AND domain = $2 AND method = 'email'
+                  AND used_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+                ORDER BY created_at DESC
+                LIMIT 1
+                "#
+            )
+            .bind(company.id)
+            .bind(domain)
+            .fetch_optional(pool.get_ref())
+            .await;
+
+            match (pending_email, pending_dns_token(&pool, company.id, domain).await) {
+                (Ok(email), Ok(dns)) => (
+                    email.map(|(email, expires_at)| PendingEmailVerification { email, expires_at }),
+                    dns.map(|(token, expires_at)| challenge(domain, &token, expires_at)),
+                ),
+                _ => return Ok(database_error()),
+            }
+        }
+        None => (None, None),
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(
+        "Verification status retrieved successfully",
+        CompanyVerificationStatus {
+            domain,
+            verified: company.verified,
+            verified_domain: company.verified_domain,
+            verified_at: company.verified_at,
+            pending_email,
+            pending_dns,
+        }
+    )))
+}
+
+// Email a verification link to an address on the company's domain
+pub async fn request_email_verification(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    mailer: web::Data<Mailer>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+    verification_data: web::Json<CompanyEmailVerificationRequest>,
+) -> Result<HttpResponse> {
+    let company = match owned_company(&pool, &req, path.into_inner()).await {
+        Ok(company) => company,
+        Err(response) => return Ok(response),
+    };
+    let domain = match company_domain(&company) {
+        Ok(domain) => domain,
+        Err(response) => return Ok(response),
+    };
+    if let Err(response) = ensure_unverified(&company, &domain) {
+        return Ok(response);
+    }
+
+    let email = verification_data.email.trim().to_lowercase();
+    if email.len() > 100 || !domain_verification::is_admin_mailbox(&email, &domain) {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(&format!(
+            "The verification link must go to admin@, hostmaster@, postmaster@ or webmaster@{domain}"
+        ))));
+    }
+
+    let last_sent: Option<DateTime<Utc>> = match sqlx::query_scalar(
+        "SELECT MAX(created_at) FROM company_domain_verifications WHERE company_id = $1 AND method = 'email'"
+    )
+    .bind(company.id)
+    .fetch_one(pool.get_ref())
+    .await
+    {
+        Ok(last_sent) => last_sent,
+        Err(_) => return Ok(database_error()),
+    };
+
+    if let Some(last_sent) = last_sent {
+        let elapsed = (Utc::now() - last_sent).num_seconds();
+        if elapsed < config.email_verification_cooldown {
+            let retry_after = config.email_verification_cooldown - elapsed;
+            return Ok(HttpResponse::TooManyRequests()
+                .insert_header(("Retry-After", retry_after.to_string()))
+                .json(ApiResponse::<()>::error(&format!(
+                    "Please wait {retry_after} seconds before requesting another verification email"
+                ))));
+        }
+    }
+
+    // Only the latest link works, so a link sent to a mistyped address can be replaced
+    let token = generate_token();
+    let expires_at = Utc::now() + Duration::seconds(config.company_verification_expiration);
+    let insert_result: Result<(), sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query(
+            r#"
+            UPDATE company_domain_verifications SET used_at = CURRENT_TIMESTAMP
+            WHERE company_id = $1 AND method = 'email' AND used_at IS NULL
+            "#
+        )
+        .bind(company.id)
+        .execute(&mut tx)
+        .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO company_domain_verifications (company_id, domain, method, email, token, requested_by, expires_at)
+            VALUES ($1, $2, 'email', $3, $4, $5, $6)
+            "#
+        )
+        .bind(company.id)
+        .bind(&domain)
+        .bind(&email)
+        .bind(hash_token(&token))
+        .bind(user_id(&req))
+        .bind(expires_at)
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await
+    }
+    .await;
+
+    if insert_result.is_err() {
+        return Ok(database_error());
+    }
+
+    let verify_link = format!("{}/verify-company?token={token}", config.app_base_url);
+    let body = format!(
+        "Someone asked to confirm that {} owns {domain} on Connecting Opportunities.\n\n\
+         If your organisation runs this company profile, confirm by opening the link below.\n\n\
+         {verify_link}\n\n\
+         The link expires in {} hours. If you do not recognise this request, ignore this email.",
+        company.name,
+        config.company_verification_expiration / 3600
+    );
+
+    match mailer.send(&email, &format!("Confirm that {} owns {domain}", company.name), &body).await {
+        Ok(()) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(&format!(
+                "Verification link sent to {email}"
+            ))))
+        }
+        Err(e) => {
+            log::error!("Failed to send company verification email for company {}: {e}", company.id);
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to send verification email"
+            )))
+        }
+    }
+}
+
+// Public endpoint: confirm a company's domain with the token from the emailed link
+pub async fn confirm_email_verification(
+    pool: web::Data<PgPool>,
+    confirm_data: web::Json<ConfirmCompanyVerificationRequest>,
+) -> Result<HttpResponse> {
+    if confirm_data.token.trim().is_empty() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Verification token is required"
+        )));
+    }
+
+    let result: Result<Option<bool>, sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+
+        let verification: Option<(i32, String)> = sqlx::query_as(
+            r#"
+            UPDATE company_domain_verifications SET used_at = CURRENT_TIMESTAMP
+            WHERE token = $1 AND method = 'email' AND used_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+            RETURNING company_id, domain
+            "#
+        )
+        .bind(hash_token(confirm_data.token.trim()))
+        .fetch_optional(&mut tx)
+        .await?;
+
+        let Some((company_id, domain)) = verification else {
+            return Ok(None);
+        };
+
+        if !domain_verification::mark_verified(&mut tx, company_id, &domain).await? {
+            return Ok(Some(false));
+        }
+
+        tx.commit().await?;
+        Ok(Some(true))
+    }
+    .await;
+
+    match result {
+        Ok(Some(true)) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "Company domain verified"
+            )))
+        }
+        Ok(Some(false)) => {
+            Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(
+                "The company's website has moved to another domain since this link was sent"
+            )))
+        }
+        Ok(None) => {
+            Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+                "Invalid or expired verification token"
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to verify company"
+            )))
+        }
+    }
+}
+
+// The TXT record to publish on the company's domain; the same token is
+// returned until it expires so a record already published keeps working
+pub async fn request_dns_verification(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let company = match owned_company(&pool, &req, path.into_inner()).await {
+        Ok(company) => company,
+        Err(response) => return Ok(response),
+    };
+    let domain = match company_domain(&company) {
+        Ok(domain) => domain,
+        Err(response) => return Ok(response),
+    };
+    if let Err(response) = ensure_unverified(&company, &domain) {
+        return Ok(response);
+    }
+
+    let pending = match pending_dns_token(&pool, company.id, &domain).await {
+        Ok(pending) => pending,
+        Err(_) => return Ok(database_error()),
+    };
+
+    let (token, expires_at) = match pending {
+        Some(pending) => pending,
+        None => {
+            let token = generate_token();
+            let expires_at = Utc::now() + Duration::seconds(config.company_verification_expiration);
+            let insert_result = sqlx::query(
+                r#"
+                INSERT INTO company_domain_verifications (company_id, domain, method, token, requested_by, expires_at)
+                VALUES ($1, $2, 'dns', $3, $4, $5)
+                "#
+            )
+            .bind(company.id)
+            .bind(&domain)
+            .bind(&token)
+            .bind(user_id(&req))
+            .bind(expires_at)
+            .execute(pool.get_ref())
+            .await;
+
+            if insert_result.is_err() {
+                return Ok(database_error());
+            }
+            (token, expires_at)
+        }
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(
+        "Publish this TXT record on the domain, then ask for it to be checked",
+        challenge(&domain, &token, expires_at)
+    )))
+}
+
+// Look up the company's TXT records and verify the domain if the token is published
+pub async fn check_dns_verification(
+    pool: web::Data<PgPool>,
+    resolver: web::Data<dyn TxtResolver>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let company = match owned_company(&pool, &req, path.into_inner()).await {
+        Ok(company) => company,
+        Err(response) => return Ok(response),
+    };
+    let domain = match company_domain(&company) {
+        Ok(domain) => domain,
+        Err(response) => return Ok(response),
+    };
+    if let Err(response) = ensure_unverified(&company, &domain) {
+        return Ok(response);
+    }
+
+    let token = match pending_dns_token(&pool, company.id, &domain).await {
+        Ok(Some((token, _))) => token,
+        Ok(None) => {
+            return Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+                "No DNS verification is pending for this domain; request a TXT record first"
+            )));
+        }
+        Err(_) => return Ok(database_error()),
+    };
+
+    match domain_verification::has_token(resolver.get_ref(), &domain, &token).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(&format!(
+                "The verification TXT record was not found on {domain}; DNS changes can take a while to show up"
+            ))));
+        }
+        Err(e) => {
+            log::warn!("TXT lookup for {domain} failed: {e}");
+            return Ok(HttpResponse::BadGateway().json(ApiResponse::<()>::error(
+                "Could not look up the domain's DNS records; try again later"
+            )));
+        }
+    }
+
+    let result: Result<bool, sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+        let verified = domain_verification::mark_verified(&mut tx, company.id, &domain).await?;
+        tx.commit().await?;
+        Ok(verified)
+    }
+    .await;
+
+    let verified = match result {
+        Ok(true) => companies::find(&pool, company.id).await,
+        Ok(false) => {
+            return Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(
+                "The company's website changed while the domain was being checked; try again"
+            )));
+        }
+        Err(e) => Err(e),
+    };
+
+    match verified {
+        Ok(Some(company)) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Company domain verified",
+                company
+            )))
+        }
+        _ => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to verify company"
+            )))
+        }
+    }
+}
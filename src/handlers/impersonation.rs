@@ -0,0 +1,331 @@
+use actix_web::{web, HttpResponse, HttpRequest, HttpMessage, Result};
+use sqlx::PgPool;
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+
+use crate::client_ip::client_ip;
+use crate::models::{
+    Actor, ApiResponse, Claims, ImpersonationEvent, ImpersonationQuery, ImpersonationResponse,
+    ImpersonationSession, StartImpersonationRequest, User,
+};
+use crate::utils::generate_impersonation_jwt;
+use crate::config::Config;
+use crate::cache::CacheManager;
+use crate::audit::{self, SecurityEventType};
+use crate::impersonation::{self, Impersonation, ImpersonationEventType};
+use crate::revocation::{self, RevocationEvent};
+use crate::keys::KeyStore;
+use crate::permissions;
+
+// Admin endpoint: start acting as another user, for support. The token expires with the session.
+pub async fn start_impersonation(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    cache: web::Data<CacheManager>,
+    keys: web::Data<KeyStore>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+    request_data: web::Json<StartImpersonationRequest>,
+) -> Result<HttpResponse> {
+    let (admin_id, admin_username): (i32, String) = {
+        let extensions = req.extensions();
+        let claims = extensions.get::<Claims>().unwrap();
+        (claims.sub.parse().unwrap(), claims.username.clone())
+    };
+    let user_id = path.into_inner();
+
+    let reason = request_data.reason.trim();
+    if reason.is_empty() || reason.len() > 500 {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "A reason of at most 500 characters is required"
+        )));
+    }
+
+    if user_id == admin_id {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "You cannot impersonate yourself"
+        )));
+    }
+
+    let user = match sqlx::query_as::<_, User>(
+        r#"
+        SELECT id, username, email, password_hash, first_name, last_name, phone, role, professional_role, company_name, is_active, email_verified, created_at, updated_at
+        FROM users
+        WHERE id = $1
+        "#
+    )
+    .bind(user_id)
+    .fetch_optional(pool.get_ref())
+    .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            return Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+                "User not found"
+            )));
+        }
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    };
+
+    let role = match permissions::load(&pool, &cache, &user.role).await {
+        Ok(Some(role)) => role,
+        Ok(None) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "User has an unknown role"
+            )));
+        }
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    };
+
+    // Acting as another admin would hand out admin rights without an audit of their own
+    if role.is_administrative() {
+        return Ok(HttpResponse::Forbidden().json(ApiResponse::<()>::error(
+            "Accounts with administrative permissions cannot be impersonated"
+        )));
+    }
+
+    if !user.is_active {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Account is deactivated"
+        )));
+    }
+
+    let session_id = Uuid::new_v4();
+    let expires_at = Utc::now() + Duration::seconds(config.impersonation_expiration);
+    let ip_address = client_ip(&req);
+
+    if sqlx::query(
+        r#"
+        INSERT INTO impersonation_sessions (id, admin_id, user_id, reason, ip_address, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#
+    )
+    .bind(session_id)
+    .bind(admin_id)
+    .bind(user_id)
+    .bind(reason)
+    .bind(&ip_address)
+    .bind(expires_at)
+    .execute(pool.get_ref())
+    .await
+    .is_err()
+    {
+        return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            "Failed to start impersonation"
+        )));
+    }
+
+    let actor = Actor { sub: admin_id.to_string(), username: admin_username };
+    let access_token = match generate_impersonation_jwt(&user, role.version, actor, session_id, expires_at, &keys, &config) {
+        Ok(token) => token,
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to generate token"
+            )));
+        }
+    };
+
+    impersonation::record(
+        &pool, session_id, ImpersonationEventType::Start, None, ip_address.as_deref(), Some(reason),
+    ).await;
+    audit::record(
+        &pool,
+        SecurityEventType::ImpersonationStarted,
+        Some(user_id),
+        Some(admin_id),
+        ip_address.as_deref(),
+        &format!("Impersonation {session_id} started: {reason}"),
+    )
+    .await;
+
+    Ok(HttpResponse::Created().json(ApiResponse::success(
+        "Impersonation started",
+        ImpersonationResponse {
+            impersonation_id: session_id,
+            access_token,
+            token_type: "Bearer".to_string(),
+            expires_in: config.impersonation_expiration,
+            expires_at,
+            user: user.into(),
+        }
+    )))
+}
+
+/// Close an open impersonation session and make every replica stop accepting its token.
+/// Returns false when the session does not exist or has already ended.
+async fn end_session(
+    pool: &PgPool,
+    cache: &CacheManager,
+    session_id: Uuid,
+    ended_by: Option<i32>,
+    ip_address: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    let ended: Option<(i32, i32)> = sqlx::query_as(
+        r#"
+        UPDATE impersonation_sessions SET ended_at = CURRENT_TIMESTAMP, ended_by = $2
+        WHERE id = $1 AND ended_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+        RETURNING admin_id, user_id
+        "#
+    )
+    .bind(session_id)
+    .bind(ended_by)
+    .fetch_optional(pool)
+    .await?;
+
+    let Some((admin_id, user_id)) = ended else {
+        return Ok(false);
+    };
+
+    revocation::publish(pool, cache, &[RevocationEvent::Session(session_id)]).await;
+
+    impersonation::record(pool, session_id, ImpersonationEventType::Stop, None, ip_address, None).await;
+    audit::record(
+        pool,
+        SecurityEventType::ImpersonationStopped,
+        Some(user_id),
+        Some(ended_by.unwrap_or(admin_id)),
+        ip_address,
+        &format!("Impersonation {session_id} stopped"),
+    )
+    .await;
+
+    Ok(true)
+}
+
+// Endpoint for the impersonation token itself: end the current impersonation
+pub async fn stop_current_impersonation(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheManager>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let impersonation = req.extensions().get::<Impersonation>().copied();
+    let Some(impersonation) = impersonation else {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "This token is not an impersonation token"
+        )));
+    };
+
+    match end_session(&pool, &cache, impersonation.session_id, None, client_ip(&req).as_deref()).await {
+        Ok(_) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "Impersonation stopped"
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to stop impersonation"
+            )))
+        }
+    }
+}
+
+// Admin endpoint: end any open impersonation session
+pub async fn stop_impersonation(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheManager>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse> {
+    let admin_id: i32 = {
+        let extensions = req.extensions();
+        let claims = extensions.get::<Claims>().unwrap();
+        claims.sub.parse().unwrap()
+    };
+
+    match end_session(&pool, &cache, path.into_inner(), Some(admin_id), client_ip(&req).as_deref()).await {
+        Ok(true) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "Impersonation stopped"
+            )))
+        }
+        Ok(false) => {
+            Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+                "No active impersonation with that id"
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to stop impersonation"
+            )))
+        }
+    }
+}
+
+pub async fn list_impersonations(
+    pool: web::Data<PgPool>,
+    query: web::Query<ImpersonationQuery>,
+) -> Result<HttpResponse> {
+    let limit = query.limit.unwrap_or(100).clamp(1, 500);
+
+    let sessions_result = sqlx::query_as::<_, ImpersonationSession>(
+        r#"
+        SELECT id, admin_id, user_id, reason, ip_address, started_at, expires_at, last_seen_at, ended_at, ended_by
+        FROM impersonation_sessions
+        WHERE ($1::INTEGER IS NULL OR admin_id = $1)
+          AND ($2::INTEGER IS NULL OR user_id = $2)
+          AND ($3::BOOLEAN IS NULL OR (ended_at IS NULL AND expires_at > CURRENT_TIMESTAMP) = $3)
+        ORDER BY started_at DESC
+        LIMIT $4
+        "#
+    )
+    .bind(query.admin_id)
+    .bind(query.user_id)
+    .bind(query.active)
+    .bind(limit)
+    .fetch_all(pool.get_ref())
+    .await;
+
+    match sessions_result {
+        Ok(sessions) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Impersonations retrieved successfully",
+                sessions
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )))
+        }
+    }
+}
+
+// Admin endpoint: the full trail of one impersonation session
+pub async fn list_impersonation_events(
+    pool: web::Data<PgPool>,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse> {
+    let events_result = sqlx::query_as::<_, ImpersonationEvent>(
+        r#"
+        SELECT id, event_type, method, path, status_code, ip_address, details, created_at
+        FROM impersonation_events
+        WHERE session_id = $1
+        ORDER BY created_at, id
+        "#
+    )
+    .bind(path.into_inner())
+    .fetch_all(pool.get_ref())
+    .await;
+
+    match events_result {
+        Ok(events) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Impersonation events retrieved successfully",
+                events
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )))
+        }
+    }
+}
@@ -0,0 +1,603 @@
+use actix_web::{web, HttpResponse, HttpRequest, HttpMessage, Result, Scope};
+use sqlx::PgPool;
+use chrono::Utc;
+
+use crate::models::{
+    ApiResponse, Claims, CreateJobRequest, Job, JobSearchParams, JobSearchQuery, MyJobsQuery, UpdateJobRequest,
+    UpdateJobStatusRequest
+};
+use crate::api_keys;
+use crate::config::Config;
+use crate::cache::CacheManager;
+use crate::companies::{self, CompanyRole};
+use crate::jobs::{self, contains_pattern, JobStatus, JOB_COLUMNS};
+use crate::job_search::{self, JobSearch};
+use crate::middleware::require_permission;
+use crate::permissions::Permission;
+use crate::pipelines;
+use crate::handlers::{applications, pipelines as pipeline_handlers};
+use crate::handlers::verification::{self, VerifiedAction};
+
+fn user_id(req: &HttpRequest) -> i32 {
+    let extensions = req.extensions();
+    let claims = extensions.get::<Claims>().unwrap();
+    claims.sub.parse().unwrap()
+}
+
+fn expiry_in_past() -> HttpResponse {
+    HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+        "expires_at must be in the future"
+    ))
+}
+
+fn job_not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(ApiResponse::<()>::error(
+        "Job not found"
+    ))
+}
+
+fn database_error() -> HttpResponse {
+    HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+        "Database error"
+    ))
+}
+
+/// A posting of one of the caller's companies with their role there, or 404 so
+/// other companies' postings are not revealed
+pub async fn member_job(pool: &PgPool, req: &HttpRequest, job_id: i32) -> Result<(Job, CompanyRole), HttpResponse> {
+    let job = match jobs::find(pool, job_id).await {
+        Ok(Some(job)) => job,
+        Ok(None) => return Err(job_not_found()),
+        Err(_) => return Err(database_error()),
+    };
+    if !api_keys::reaches_company(req, job.company_id) {
+        return Err(job_not_found());
+    }
+    match companies::member_role(pool, job.company_id, user_id(req)).await {
+        Ok(Some(role)) => Ok((job, role)),
+        Ok(None) => Err(job_not_found()),
+        Err(_) => Err(database_error()),
+    }
+}
+
+/// A posting the caller may change: one of a company where they are an owner or recruiter
+pub async fn managed_job(pool: &PgPool, req: &HttpRequest, job_id: i32) -> Result<Job, HttpResponse> {
+    match member_job(pool, req, job_id).await? {
+        (job, role) if role.can_manage_jobs() => Ok(job),
+        _ => Err(HttpResponse::Forbidden().json(ApiResponse::<()>::error(
+            "Viewers cannot change this company's postings or applicants"
+        ))),
+    }
+}
+
+/// A posting whose pipeline and applicants the caller may see: any member of its
+/// company, or roles with `applications:read_any`
+pub async fn visible_job(pool: &PgPool, req: &HttpRequest, job_id: i32) -> Result<Job, HttpResponse> {
+    if !applications::can_read_any(req) {
+        return member_job(pool, req, job_id).await.map(|(job, _)| job);
+    }
+    match jobs::find(pool, job_id).await {
+        Ok(Some(job)) => Ok(job),
+        Ok(None) => Err(job_not_found()),
+        Err(_) => Err(database_error()),
+    }
+}
+
+/// The company a new posting is for: the one asked for, or the caller's only one.
+/// With a company key that is always the key's company.
+async fn posting_company(pool: &PgPool, req: &HttpRequest, requested: Option<i32>) -> Result<i32, HttpResponse> {
+    let mut company_ids = companies::posting_companies(pool, user_id(req)).await.map_err(|_| database_error())?;
+    company_ids.retain(|company_id| api_keys::reaches_company(req, *company_id));
+    match (requested, company_ids.as_slice()) {
+        (Some(company_id), _) if company_ids.contains(&company_id) => Ok(company_id),
+        (Some(_), _) => Err(HttpResponse::Forbidden().json(ApiResponse::<()>::error(
+            "You can only post jobs for companies where you are an owner or recruiter"
+        ))),
+        (None, [company_id]) => Ok(*company_id),
+        (None, []) => Err(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Create a company or join one as a recruiter before posting jobs"
+        ))),
+        (None, _) => Err(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "company_id is required when you post jobs for several companies"
+        ))),
+    }
+}
+
+// Create a posting as a draft
+pub async fn create_job(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheManager>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    job_data: web::Json<CreateJobRequest>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_permission(&req, Permission::JobsPost) {
+        return Ok(response);
+    }
+
+    let employer_id = user_id(&req);
+    if let Err(response) = verification::ensure_email_verified(
+        &pool, &cache, &config, employer_id, VerifiedAction::PostJobs,
+    ).await {
+        return Ok(response);
+    }
+
+    let job = match jobs::validate(job_data.into_inner()) {
+        Ok(job) => job,
+        Err(message) => return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(&message))),
+    };
+    if job.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+        return Ok(expiry_in_past());
+    }
+
+    let company_id = match posting_company(&pool, &req, job.company_id).await {
+        Ok(company_id) => company_id,
+        Err(response) => return Ok(response),
+    };
+
+    // The posting and its pipeline, copied from the default template, are created together
+    let insert_result: Result<Option<i32>, sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+        if !jobs::within_posting_limit(&mut tx, company_id, config.unverified_company_job_limit).await? {
+            return Ok(None);
+        }
+
+        let job_id: i32 = sqlx::query_scalar(
+            r#"
+            INSERT INTO jobs (company_id, employer_id, title, description, employment_type, seniority, location,
+                              remote_policy, salary_min, salary_max, salary_currency, skills, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+            RETURNING id
+            "#
+        )
+        .bind(company_id)
+        .bind(employer_id)
+        .bind(&job.title)
+        .bind(&job.description)
+        .bind(&job.employment_type)
+        .bind(&job.seniority)
+        .bind(&job.location)
+        .bind(&job.remote_policy)
+        .bind(job.salary_min)
+        .bind(job.salary_max)
+        .bind(&job.salary_currency)
+        .bind(&job.skills)
+        .bind(job.expires_at)
+        .fetch_one(&mut tx)
+        .await?;
+
+        pipelines::create_default(&mut tx, job_id).await?;
+        tx.commit().await?;
+        Ok(Some(job_id))
+    }
+    .await;
+
+    let created = match insert_result {
+        Ok(Some(job_id)) => jobs::find(&pool, job_id).await,
+        Ok(None) => {
+            return Ok(HttpResponse::Forbidden().json(ApiResponse::<()>::error(&format!(
+                "Unverified companies can have at most {} draft or published postings; verify the company's domain to post more",
+                config.unverified_company_job_limit
+            ))));
+        }
+        Err(e) => Err(e),
+    };
+
+    match created {
+        Ok(Some(job)) => {
+            Ok(HttpResponse::Created().json(ApiResponse::success(
+                "Job created as a draft; publish it to list it",
+                job
+            )))
+        }
+        _ => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to create job"
+            )))
+        }
+    }
+}
+
+// Browse published postings, newest first
+pub async fn list_jobs(
+    pool: web::Data<PgPool>,
+    query: web::Query<JobSearchQuery>,
+) -> Result<HttpResponse> {
+    let limit = query.limit.unwrap_or(20).clamp(1, 100);
+    let offset = query.offset.unwrap_or(0).max(0);
+
+    let filters = [
+        ("employment_type", &query.employment_type, jobs::EMPLOYMENT_TYPES),
+        ("seniority", &query.seniority, jobs::SENIORITIES),
+        ("remote_policy", &query.remote_policy, jobs::REMOTE_POLICIES),
+    ];
+    for (field, value, allowed) in filters {
+        if value.as_deref().is_some_and(|value| !allowed.contains(&value)) {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(&format!(
+                "{field} must be one of: {}",
+                allowed.join(", ")
+            ))));
+        }
+    }
+
+    let search = query.q.as_deref().filter(|q| !q.trim().is_empty()).map(contains_pattern);
+    let location = query.location.as_deref().filter(|l| !l.trim().is_empty()).map(contains_pattern);
+    let skill = query.skill.as_deref().map(|skill| skill.trim().to_lowercase()).filter(|skill| !skill.is_empty());
+
+    let jobs_result = sqlx::query_as::<_, Job>(&format!(
+        r#"
+        SELECT {JOB_COLUMNS}
+        FROM jobs j
+        JOIN companies c ON c.id = j.company_id
+        LEFT JOIN users u ON u.id = j.employer_id
+        WHERE j.status = 'published' AND (j.expires_at IS NULL OR j.expires_at > CURRENT_TIMESTAMP)
+          AND COALESCE(u.is_active, true)
+          AND ($1::TEXT IS NULL OR j.title ILIKE $1 OR j.description ILIKE $1)
+          AND ($2::VARCHAR IS NULL OR j.employment_type = $2)
+          AND ($3::VARCHAR IS NULL OR j.seniority = $3)
+          AND ($4::VARCHAR IS NULL OR j.remote_policy = $4)
+          AND ($5::TEXT IS NULL OR j.location ILIKE $5)
+          AND ($6::TEXT IS NULL OR j.skills @> ARRAY[$6::TEXT])
+          AND ($7::INTEGER IS NULL OR COALESCE(j.salary_max, j.salary_min) >= $7)
+          AND ($8::INTEGER IS NULL OR j.company_id = $8)
+        ORDER BY j.published_at DESC, j.id DESC
+        LIMIT $9 OFFSET $10
+        "#
+    ))
+    .bind(search)
+    .bind(&query.employment_type)
+    .bind(&query.seniority)
+    .bind(&query.remote_policy)
+    .bind(location)
+    .bind(skill)
+    .bind(query.min_salary)
+    .bind(query.company_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool.get_ref())
+    .await;
+
+    match jobs_result {
+        Ok(jobs) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Jobs retrieved successfully",
+                jobs
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )))
+        }
+    }
+}
+
+// Full-text search over published postings with filters and facet counts
+pub async fn search_jobs(
+    pool: web::Data<PgPool>,
+    query: web::Query<JobSearchParams>,
+) -> Result<HttpResponse> {
+    let search = match JobSearch::from_params(&query) {
+        Ok(search) => search,
+        Err(message) => return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(&message))),
+    };
+
+    match job_search::search(&pool, &search).await {
+        Ok(results) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Search completed successfully",
+                results
+            )))
+        }
+        Err(e) => {
+            log::error!("Job search failed: {e}");
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )))
+        }
+    }
+}
+
+// The postings of the caller's companies in every status
+pub async fn list_my_jobs(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    query: web::Query<MyJobsQuery>,
+) -> Result<HttpResponse> {
+    if query.status.as_deref().is_some_and(|status| JobStatus::parse(status).is_none()) {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "status must be one of: draft, published, closed, expired"
+        )));
+    }
+
+    let jobs_result = sqlx::query_as::<_, Job>(&format!(
+        r#"
+        SELECT {JOB_COLUMNS}
+        FROM jobs j
+        JOIN companies c ON c.id = j.company_id
+        WHERE j.company_id IN (SELECT company_id FROM company_members WHERE user_id = $1)
+          AND ($2::INTEGER IS NULL OR j.company_id = $2)
+          AND ($3::VARCHAR IS NULL OR j.status = $3)
+          AND ($4::INTEGER IS NULL OR j.company_id = $4)
+        ORDER BY j.updated_at DESC
+        "#
+    ))
+    .bind(user_id(&req))
+    .bind(query.company_id)
+    .bind(&query.status)
+    .bind(api_keys::key_company(&req))
+    .fetch_all(pool.get_ref())
+    .await;
+
+    match jobs_result {
+        Ok(jobs) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Jobs retrieved successfully",
+                jobs
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )))
+        }
+    }
+}
+
+// A posting; drafts are only visible to members of its company
+pub async fn get_job(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let job_id = path.into_inner();
+    let job = match jobs::find(&pool, job_id).await {
+        Ok(Some(job)) if job.status != JobStatus::Draft.as_str() => job,
+        Ok(Some(_)) => match member_job(&pool, &req, job_id).await {
+            Ok((job, _)) => job,
+            Err(response) => return Ok(response),
+        },
+        Ok(None) => return Ok(job_not_found()),
+        Err(_) => return Ok(database_error()),
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(
+        "Job found",
+        job
+    )))
+}
+
+// Edit a draft or published posting; omitted fields are kept
+pub async fn update_job(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+    job_data: web::Json<UpdateJobRequest>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_permission(&req, Permission::JobsPost) {
+        return Ok(response);
+    }
+
+    let current = match managed_job(&pool, &req, path.into_inner()).await {
+        Ok(job) => job,
+        Err(response) => return Ok(response),
+    };
+
+    if !JobStatus::parse(&current.status).is_some_and(|status| status.is_editable()) {
+        return Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(&format!(
+            "A {} posting can no longer be edited",
+            current.status
+        ))));
+    }
+
+    let changes = job_data.into_inner();
+    if changes.expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+        return Ok(expiry_in_past());
+    }
+
+    let job = match jobs::validate(jobs::merge(&current, changes)) {
+        Ok(job) => job,
+        Err(message) => return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(&message))),
+    };
+
+    let update_result = sqlx::query(
+        r#"
+        UPDATE jobs
+        SET title = $1, description = $2, employment_type = $3, seniority = $4, location = $5, remote_policy = $6,
+            salary_min = $7, salary_max = $8, salary_currency = $9, skills = $10, expires_at = $11,
+            updated_at = CURRENT_TIMESTAMP
+        WHERE id = $12 AND status IN ('draft', 'published')
+        "#
+    )
+    .bind(&job.title)
+    .bind(&job.description)
+    .bind(&job.employment_type)
+    .bind(&job.seniority)
+    .bind(&job.location)
+    .bind(&job.remote_policy)
+    .bind(job.salary_min)
+    .bind(job.salary_max)
+    .bind(&job.salary_currency)
+    .bind(&job.skills)
+    .bind(job.expires_at)
+    .bind(current.id)
+    .execute(pool.get_ref())
+    .await;
+
+    match update_result {
+        Ok(result) if result.rows_affected() == 0 => {
+            Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(
+                "The posting was closed or expired in the meantime"
+            )))
+        }
+        Ok(_) => match jobs::find(&pool, current.id).await {
+            Ok(Some(job)) => Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Job updated successfully",
+                job
+            ))),
+            _ => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            ))),
+        },
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to update job"
+            )))
+        }
+    }
+}
+
+// Publish a draft or close a published posting
+pub async fn update_job_status(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheManager>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+    status_data: web::Json<UpdateJobStatusRequest>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_permission(&req, Permission::JobsPost) {
+        return Ok(response);
+    }
+
+    let Some(next) = JobStatus::parse(&status_data.status) else {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "status must be one of: published, closed"
+        )));
+    };
+
+    let current = match managed_job(&pool, &req, path.into_inner()).await {
+        Ok(job) => job,
+        Err(response) => return Ok(response),
+    };
+
+    let Some(from) = JobStatus::parse(&current.status).filter(|from| from.can_become(next)) else {
+        return Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(&format!(
+            "A {} posting cannot become {}",
+            current.status,
+            next.as_str()
+        ))));
+    };
+
+    let expires_at = match next {
+        JobStatus::Published => {
+            if let Err(response) = verification::ensure_email_verified(
+                &pool, &cache, &config, user_id(&req), VerifiedAction::PostJobs,
+            ).await {
+                return Ok(response);
+            }
+
+            match current.expires_at {
+                Some(expires_at) if expires_at <= Utc::now() => {
+                    return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+                        "expires_at has passed; set a new one before publishing"
+                    )));
+                }
+                Some(expires_at) => Some(expires_at),
+                None => Some(jobs::default_expiry(config.job_posting_lifetime_days)),
+            }
+        }
+        _ => current.expires_at,
+    };
+
+    // Only move from the status that was checked, in case the posting changed meanwhile
+    let update_result = sqlx::query(
+        r#"
+        UPDATE jobs
+        SET status = $1, expires_at = $2, updated_at = CURRENT_TIMESTAMP,
+            published_at = CASE WHEN $1 = 'published' THEN CURRENT_TIMESTAMP ELSE published_at END,
+            closed_at = CASE WHEN $1 = 'closed' THEN CURRENT_TIMESTAMP ELSE closed_at END
+        WHERE id = $3 AND status = $4
+        "#
+    )
+    .bind(next.as_str())
+    .bind(expires_at)
+    .bind(current.id)
+    .bind(from.as_str())
+    .execute(pool.get_ref())
+    .await;
+
+    match update_result {
+        Ok(result) if result.rows_affected() == 0 => {
+            Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(
+                "The posting changed in the meantime; reload it and try again"
+            )))
+        }
+        Ok(_) => match jobs::find(&pool, current.id).await {
+            Ok(Some(job)) => Ok(HttpResponse::Ok().json(ApiResponse::success(
+                &format!("Job {}", next.as_str()),
+                job
+            ))),
+            _ => Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            ))),
+        },
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to update job status"
+            )))
+        }
+    }
+}
+
+// Delete one of the caller's postings that nobody has applied to
+pub async fn delete_job(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_permission(&req, Permission::JobsPost) {
+        return Ok(response);
+    }
+
+    let job = match managed_job(&pool, &req, path.into_inner()).await {
+        Ok(job) => job,
+        Err(response) => return Ok(response),
+    };
+
+    // Applicants keep their applications; a job that has any can only be closed
+    let result = sqlx::query(
+        "DELETE FROM jobs WHERE id = $1 AND NOT EXISTS (SELECT 1 FROM applications WHERE job_id = $1)"
+    )
+    .bind(job.id)
+    .execute(pool.get_ref())
+    .await;
+
+    match result {
+        Ok(result) if result.rows_affected() > 0 => {
+            Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "Job deleted"
+            )))
+        }
+        Ok(_) => {
+            Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(
+                "This job has applications; close it instead of deleting it"
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to delete job"
+            )))
+        }
+    }
+}
+
+pub fn job_routes() -> Scope {
+    web::scope("/jobs")
+        .route("", web::get().to(list_jobs))
+        .route("", web::post().to(create_job))
+        // Registered before /{id} so "search" and "mine" are not read as ids
+        .route("/search", web::get().to(search_jobs))
+        .route("/mine", web::get().to(list_my_jobs))
+        .route("/{id}", web::get().to(get_job))
+        .route("/{id}", web::put().to(update_job))
+        .route("/{id}", web::delete().to(delete_job))
+        .route("/{id}/status", web::put().to(update_job_status))
+        .route("/{id}/applications", web::get().to(applications::list_job_applications))
+        .route("/{id}/applications", web::post().to(applications::apply_to_job))
+        .route("/{id}/applications/move", web::post().to(pipeline_handlers::bulk_move_applications))
+        .route("/{id}/pipeline", web::get().to(pipeline_handlers::get_pipeline))
+        .route("/{id}/pipeline/stages", web::post().to(pipeline_handlers::create_stage))
+        .route("/{id}/pipeline/stages/{stage_id}", web::put().to(pipeline_handlers::update_stage))
+        .route("/{id}/pipeline/stages/{stage_id}", web::delete().to(pipeline_handlers::delete_stage))
+}
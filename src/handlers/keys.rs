@@ -0,0 +1,43 @@
+use actix_web::{web, HttpResponse, Result, HttpRequest, HttpMessage};
+use sqlx::PgPool;
+
+use crate::models::{ApiResponse, Claims, SigningKeyRotationResponse};
+use crate::keys::{self, KeyStore};
+
+// Public endpoint: keys other services use to verify our access tokens
+pub async fn jwks(keys: web::Data<KeyStore>) -> Result<HttpResponse> {
+    // Short cache so consumers see a rotated key before it starts signing
+    Ok(HttpResponse::Ok()
+        .insert_header(("Cache-Control", "public, max-age=30"))
+        .json(keys.jwks()))
+}
+
+// Start signing with a freshly generated key; the current key keeps verifying for the grace period
+pub async fn rotate_signing_key(
+    pool: web::Data<PgPool>,
+    keys: web::Data<KeyStore>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let admin_id = req.extensions().get::<Claims>().map(|claims| claims.sub.clone()).unwrap_or_default();
+
+    match keys::rotate(&pool, None).await {
+        Ok((kid, activates_at)) => {
+            // Publish it locally now; other replicas pick it up on their next refresh
+            if let Err(e) = keys.reload(&pool).await {
+                log::error!("Failed to reload JWT signing keys: {e}");
+            }
+            log::info!("Admin {admin_id} rotated the JWT signing key to {kid}, active from {activates_at}");
+
+            Ok(HttpResponse::Created().json(ApiResponse::success(
+                "Signing key rotated successfully",
+                SigningKeyRotationResponse { kid, activates_at }
+            )))
+        }
+        Err(e) => {
+            log::error!("Failed to rotate JWT signing key: {e}");
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to rotate signing key"
+            )))
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use actix_web::{web, HttpResponse, HttpRequest, Result};
+use sqlx::PgPool;
+use validator::Validate;
+use chrono::{DateTime, Duration, Utc};
+
+use crate::client_ip::client_ip;
+use crate::models::{ApiResponse, MagicLinkLoginRequest, MagicLinkRequest, User};
+use crate::utils::{generate_token, hash_token};
+use crate::config::Config;
+use crate::mailer::Mailer;
+use crate::throttle::{self, ThrottleKey};
+use crate::keys::KeyStore;
+use crate::handlers::auth::{finish_login, SessionDevice};
+
+// Response shared by every link request so it cannot be used to probe for accounts
+const MAGIC_LINK_MESSAGE: &str = "If an account with that email exists, a login link has been sent";
+
+#[derive(sqlx::FromRow)]
+struct RecentRequests {
+    email_count: i64,
+    email_oldest: Option<DateTime<Utc>>,
+    ip_count: i64,
+    ip_oldest: Option<DateTime<Utc>>,
+}
+
+/// Seconds until the caller may request another link, if either limit has been reached
+async fn rate_limited(
+    pool: &PgPool,
+    config: &Config,
+    email: &str,
+    ip_address: Option<&str>,
+) -> Result<Option<i64>, sqlx::Error> {
+    let now = Utc::now();
+    let window_start = now - Duration::seconds(config.magic_link_window);
+
+    let recent: RecentRequests = sqlx::query_as(
+        r#"
+        SELECT COUNT(*) FILTER (WHERE email = $1) AS email_count,
+               MIN(created_at) FILTER (WHERE email = $1) AS email_oldest,
+               COUNT(*) FILTER (WHERE ip_address = $2) AS ip_count,
+               MIN(created_at) FILTER (WHERE ip_address = $2) AS ip_oldest
+        FROM magic_link_requests
+        WHERE created_at > $3 AND (email = $1 OR ip_address = $2)
+        "#
+    )
+    .bind(email)
+    .bind(ip_address)
+    .bind(window_start)
+    .fetch_one(pool)
+    .await?;
+
+    let retry_after = |oldest: Option<DateTime<Utc>>| {
+        let oldest = oldest.unwrap_or(now);
+        (oldest + Duration::seconds(config.magic_link_window) - now).num_seconds().max(0) + 1
+    };
+
+    let mut limited = None;
+    if recent.email_count >= config.magic_link_max_per_email {
+        limited = Some(retry_after(recent.email_oldest));
+    }
+    if recent.ip_count >= config.magic_link_max_per_ip {
+        limited = limited.max(Some(retry_after(recent.ip_oldest)));
+    }
+    Ok(limited)
+}
+
+// Public endpoint: email a single-use login link
+pub async fn request_magic_link(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    mailer: web::Data<Mailer>,
+    req: HttpRequest,
+    request_data: web::Json<MagicLinkRequest>,
+) -> Result<HttpResponse> {
+    if request_data.validate().is_err() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Valid email is required"
+        )));
+    }
+
+    let email = request_data.into_inner().email.trim().to_string();
+    let rate_key = email.to_lowercase();
+    // The connecting address, or the client behind a trusted proxy; never a client-supplied header
+    let ip_address = client_ip(&req);
+
+    // Limits apply whether or not the account exists, so they reveal nothing about it
+    match rate_limited(&pool, &config, &rate_key, ip_address.as_deref()).await {
+        Ok(None) => {}
+        Ok(Some(retry_after)) => {
+            return Ok(HttpResponse::TooManyRequests()
+                .insert_header(("Retry-After", retry_after.to_string()))
+                .json(ApiResponse::<()>::error(&format!(
+                    "Too many login links requested. Try again in {retry_after} seconds"
+                ))));
+        }
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    }
+
+    if sqlx::query("INSERT INTO magic_link_requests (email, ip_address) VALUES ($1, $2)")
+        .bind(&rate_key)
+        .bind(&ip_address)
+        .execute(pool.get_ref())
+        .await
+        .is_err()
+    {
+        return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            "Database error"
+        )));
+    }
+
+    // Look up and deliver in the background so the response time is the same
+    // whether or not the account exists
+    actix_web::rt::spawn(async move {
+        if let Err(e) = send_magic_link(&pool, &config, &mailer, &email).await {
+            log::error!("Failed to process magic link request: {e}");
+        }
+    });
+
+    Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(MAGIC_LINK_MESSAGE)))
+}
+
+async fn send_magic_link(
+    pool: &PgPool,
+    config: &Config,
+    mailer: &Mailer,
+    email: &str,
+) -> anyhow::Result<()> {
+    let user: Option<(i32, String)> = sqlx::query_as(
+        "SELECT id, email FROM users WHERE email = $1 AND is_active = true"
+    )
+    .bind(email)
+    .fetch_optional(pool)
+    .await?;
+
+    let Some((user_id, email)) = user else {
+        return Ok(());
+    };
+
+    let token = generate_token();
+    let expires_at = Utc::now() + Duration::seconds(config.magic_link_expiration);
+
+    let mut tx = pool.begin().await?;
+
+    // Only the most recently requested link stays usable
+    sqlx::query(
+        "UPDATE magic_link_tokens SET used_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND used_at IS NULL"
+    )
+    .bind(user_id)
+    .execute(&mut tx)
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO magic_link_tokens (user_id, email, token_hash, expires_at) VALUES ($1, $2, $3, $4)"
+    )
+    .bind(user_id)
+    .bind(&email)
+    .bind(hash_token(&token))
+    .bind(expires_at)
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let login_link = format!("{}/magic-link?token={token}", config.app_base_url);
+    let body = format!(
+        "Use the link below to sign in. It expires in {} minutes and can only be used once.\n\n\
+         {login_link}\n\n\
+         If you did not request this, you can ignore this email.",
+        config.magic_link_expiration / 60
+    );
+    mailer.send(&email, "Your login link", &body).await?;
+
+    Ok(())
+}
+
+// Public endpoint: exchange the token from a login link for a session
+pub async fn login_with_magic_link(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    keys: web::Data<KeyStore>,
+    req: HttpRequest,
+    login_data: web::Json<MagicLinkLoginRequest>,
+) -> Result<HttpResponse> {
+    if login_data.token.trim().is_empty() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Login token is required"
+        )));
+    }
+
+    let device = SessionDevice::from_request(&req, login_data.device_label.clone());
+    let ip_key: Vec<ThrottleKey> = device.ip_address.clone().map(ThrottleKey::Ip).into_iter().collect();
+
+    // Guessing tokens counts towards the same per-IP lockout as guessing passwords
+    if let Err(error_response) = throttle::check(&pool, &ip_key).await {
+        return Ok(error_response);
+    }
+
+    // Consume the token; the used_at check makes it single-use even under concurrent requests.
+    // The link only works for the address it was sent to.
+    let user = sqlx::query_as::<_, User>(
+        r#"
+        WITH consumed AS (
+            UPDATE magic_link_tokens SET used_at = CURRENT_TIMESTAMP
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+            RETURNING user_id, email
+        )
+        SELECT u.id, u.username, u.email, u.password_hash, u.first_name, u.last_name, u.phone, u.role, u.professional_role, u.company_name, u.is_active, u.email_verified, u.created_at, u.updated_at
+        FROM users u
+        JOIN consumed c ON c.user_id = u.id AND c.email = u.email
+        WHERE u.is_active = true
+        "#
+    )
+    .bind(hash_token(login_data.token.trim()))
+    .fetch_optional(pool.get_ref())
+    .await;
+
+    match user {
+        Ok(Some(user)) => Ok(finish_login(&pool, &config, &keys, user, device).await),
+        Ok(None) => {
+            throttle::record_failure(&pool, &config, &ip_key, None, device.ip_address.as_deref()).await;
+            Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
+                "Invalid or expired login link"
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )))
+        }
+    }
+}
+
+/// Drop expired login links and request records outside the rate limit window
+pub async fn cleanup(pool: &PgPool, config: &Config) -> Result<(), sqlx::Error> {
+    let window_start = Utc::now() - Duration::seconds(config.magic_link_window);
+
+    sqlx::query("DELETE FROM magic_link_requests WHERE created_at < $1")
+        .bind(window_start)
+        .execute(pool)
+        .await?;
+
+    sqlx::query("DELETE FROM magic_link_tokens WHERE expires_at < CURRENT_TIMESTAMP")
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
@@ -1,3 +1,20 @@
 pub mod auth;
 pub mod users;
-pub mod health; 
\ No newline at end of file
+pub mod health;
+pub mod verification;
+pub mod sessions;
+pub mod admin;
+pub mod two_factor;
+pub mod keys;
+pub mod oauth;
+pub mod magic_link;
+pub mod impersonation;
+pub mod api_keys;
+pub mod accounts;
+pub mod roles;
+pub mod jobs;
+pub mod companies;
+pub mod company_verification;
+pub mod applications;
+pub mod pipelines;
+pub mod candidate_profiles;
@@ -0,0 +1,552 @@
+use actix_web::{web, HttpResponse, Result, HttpRequest, HttpMessage};
+use sqlx::PgPool;
+use chrono::{Duration, Utc};
+use rand::RngCore;
+
+use crate::client_ip::client_ip;
+use crate::models::{
+    ApiResponse, Claims, LinkedIdentity, OAuthAuthorizeRequest, OAuthAuthorizeResponse,
+    OAuthCallbackRequest, User,
+};
+use crate::utils::{generate_token, hash_token, PasswordHasher};
+use crate::config::{Config, OidcProviderConfig};
+use crate::keys::KeyStore;
+use crate::mailer::Mailer;
+use crate::oidc::{ExternalIdentity, OidcClient};
+use crate::audit::{self, SecurityEventType};
+use crate::accounts;
+use crate::cache::CacheManager;
+use crate::handlers::auth::{finish_login, SessionDevice};
+use crate::handlers::verification;
+
+// Users have 10 minutes to finish signing in at the provider
+const OAUTH_STATE_EXPIRATION_SECONDS: i64 = 600;
+
+fn find_provider<'a>(config: &'a Config, name: &str) -> Option<&'a OidcProviderConfig> {
+    config.oidc_providers.iter().find(|provider| provider.name == name)
+}
+
+fn redirect_uri(config: &Config, provider: &OidcProviderConfig) -> String {
+    format!("{}/{}", config.oidc_redirect_url, provider.name)
+}
+
+fn unknown_provider() -> HttpResponse {
+    HttpResponse::NotFound().json(ApiResponse::<()>::error("Unknown identity provider"))
+}
+
+/// Store state, nonce and PKCE verifier, then hand the provider URL to the client
+async fn start_authorization(
+    pool: &PgPool,
+    config: &Config,
+    oidc: &OidcClient,
+    provider: &OidcProviderConfig,
+    link_user_id: Option<i32>,
+    device_label: Option<String>,
+) -> HttpResponse {
+    let request = match oidc.authorization_request(provider, &redirect_uri(config, provider)).await {
+        Ok(request) => request,
+        Err(e) => {
+            log::error!("Failed to start {} sign-in: {e}", provider.name);
+            return HttpResponse::BadGateway().json(ApiResponse::<()>::error(
+                "Identity provider is unavailable"
+            ));
+        }
+    };
+
+    let device_label = device_label
+        .map(|label| label.trim().chars().take(100).collect::<String>())
+        .filter(|label| !label.is_empty());
+
+    let insert_result = sqlx::query(
+        r#"
+        INSERT INTO oauth_states (state_hash, provider, nonce, code_verifier, link_user_id, device_label, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#
+    )
+    .bind(hash_token(&request.state))
+    .bind(&provider.name)
+    .bind(&request.nonce)
+    .bind(&request.code_verifier)
+    .bind(link_user_id)
+    .bind(device_label)
+    .bind(Utc::now() + Duration::seconds(OAUTH_STATE_EXPIRATION_SECONDS))
+    .execute(pool)
+    .await;
+
+    match insert_result {
+        Ok(_) => {
+            HttpResponse::Ok().json(ApiResponse::success(
+                "Redirect the user to the authorization URL",
+                OAuthAuthorizeResponse {
+                    authorization_url: request.url,
+                    state: request.state,
+                }
+            ))
+        }
+        Err(_) => {
+            HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to start sign-in"
+            ))
+        }
+    }
+}
+
+pub async fn list_providers(config: web::Data<Config>) -> Result<HttpResponse> {
+    let providers: Vec<&str> = config.oidc_providers.iter().map(|provider| provider.name.as_str()).collect();
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(
+        "Identity providers retrieved successfully",
+        providers
+    )))
+}
+
+// Public endpoint: begin "Sign in with <provider>"
+pub async fn authorize(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    oidc: web::Data<OidcClient>,
+    path: web::Path<String>,
+    authorize_data: Option<web::Json<OAuthAuthorizeRequest>>,
+) -> Result<HttpResponse> {
+    let Some(provider) = find_provider(&config, &path) else {
+        return Ok(unknown_provider());
+    };
+
+    let device_label = authorize_data.and_then(|data| data.into_inner().device_label);
+    Ok(start_authorization(&pool, &config, &oidc, provider, None, device_label).await)
+}
+
+#[derive(sqlx::FromRow)]
+struct OAuthState {
+    nonce: String,
+    code_verifier: String,
+    link_user_id: Option<i32>,
+    device_label: Option<String>,
+}
+
+enum Resolution {
+    /// Signed in before with this identity
+    Existing(User),
+    /// Matched an existing account by verified email and linked to it
+    LinkedByEmail(User),
+    /// New account created from the identity
+    Created(User),
+    /// An account with the email exists but linking it automatically is not safe
+    EmailTaken,
+    /// Nothing to match or create an account with
+    NoEmail,
+}
+
+const USER_COLUMNS: &str = "id, username, email, password_hash, first_name, last_name, phone, role, professional_role, company_name, is_active, email_verified, created_at, updated_at";
+
+/// Usernames to try for a new account: the provider's, then with random suffixes
+fn username_candidate(identity: &ExternalIdentity, attempt: usize) -> String {
+    let source = identity.preferred_username.clone()
+        .or_else(|| identity.email.as_deref().and_then(|email| email.split('@').next()).map(str::to_string))
+        .unwrap_or_default();
+
+    let mut base: String = source.to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-' { c } else { '_' })
+        .take(40)
+        .collect();
+    if base.len() < 3 {
+        base = "user".to_string();
+    }
+
+    if attempt == 0 {
+        base
+    } else {
+        let mut suffix = [0u8; 3];
+        rand::thread_rng().fill_bytes(&mut suffix);
+        format!("{base}_{}", hex::encode(suffix))
+    }
+}
+
+async fn resolve_user(
+    pool: &PgPool,
+    hasher: &PasswordHasher,
+    provider: &OidcProviderConfig,
+    identity: &ExternalIdentity,
+) -> Result<Resolution, sqlx::Error> {
+    let linked_user_id: Option<i32> = sqlx::query_scalar(
+        r#"
+        UPDATE user_identities SET last_login_at = CURRENT_TIMESTAMP, email = COALESCE($3, email)
+        WHERE provider = $1 AND subject = $2
+        RETURNING user_id
+        "#
+    )
+    .bind(&provider.name)
+    .bind(&identity.subject)
+    .bind(&identity.email)
+    .fetch_optional(pool)
+    .await?;
+
+    if let Some(user_id) = linked_user_id {
+        let user = sqlx::query_as::<_, User>(&format!("SELECT {USER_COLUMNS} FROM users WHERE id = $1"))
+            .bind(user_id)
+            .fetch_one(pool)
+            .await?;
+        return Ok(Resolution::Existing(user));
+    }
+
+    let Some(email) = &identity.email else {
+        return Ok(Resolution::NoEmail);
+    };
+
+    let existing = sqlx::query_as::<_, User>(&format!("SELECT {USER_COLUMNS} FROM users WHERE LOWER(email) = LOWER($1)"))
+        .bind(email)
+        .fetch_optional(pool)
+        .await?;
+
+    if let Some(user) = existing {
+        // Both sides must have proven ownership of the address, otherwise whoever
+        // registered it first (or controls the provider account) could take over the other
+        if !(identity.email_verified && user.email_verified) {
+            return Ok(Resolution::EmailTaken);
+        }
+
+        sqlx::query(
+            "INSERT INTO user_identities (user_id, provider, subject, email, last_login_at) VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP)"
+        )
+        .bind(user.id)
+        .bind(&provider.name)
+        .bind(&identity.subject)
+        .bind(email)
+        .execute(pool)
+        .await?;
+
+        return Ok(Resolution::LinkedByEmail(user));
+    }
+
+    // Nobody can log in with this password; a real one can be set through forgot-password
+    let password_hash = hasher.hash(&generate_token())
+        .await
+        .map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+    let first_name = identity.given_name.as_ref().map(|name| name.chars().take(50).collect::<String>());
+    let last_name = identity.family_name.as_ref().map(|name| name.chars().take(50).collect::<String>());
+
+    let mut tx = pool.begin().await?;
+    let mut created = None;
+    for attempt in 0..5 {
+        created = sqlx::query_as::<_, User>(&format!(
+            r#"
+            INSERT INTO users (username, email, password_hash, first_name, last_name, role, email_verified, has_password)
+            VALUES ($1, $2, $3, $4, $5, 'job_seeker', $6, false)
+            ON CONFLICT (username) DO NOTHING
+            RETURNING {USER_COLUMNS}
+            "#
+        ))
+        .bind(username_candidate(identity, attempt))
+        .bind(email)
+        .bind(&password_hash)
+        .bind(&first_name)
+        .bind(&last_name)
+        .bind(identity.email_verified)
+        .fetch_optional(&mut tx)
+        .await?;
+
+        if created.is_some() {
+            break;
+        }
+    }
+
+    let Some(user) = created else {
+        return Err(sqlx::Error::Protocol("Could not find a free username".to_string()));
+    };
+
+    sqlx::query(
+        "INSERT INTO user_identities (user_id, provider, subject, email, last_login_at) VALUES ($1, $2, $3, $4, CURRENT_TIMESTAMP)"
+    )
+    .bind(user.id)
+    .bind(&provider.name)
+    .bind(&identity.subject)
+    .bind(email)
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok(Resolution::Created(user))
+}
+
+/// Attach the identity to a signed-in user who started linking from their account
+async fn link_to_user(
+    pool: &PgPool,
+    provider: &OidcProviderConfig,
+    identity: &ExternalIdentity,
+    user_id: i32,
+    ip_address: Option<&str>,
+) -> HttpResponse {
+    let inserted: Result<Option<i32>, sqlx::Error> = sqlx::query_scalar(
+        r#"
+        INSERT INTO user_identities (user_id, provider, subject, email)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (provider, subject) DO NOTHING
+        RETURNING id
+        "#
+    )
+    .bind(user_id)
+    .bind(&provider.name)
+    .bind(&identity.subject)
+    .bind(&identity.email)
+    .fetch_optional(pool)
+    .await;
+
+    match inserted {
+        Ok(Some(_)) => {
+            audit::record(pool, SecurityEventType::IdentityLinked, Some(user_id), None, ip_address, &provider.name).await;
+
+            HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "Account linked successfully"
+            ))
+        }
+        Ok(None) => {
+            let owner: Result<Option<i32>, sqlx::Error> = sqlx::query_scalar(
+                "SELECT user_id FROM user_identities WHERE provider = $1 AND subject = $2"
+            )
+            .bind(&provider.name)
+            .bind(&identity.subject)
+            .fetch_optional(pool)
+            .await;
+
+            match owner {
+                Ok(Some(owner)) if owner == user_id => {
+                    HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                        "Account is already linked"
+                    ))
+                }
+                Ok(_) => {
+                    HttpResponse::Conflict().json(ApiResponse::<()>::error(
+                        "This account is already linked to another user"
+                    ))
+                }
+                Err(_) => {
+                    HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                        "Database error"
+                    ))
+                }
+            }
+        }
+        Err(_) => {
+            HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to link account"
+            ))
+        }
+    }
+}
+
+// Public endpoint: the client forwards the code and state from the provider redirect
+#[allow(clippy::too_many_arguments)] // actix extractors
+pub async fn callback(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    keys: web::Data<KeyStore>,
+    mailer: web::Data<Mailer>,
+    oidc: web::Data<OidcClient>,
+    hasher: web::Data<PasswordHasher>,
+    cache: web::Data<CacheManager>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    callback_data: web::Json<OAuthCallbackRequest>,
+) -> Result<HttpResponse> {
+    let Some(provider) = find_provider(&config, &path) else {
+        return Ok(unknown_provider());
+    };
+
+    // States are single-use and bound to the provider they were issued for
+    let state = sqlx::query_as::<_, OAuthState>(
+        r#"
+        DELETE FROM oauth_states
+        WHERE state_hash = $1 AND provider = $2 AND expires_at > CURRENT_TIMESTAMP
+        RETURNING nonce, code_verifier, link_user_id, device_label
+        "#
+    )
+    .bind(hash_token(&callback_data.state))
+    .bind(&provider.name)
+    .fetch_optional(pool.get_ref())
+    .await;
+
+    let OAuthState { nonce, code_verifier, link_user_id, device_label } = match state {
+        Ok(Some(state)) => state,
+        Ok(None) => {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+                "Invalid or expired sign-in attempt"
+            )));
+        }
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    };
+
+    let identity = match oidc.complete(provider, &callback_data.code, &code_verifier, &nonce, &redirect_uri(&config, provider)).await {
+        Ok(identity) => identity,
+        Err(e) => {
+            log::warn!("{} sign-in failed: {e}", provider.name);
+            return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
+                "Could not verify the sign-in with the identity provider"
+            )));
+        }
+    };
+
+    let device = SessionDevice::from_request(&req, device_label);
+
+    if let Some(user_id) = link_user_id {
+        return Ok(link_to_user(&pool, provider, &identity, user_id, device.ip_address.as_deref()).await);
+    }
+
+    let mut user = match resolve_user(&pool, &hasher, provider, &identity).await {
+        Ok(Resolution::Existing(user)) => user,
+        Ok(Resolution::LinkedByEmail(user)) => {
+            audit::record(
+                &pool,
+                SecurityEventType::IdentityLinked,
+                Some(user.id),
+                None,
+                device.ip_address.as_deref(),
+                &format!("{} linked automatically by verified email", provider.name),
+            )
+            .await;
+            user
+        }
+        Ok(Resolution::Created(user)) => {
+            log::info!("Created user {} from a {} sign-in", user.id, provider.name);
+            if !user.email_verified {
+                verification::spawn_verification_email(pool.clone(), config.clone(), mailer.clone(), user.id, user.email.clone());
+            }
+            user
+        }
+        Ok(Resolution::EmailTaken) => {
+            return Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(
+                "An account with this email already exists. Sign in with your password and link this provider from your account settings"
+            )));
+        }
+        Ok(Resolution::NoEmail) => {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+                "The identity provider did not share an email address"
+            )));
+        }
+        Err(e) => {
+            log::error!("Failed to resolve {} identity: {e}", provider.name);
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to sign in"
+            )));
+        }
+    };
+
+    if let Err(error_response) = accounts::ensure_can_sign_in(&pool, &cache, &mut user).await {
+        return Ok(error_response);
+    }
+
+    Ok(finish_login(&pool, &config, &keys, user, device).await)
+}
+
+fn user_id(req: &HttpRequest) -> i32 {
+    let extensions = req.extensions();
+    let claims = extensions.get::<Claims>().unwrap();
+    claims.sub.parse().unwrap()
+}
+
+pub async fn list_identities(pool: web::Data<PgPool>, req: HttpRequest) -> Result<HttpResponse> {
+    let identities_result = sqlx::query_as::<_, LinkedIdentity>(
+        r#"
+        SELECT id, provider, email, created_at, last_login_at
+        FROM user_identities
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#
+    )
+    .bind(user_id(&req))
+    .fetch_all(pool.get_ref())
+    .await;
+
+    match identities_result {
+        Ok(identities) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Linked accounts retrieved successfully",
+                identities
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )))
+        }
+    }
+}
+
+// Start linking a provider account to the signed-in user; finished by the public callback
+pub async fn link_identity(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    oidc: web::Data<OidcClient>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse> {
+    let Some(provider) = find_provider(&config, &path) else {
+        return Ok(unknown_provider());
+    };
+
+    Ok(start_authorization(&pool, &config, &oidc, provider, Some(user_id(&req)), None).await)
+}
+
+pub async fn unlink_identity(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    let user_id = user_id(&req);
+    let identity_id = path.into_inner();
+
+    // Never remove the last way to sign in
+    let sign_in_methods: Result<(bool, i64), sqlx::Error> = sqlx::query_as(
+        "SELECT has_password, (SELECT COUNT(*) FROM user_identities WHERE user_id = $1) FROM users WHERE id = $1"
+    )
+    .bind(user_id)
+    .fetch_one(pool.get_ref())
+    .await;
+
+    match sign_in_methods {
+        Ok((false, identities)) if identities <= 1 => {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+                "Set a password before unlinking your only sign-in method"
+            )));
+        }
+        Ok(_) => {}
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    }
+
+    let delete_result: Result<Option<String>, sqlx::Error> = sqlx::query_scalar(
+        "DELETE FROM user_identities WHERE id = $1 AND user_id = $2 RETURNING provider"
+    )
+    .bind(identity_id)
+    .bind(user_id)
+    .fetch_optional(pool.get_ref())
+    .await;
+
+    match delete_result {
+        Ok(Some(provider)) => {
+            let ip_address = client_ip(&req);
+            audit::record(&pool, SecurityEventType::IdentityUnlinked, Some(user_id), None, ip_address.as_deref(), &provider).await;
+
+            Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "Account unlinked successfully"
+            )))
+        }
+        Ok(None) => {
+            Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+                "Linked account not found"
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to unlink account"
+            )))
+        }
+    }
+}
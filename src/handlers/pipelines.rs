@@ -0,0 +1,468 @@
+use actix_web::{web, HttpResponse, HttpRequest, HttpMessage, Result};
+use sqlx::PgPool;
+
+use crate::models::{
+    ApiResponse, BulkMoveApplicationsRequest, BulkMoveResponse, Claims, CreateStageRequest, MoveApplicationRequest,
+    PipelineStage, UpdateStageRequest
+};
+use crate::applications;
+use crate::middleware::require_permission;
+use crate::permissions::Permission;
+use crate::pipelines::{self, StageKind, StageMove, MAX_BULK_MOVE, MAX_STAGES};
+use crate::handlers::applications::{can_read_any, involved_application};
+use crate::handlers::jobs::{managed_job, visible_job};
+
+fn user_id(req: &HttpRequest) -> i32 {
+    let extensions = req.extensions();
+    let claims = extensions.get::<Claims>().unwrap();
+    claims.sub.parse().unwrap()
+}
+
+fn database_error() -> HttpResponse {
+    HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+        "Database error"
+    ))
+}
+
+fn stage_not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(ApiResponse::<()>::error(
+        "Stage not found in this job's pipeline"
+    ))
+}
+
+fn duplicate_name(stages: &[PipelineStage], name: &str, except: Option<i32>) -> Option<HttpResponse> {
+    let taken = stages.iter()
+        .any(|stage| Some(stage.id) != except && stage.name.eq_ignore_ascii_case(name));
+    taken.then(|| HttpResponse::Conflict().json(ApiResponse::<()>::error(&format!(
+        "This pipeline already has a stage named {name}"
+    ))))
+}
+
+/// Where a 1-based requested position lands among `len` stages
+fn index_for(position: i32, len: usize) -> usize {
+    (position.max(1) as usize - 1).min(len)
+}
+
+async fn pipeline_response(pool: &PgPool, job_id: i32, message: &str, created: bool) -> HttpResponse {
+    match pipelines::stages(pool, job_id).await {
+        Ok(stages) if created => HttpResponse::Created().json(ApiResponse::success(message, stages)),
+        Ok(stages) => HttpResponse::Ok().json(ApiResponse::success(message, stages)),
+        Err(_) => database_error(),
+    }
+}
+
+// A job's pipeline stages in order, for members of its company or an admin
+pub async fn get_pipeline(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    match visible_job(&pool, &req, path.into_inner()).await {
+        Ok(job) => Ok(pipeline_response(&pool, job.id, "Pipeline retrieved successfully", false).await),
+        Err(response) => Ok(response),
+    }
+}
+
+// Add a stage to one of the caller's pipelines
+pub async fn create_stage(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+    stage_data: web::Json<CreateStageRequest>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_permission(&req, Permission::JobsPost) {
+        return Ok(response);
+    }
+
+    let job = match managed_job(&pool, &req, path.into_inner()).await {
+        Ok(job) => job,
+        Err(response) => return Ok(response),
+    };
+
+    let stage_data = stage_data.into_inner();
+    let name = match pipelines::validate_stage_name(&stage_data.name) {
+        Ok(name) => name,
+        Err(message) => return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(&message))),
+    };
+    let Some(kind) = StageKind::parse(stage_data.kind.as_deref().unwrap_or("active")) else {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "kind must be one of: active, hired, rejected"
+        )));
+    };
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return Ok(database_error()),
+    };
+    let stages = match pipelines::lock_stages(&mut tx, job.id).await {
+        Ok(stages) => stages,
+        Err(_) => return Ok(database_error()),
+    };
+
+    if stages.len() >= MAX_STAGES {
+        return Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(&format!(
+            "A pipeline can have at most {MAX_STAGES} stages"
+        ))));
+    }
+    if let Some(response) = duplicate_name(&stages, &name, None) {
+        return Ok(response);
+    }
+
+    // New stages go after the last active one unless a position is given,
+    // so they land before the hired and rejected outcomes
+    let index = match stage_data.position {
+        Some(position) => index_for(position, stages.len()),
+        None => stages.iter()
+            .rposition(|stage| stage.kind == StageKind::Active.as_str())
+            .map_or(0, |last_active| last_active + 1),
+    };
+
+    let insert_result: Result<i32, sqlx::Error> = sqlx::query_scalar(
+        "INSERT INTO pipeline_stages (job_id, name, kind, position) VALUES ($1, $2, $3, 0) RETURNING id"
+    )
+    .bind(job.id)
+    .bind(&name)
+    .bind(kind.as_str())
+    .fetch_one(&mut tx)
+    .await;
+
+    let stage_id = match insert_result {
+        Ok(stage_id) => stage_id,
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to add stage"
+            )));
+        }
+    };
+
+    let mut order: Vec<i32> = stages.iter().map(|stage| stage.id).collect();
+    order.insert(index, stage_id);
+    if pipelines::renumber(&mut tx, &order).await.is_err() || tx.commit().await.is_err() {
+        return Ok(database_error());
+    }
+
+    Ok(pipeline_response(&pool, job.id, "Stage added", true).await)
+}
+
+// Rename or reorder a stage of one of the caller's pipelines
+pub async fn update_stage(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<(i32, i32)>,
+    stage_data: web::Json<UpdateStageRequest>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_permission(&req, Permission::JobsPost) {
+        return Ok(response);
+    }
+
+    let (job_id, stage_id) = path.into_inner();
+    let job = match managed_job(&pool, &req, job_id).await {
+        Ok(job) => job,
+        Err(response) => return Ok(response),
+    };
+
+    let stage_data = stage_data.into_inner();
+    let name = match stage_data.name.as_deref().map(pipelines::validate_stage_name).transpose() {
+        Ok(name) => name,
+        Err(message) => return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(&message))),
+    };
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return Ok(database_error()),
+    };
+    let stages = match pipelines::lock_stages(&mut tx, job.id).await {
+        Ok(stages) => stages,
+        Err(_) => return Ok(database_error()),
+    };
+    let Some(current) = stages.iter().position(|stage| stage.id == stage_id) else {
+        return Ok(stage_not_found());
+    };
+
+    // History keeps the names stages had at the time, so renaming never rewrites it
+    if let Some(name) = &name {
+        if let Some(response) = duplicate_name(&stages, name, Some(stage_id)) {
+            return Ok(response);
+        }
+        let rename_result = sqlx::query("UPDATE pipeline_stages SET name = $1 WHERE id = $2")
+            .bind(name)
+            .bind(stage_id)
+            .execute(&mut tx)
+            .await;
+        if rename_result.is_err() {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to update stage"
+            )));
+        }
+    }
+
+    if let Some(position) = stage_data.position {
+        let mut order: Vec<i32> = stages.iter().map(|stage| stage.id).collect();
+        order.remove(current);
+        order.insert(index_for(position, order.len()), stage_id);
+        if pipelines::renumber(&mut tx, &order).await.is_err() {
+            return Ok(database_error());
+        }
+    }
+
+    if tx.commit().await.is_err() {
+        return Ok(database_error());
+    }
+
+    Ok(pipeline_response(&pool, job.id, "Stage updated", false).await)
+}
+
+// Remove an empty stage from one of the caller's pipelines
+pub async fn delete_stage(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_permission(&req, Permission::JobsPost) {
+        return Ok(response);
+    }
+
+    let (job_id, stage_id) = path.into_inner();
+    let job = match managed_job(&pool, &req, job_id).await {
+        Ok(job) => job,
+        Err(response) => return Ok(response),
+    };
+
+    let mut tx = match pool.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return Ok(database_error()),
+    };
+    let stages = match pipelines::lock_stages(&mut tx, job.id).await {
+        Ok(stages) => stages,
+        Err(_) => return Ok(database_error()),
+    };
+    let Some(stage) = stages.iter().find(|stage| stage.id == stage_id) else {
+        return Ok(stage_not_found());
+    };
+
+    // Every pipeline keeps somewhere to apply into, to hire into and to reject into
+    if !stages.iter().any(|other| other.id != stage.id && other.kind == stage.kind) {
+        return Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(&format!(
+            "A pipeline needs at least one {} stage",
+            stage.kind
+        ))));
+    }
+
+    // Withdrawn applications still sit in their last stage, so they count here too
+    let delete_result = sqlx::query(
+        "DELETE FROM pipeline_stages WHERE id = $1 AND NOT EXISTS (SELECT 1 FROM applications WHERE stage_id = $1)"
+    )
+    .bind(stage.id)
+    .execute(&mut tx)
+    .await;
+
+    match delete_result {
+        Ok(result) if result.rows_affected() == 0 => {
+            return Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(
+                "This stage has applications; move them to another stage first"
+            )));
+        }
+        Ok(_) => {}
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to delete stage"
+            )));
+        }
+    }
+
+    let order: Vec<i32> = stages.iter().map(|stage| stage.id).filter(|id| *id != stage_id).collect();
+    if pipelines::renumber(&mut tx, &order).await.is_err() || tx.commit().await.is_err() {
+        return Ok(database_error());
+    }
+
+    Ok(pipeline_response(&pool, job.id, "Stage deleted", false).await)
+}
+
+fn list_ids(ids: &[i32]) -> String {
+    ids.iter().map(|id| id.to_string()).collect::<Vec<_>>().join(", ")
+}
+
+/// Move applications to `job_id` into one of its stages, all or none. Every
+/// application must belong to the job and not be withdrawn.
+async fn move_to_stage(
+    pool: &PgPool,
+    job_id: i32,
+    application_ids: &[i32],
+    stage_id: i32,
+    moved_by: i32,
+    reason: Option<&str>,
+) -> Result<BulkMoveResponse, HttpResponse> {
+    let mut tx = pool.begin().await.map_err(|_| database_error())?;
+
+    // Share-locked so the stage cannot be deleted before the move commits
+    let stage: Option<(String, String)> = sqlx::query_as(
+        "SELECT name, kind FROM pipeline_stages WHERE id = $1 AND job_id = $2 FOR SHARE"
+    )
+    .bind(stage_id)
+    .bind(job_id)
+    .fetch_optional(&mut tx)
+    .await
+    .map_err(|_| database_error())?;
+    let Some((stage_name, kind)) = stage else {
+        return Err(stage_not_found());
+    };
+    let kind = StageKind::parse(&kind).ok_or_else(database_error)?;
+
+    // Locked in id order so concurrent moves of overlapping sets cannot deadlock
+    let found: Vec<(i32, String)> = sqlx::query_as(
+        "SELECT id, status FROM applications WHERE id = ANY($1) AND job_id = $2 ORDER BY id FOR UPDATE"
+    )
+    .bind(application_ids)
+    .bind(job_id)
+    .fetch_all(&mut tx)
+    .await
+    .map_err(|_| database_error())?;
+
+    let missing: Vec<i32> = application_ids.iter().copied()
+        .filter(|id| !found.iter().any(|(found_id, _)| found_id == id))
+        .collect();
+    if !missing.is_empty() {
+        return Err(HttpResponse::NotFound().json(ApiResponse::<()>::error(&format!(
+            "Applications not found for this job: {}",
+            list_ids(&missing)
+        ))));
+    }
+
+    let withdrawn: Vec<i32> = found.iter()
+        .filter(|(_, status)| status == applications::ApplicationStatus::Withdrawn.as_str())
+        .map(|(id, _)| *id)
+        .collect();
+    if !withdrawn.is_empty() {
+        return Err(HttpResponse::Conflict().json(ApiResponse::<()>::error(&format!(
+            "Withdrawn applications cannot be moved: {}",
+            list_ids(&withdrawn)
+        ))));
+    }
+
+    let to = StageMove { stage_id, stage_name: &stage_name, kind, moved_by, reason };
+    let moved = pipelines::move_applications(&mut tx, application_ids, &to).await.map_err(|_| {
+        HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            "Failed to move applications"
+        ))
+    })?;
+    tx.commit().await.map_err(|_| database_error())?;
+
+    Ok(BulkMoveResponse { moved, unchanged: application_ids.len() as u64 - moved })
+}
+
+// Move one application to another stage of its job's pipeline
+pub async fn move_application(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+    move_data: web::Json<MoveApplicationRequest>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_permission(&req, Permission::JobsPost) {
+        return Ok(response);
+    }
+
+    let application = match involved_application(&pool, &req, path.into_inner()).await {
+        Ok((application, Some(role))) if role.can_manage_jobs() => application,
+        Ok(_) => {
+            return Ok(HttpResponse::Forbidden().json(ApiResponse::<()>::error(
+                "Only owners and recruiters of the hiring company can move its applications"
+            )));
+        }
+        Err(response) => return Ok(response),
+    };
+    let moved_by = user_id(&req);
+
+    let move_data = move_data.into_inner();
+    let reason = match pipelines::validate_reason(move_data.reason) {
+        Ok(reason) => reason,
+        Err(message) => return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(&message))),
+    };
+
+    if let Err(response) = move_to_stage(
+        &pool, application.job_id, &[application.id], move_data.stage_id, moved_by, reason.as_deref(),
+    ).await {
+        return Ok(response);
+    }
+
+    match applications::find(&pool, application.id).await {
+        Ok(Some(application)) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                &format!("Application moved to {}", application.stage_name),
+                application
+            )))
+        }
+        _ => Ok(database_error()),
+    }
+}
+
+// Move many applications to one of the caller's jobs into a stage at once
+pub async fn bulk_move_applications(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+    move_data: web::Json<BulkMoveApplicationsRequest>,
+) -> Result<HttpResponse> {
+    if let Err(response) = require_permission(&req, Permission::JobsPost) {
+        return Ok(response);
+    }
+
+    let job = match managed_job(&pool, &req, path.into_inner()).await {
+        Ok(job) => job,
+        Err(response) => return Ok(response),
+    };
+
+    let move_data = move_data.into_inner();
+    let mut application_ids = move_data.application_ids;
+    application_ids.sort_unstable();
+    application_ids.dedup();
+    if application_ids.is_empty() || application_ids.len() > MAX_BULK_MOVE {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(&format!(
+            "application_ids must list between 1 and {MAX_BULK_MOVE} applications"
+        ))));
+    }
+
+    let reason = match pipelines::validate_reason(move_data.reason) {
+        Ok(reason) => reason,
+        Err(message) => return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(&message))),
+    };
+
+    match move_to_stage(
+        &pool, job.id, &application_ids, move_data.stage_id, user_id(&req), reason.as_deref(),
+    ).await {
+        Ok(outcome) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Applications moved",
+                outcome
+            )))
+        }
+        Err(response) => Ok(response),
+    }
+}
+
+// Every stage an application has been moved through, for members of the company or an admin
+pub async fn application_history(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<i32>,
+) -> Result<HttpResponse> {
+    // Reasons are the company's notes, so applicants do not see them
+    let application = match involved_application(&pool, &req, path.into_inner()).await {
+        Ok((application, role)) if role.is_some() || can_read_any(&req) => application,
+        Ok(_) => {
+            return Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+                "Application not found"
+            )));
+        }
+        Err(response) => return Ok(response),
+    };
+
+    match pipelines::history(&pool, application.id).await {
+        Ok(history) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Application history retrieved successfully",
+                history
+            )))
+        }
+        Err(_) => Ok(database_error()),
+    }
+}
@@ -0,0 +1,452 @@
+use actix_web::{web, HttpResponse, HttpRequest, HttpMessage, Result};
+use sqlx::PgPool;
+
+use crate::client_ip::client_ip;
+use crate::models::{
+    ApiResponse, Claims, CreateRoleRequest, PermissionInfo, RoleInfo, UpdateRolePermissionsRequest, UserPermissions,
+};
+use crate::cache::CacheManager;
+use crate::audit::{self, SecurityEventType};
+use crate::permissions::{self, Decision, Permission, RolePermissions};
+use crate::revocation::{self, RevocationEvent};
+
+fn caller(req: &HttpRequest) -> (i32, RolePermissions) {
+    let extensions = req.extensions();
+    let claims = extensions.get::<Claims>().unwrap();
+    let permissions = extensions.get::<RolePermissions>().unwrap();
+    (claims.sub.parse().unwrap(), permissions.clone())
+}
+
+/// Role names are short lowercase identifiers, e.g. `recruiter` or `support_agent`
+fn is_valid_role_name(name: &str) -> bool {
+    (2..=20).contains(&name.len())
+        && name.starts_with(|c: char| c.is_ascii_lowercase())
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Check a requested permission list against the catalog and the caller's own
+/// permissions, returning it sorted and without duplicates
+fn validate_permissions(caller: &RolePermissions, requested: &[String]) -> Result<Vec<String>, HttpResponse> {
+    if let Some(unknown) = requested.iter().find(|permission| Permission::parse(permission).is_none()) {
+        return Err(HttpResponse::BadRequest().json(ApiResponse::<()>::error(&format!(
+            "Unknown permission {unknown}"
+        ))));
+    }
+
+    let mut permissions = requested.to_vec();
+    permissions.sort();
+    permissions.dedup();
+
+    let missing = caller.missing(&permissions);
+    if !missing.is_empty() {
+        return Err(HttpResponse::Forbidden().json(ApiResponse::<()>::error(&format!(
+            "You cannot grant permissions you do not have: {}",
+            missing.join(", ")
+        ))));
+    }
+
+    Ok(permissions)
+}
+
+pub async fn list_permissions(pool: web::Data<PgPool>) -> Result<HttpResponse> {
+    match sqlx::query_as::<_, PermissionInfo>("SELECT name, description FROM permissions ORDER BY name")
+        .fetch_all(pool.get_ref())
+        .await
+    {
+        Ok(permissions) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Permissions retrieved successfully",
+                permissions
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )))
+        }
+    }
+}
+
+pub async fn list_roles(pool: web::Data<PgPool>) -> Result<HttpResponse> {
+    let roles_result = sqlx::query_as::<_, RoleInfo>(
+        r#"
+        SELECT r.name, r.description, r.self_assignable, r.builtin, r.permissions_version,
+               COALESCE(ARRAY(SELECT permission FROM role_permissions WHERE role = r.name ORDER BY permission), '{}') AS permissions,
+               (SELECT COUNT(*) FROM users WHERE role = r.name) AS user_count
+        FROM roles r
+        ORDER BY r.name
+        "#
+    )
+    .fetch_all(pool.get_ref())
+    .await;
+
+    match roles_result {
+        Ok(roles) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Roles retrieved successfully",
+                roles
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )))
+        }
+    }
+}
+
+// Add a role such as recruiter, moderator or support; users can be moved into it right away
+pub async fn create_role(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    role_data: web::Json<CreateRoleRequest>,
+) -> Result<HttpResponse> {
+    let (admin_id, caller) = caller(&req);
+
+    let name = role_data.name.trim();
+    if !is_valid_role_name(name) {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Role name must be 2-20 lowercase letters, digits or underscores, starting with a letter"
+        )));
+    }
+
+    let description = role_data.description.as_deref().map(str::trim).filter(|d| !d.is_empty());
+    if description.is_some_and(|d| d.chars().count() > 500) {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Description must be at most 500 characters"
+        )));
+    }
+
+    let permissions = match validate_permissions(&caller, &role_data.permissions) {
+        Ok(permissions) => permissions,
+        Err(error_response) => return Ok(error_response),
+    };
+
+    // Self-service roles are what anyone can sign up as, so they carry nothing administrative
+    if role_data.self_assignable && permissions::any_administrative(&permissions) {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Roles users can choose at registration cannot grant administrative permissions"
+        )));
+    }
+
+    let result: Result<(), sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+
+        sqlx::query("INSERT INTO roles (name, description, self_assignable) VALUES ($1, $2, $3)")
+            .bind(name)
+            .bind(description)
+            .bind(role_data.self_assignable)
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query("INSERT INTO role_permissions (role, permission) SELECT $1, UNNEST($2::VARCHAR[])")
+            .bind(name)
+            .bind(&permissions)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            audit::record(
+                &pool,
+                SecurityEventType::RoleCreated,
+                None,
+                Some(admin_id),
+                client_ip(&req).as_deref(),
+                &format!("Role {name} created with permissions [{}]", permissions.join(", ")),
+            )
+            .await;
+
+            Ok(HttpResponse::Created().json(ApiResponse::<()>::success_no_data(
+                "Role created successfully"
+            )))
+        }
+        Err(sqlx::Error::Database(db_err)) if db_err.constraint().is_some() => {
+            Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(
+                "A role with this name already exists"
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to create role"
+            )))
+        }
+    }
+}
+
+// Replace the permissions of a role. Tokens issued under the old set stop working,
+// so every member picks up the change on their next refresh.
+pub async fn update_role_permissions(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheManager>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    role_data: web::Json<UpdateRolePermissionsRequest>,
+) -> Result<HttpResponse> {
+    let (admin_id, caller) = caller(&req);
+    let name = path.into_inner();
+
+    let permissions = match validate_permissions(&caller, &role_data.permissions) {
+        Ok(permissions) => permissions,
+        Err(error_response) => return Ok(error_response),
+    };
+
+    let current = match permissions::load(&pool, &cache, &name).await {
+        Ok(Some(current)) => current,
+        Ok(None) => {
+            return Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+                "Role not found"
+            )));
+        }
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    };
+
+    // Taking permissions away is as sensitive as handing them out
+    let missing = caller.missing(&current.permissions);
+    if !missing.is_empty() {
+        return Ok(HttpResponse::Forbidden().json(ApiResponse::<()>::error(&format!(
+            "You cannot change a role that has permissions you do not have: {}",
+            missing.join(", ")
+        ))));
+    }
+
+    // Stop admins from locking themselves out
+    if name == caller.role && !permissions.iter().any(|p| p == Permission::RolesManage.as_str()) {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "You cannot remove roles:manage from your own role"
+        )));
+    }
+
+    let result: Result<bool, sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+
+        // Self-service roles cannot be given anything administrative
+        let updated = sqlx::query(
+            r#"
+            UPDATE roles SET permissions_version = permissions_version + 1, updated_at = CURRENT_TIMESTAMP
+            WHERE name = $1 AND (self_assignable = false OR NOT $2)
+            "#
+        )
+        .bind(&name)
+        .bind(permissions::any_administrative(&permissions))
+        .execute(&mut tx)
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        sqlx::query("DELETE FROM role_permissions WHERE role = $1")
+            .bind(&name)
+            .execute(&mut tx)
+            .await?;
+
+        sqlx::query("INSERT INTO role_permissions (role, permission) SELECT $1, UNNEST($2::VARCHAR[])")
+            .bind(&name)
+            .bind(&permissions)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(true)
+    }
+    .await;
+
+    match result {
+        Ok(true) => {
+            revocation::publish(&pool, &cache, &[RevocationEvent::RolePermissions]).await;
+            audit::record(
+                &pool,
+                SecurityEventType::RolePermissionsChanged,
+                None,
+                Some(admin_id),
+                client_ip(&req).as_deref(),
+                &format!(
+                    "Role {name} permissions changed from [{}] to [{}]",
+                    current.permissions.join(", "),
+                    permissions.join(", ")
+                ),
+            )
+            .await;
+
+            Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "Role permissions updated successfully"
+            )))
+        }
+        Ok(false) => {
+            Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+                "Roles users can choose at registration cannot grant administrative permissions"
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to update role"
+            )))
+        }
+    }
+}
+
+// Delete a role nobody has any more; the built-in roles are referenced by the code and stay
+pub async fn delete_role(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheManager>,
+    req: HttpRequest,
+    path: web::Path<String>,
+) -> Result<HttpResponse> {
+    let (admin_id, caller) = caller(&req);
+    let name = path.into_inner();
+
+    let role: Option<(bool, i64)> = match sqlx::query_as(
+        "SELECT builtin, (SELECT COUNT(*) FROM users WHERE role = $1) FROM roles WHERE name = $1"
+    )
+    .bind(&name)
+    .fetch_optional(pool.get_ref())
+    .await
+    {
+        Ok(role) => role,
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    };
+
+    let Some((builtin, user_count)) = role else {
+        return Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+            "Role not found"
+        )));
+    };
+
+    if builtin {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Built-in roles cannot be deleted"
+        )));
+    }
+    if user_count > 0 {
+        return Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(&format!(
+            "{user_count} users still have this role; move them to another role first"
+        ))));
+    }
+
+    if let Ok(Some(current)) = permissions::load(&pool, &cache, &name).await {
+        let missing = caller.missing(&current.permissions);
+        if !missing.is_empty() {
+            return Ok(HttpResponse::Forbidden().json(ApiResponse::<()>::error(&format!(
+                "You cannot delete a role that has permissions you do not have: {}",
+                missing.join(", ")
+            ))));
+        }
+    }
+
+    match sqlx::query("DELETE FROM roles WHERE name = $1 AND builtin = false")
+        .bind(&name)
+        .execute(pool.get_ref())
+        .await
+    {
+        Ok(_) => {
+            revocation::publish(&pool, &cache, &[RevocationEvent::RolePermissions]).await;
+            audit::record(
+                &pool,
+                SecurityEventType::RoleDeleted,
+                None,
+                Some(admin_id),
+                client_ip(&req).as_deref(),
+                &format!("Role {name} deleted"),
+            )
+            .await;
+
+            Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "Role deleted successfully"
+            )))
+        }
+        // A user was given the role in the meantime
+        Err(sqlx::Error::Database(db_err)) if db_err.constraint().is_some() => {
+            Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(
+                "Users still have this role; move them to another role first"
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to delete role"
+            )))
+        }
+    }
+}
+
+// Explain whether a user currently has a permission, e.g. when answering a support request
+pub async fn check_user_permission(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheManager>,
+    path: web::Path<(i32, String)>,
+) -> Result<HttpResponse> {
+    let (user_id, permission) = path.into_inner();
+
+    let Some(permission) = Permission::parse(&permission) else {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(&format!(
+            "Unknown permission {permission}"
+        ))));
+    };
+
+    let user: Option<(String, bool)> = match sqlx::query_as("SELECT role, is_active FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(pool.get_ref())
+        .await
+    {
+        Ok(user) => user,
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    };
+
+    let Some((role, is_active)) = user else {
+        return Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+            "User not found"
+        )));
+    };
+
+    let decision = match permissions::load(&pool, &cache, &role).await {
+        Ok(Some(_)) if !is_active => Decision {
+            allowed: false,
+            reason: "Account is deactivated or suspended".to_string(),
+        },
+        Ok(Some(role_permissions)) => role_permissions.check(permission),
+        Ok(None) => Decision {
+            allowed: false,
+            reason: format!("Role {role} does not exist"),
+        },
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    };
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(
+        "Permission checked",
+        decision
+    )))
+}
+
+// The caller's role and the permissions it grants, so clients can hide what the user cannot do
+pub async fn my_permissions(req: HttpRequest) -> Result<HttpResponse> {
+    let (_, permissions) = caller(&req);
+
+    Ok(HttpResponse::Ok().json(ApiResponse::success(
+        "Permissions retrieved successfully",
+        UserPermissions {
+            role: permissions.role.clone(),
+            permissions: permissions.permissions.to_vec(),
+        }
+    )))
+}
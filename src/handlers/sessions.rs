@@ -0,0 +1,176 @@
+use actix_web::{web, HttpResponse, Result, HttpRequest, HttpMessage};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::models::{ApiResponse, Claims, SessionInfo, UpdateSessionRequest};
+use crate::cache::CacheManager;
+use crate::database;
+use crate::revocation::{self, RevocationEvent};
+
+// The user id and session id of the caller, taken from the JWT claims
+fn current_session(req: &HttpRequest) -> Option<(i32, Uuid)> {
+    let extensions = req.extensions();
+    let claims = extensions.get::<Claims>()?;
+    Some((claims.sub.parse().ok()?, claims.sid.parse().ok()?))
+}
+
+// List the caller's active sessions, one per signed-in device
+pub async fn list_sessions(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let Some((user_id, current_session_id)) = current_session(&req) else {
+        return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error("Invalid token")));
+    };
+
+    let sessions_result = sqlx::query_as::<_, SessionInfo>(
+        r#"
+        SELECT s.family_id AS id, s.device_label, s.user_agent, s.ip_address,
+               (SELECT MIN(f.created_at) FROM user_sessions f WHERE f.family_id = s.family_id) AS signed_in_at,
+               s.last_seen_at,
+               s.family_id = $2 AS current
+        FROM user_sessions s
+        WHERE s.user_id = $1 AND s.revoked_at IS NULL AND s.expires_at > CURRENT_TIMESTAMP
+        ORDER BY s.last_seen_at DESC NULLS LAST
+        "#
+    )
+    .bind(user_id)
+    .bind(current_session_id)
+    .fetch_all(pool.get_ref())
+    .await;
+
+    match sessions_result {
+        Ok(sessions) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Sessions retrieved successfully",
+                sessions
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )))
+        }
+    }
+}
+
+// Give one of the caller's sessions a recognisable name
+pub async fn rename_session(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+    session_data: web::Json<UpdateSessionRequest>,
+) -> Result<HttpResponse> {
+    let Some((user_id, _)) = current_session(&req) else {
+        return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error("Invalid token")));
+    };
+    let session_id = path.into_inner();
+
+    let device_label = session_data.device_label.trim();
+    if device_label.is_empty() || device_label.chars().count() > 100 {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Device label must be between 1 and 100 characters"
+        )));
+    }
+
+    // Label every row of the family so the name survives refresh token rotation
+    let update_result = sqlx::query(
+        "UPDATE user_sessions SET device_label = $1 WHERE user_id = $2 AND family_id = $3"
+    )
+    .bind(device_label)
+    .bind(user_id)
+    .bind(session_id)
+    .execute(pool.get_ref())
+    .await;
+
+    match update_result {
+        Ok(result) if result.rows_affected() > 0 => {
+            Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "Session renamed successfully"
+            )))
+        }
+        Ok(_) => {
+            Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+                "Session not found"
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to rename session"
+            )))
+        }
+    }
+}
+
+// Sign out one of the caller's sessions
+pub async fn revoke_session(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheManager>,
+    req: HttpRequest,
+    path: web::Path<Uuid>,
+) -> Result<HttpResponse> {
+    let Some((user_id, _)) = current_session(&req) else {
+        return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error("Invalid token")));
+    };
+    let session_id = path.into_inner();
+
+    match database::revoke_session(&pool, user_id, session_id).await {
+        Ok(true) => {
+            revocation::publish(&pool, &cache, &[RevocationEvent::Session(session_id)]).await;
+
+            Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "Session revoked successfully"
+            )))
+        }
+        Ok(false) => {
+            Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+                "Session not found"
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to revoke session"
+            )))
+        }
+    }
+}
+
+// Sign out every session except the caller's
+pub async fn revoke_other_sessions(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheManager>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let Some((user_id, current_session_id)) = current_session(&req) else {
+        return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error("Invalid token")));
+    };
+
+    let revoke_result: Result<Vec<Uuid>, sqlx::Error> = sqlx::query_scalar(
+        r#"
+        UPDATE user_sessions SET revoked_at = CURRENT_TIMESTAMP
+        WHERE user_id = $1 AND family_id <> $2 AND revoked_at IS NULL
+        RETURNING family_id
+        "#
+    )
+    .bind(user_id)
+    .bind(current_session_id)
+    .fetch_all(pool.get_ref())
+    .await;
+
+    match revoke_result {
+        Ok(revoked_sessions) => {
+            let events: Vec<RevocationEvent> = revoked_sessions.iter().copied().map(RevocationEvent::Session).collect();
+            revocation::publish(&pool, &cache, &events).await;
+
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Other sessions revoked successfully",
+                format!("Revoked {} sessions", revoked_sessions.len())
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to revoke sessions"
+            )))
+        }
+    }
+}
@@ -0,0 +1,488 @@
+use actix_web::{web, HttpResponse, Result, HttpRequest, HttpMessage};
+use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use uuid::Uuid;
+
+use crate::models::{
+    ApiResponse, Claims, DisableTwoFactorRequest, RecoveryCodesResponse, RegenerateRecoveryCodesRequest,
+    TwoFactorCodeRequest, TwoFactorLoginRequest, TwoFactorSetupResponse, TwoFactorStatus, User,
+};
+use crate::utils::{decode_challenge_token, hash_token, totp, PasswordHasher};
+use crate::config::Config;
+use crate::cache::CacheManager;
+use crate::revocation;
+use crate::throttle::{self, ThrottleKey};
+use crate::keys::KeyStore;
+use crate::handlers::auth::{create_session, SessionDevice};
+
+const RECOVERY_CODE_COUNT: usize = 10;
+
+fn user_id(req: &HttpRequest) -> i32 {
+    let extensions = req.extensions();
+    let claims = extensions.get::<Claims>().unwrap();
+    claims.sub.parse().unwrap()
+}
+
+// Recovery codes are compared without separators and case-insensitively
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; 5];
+            rand::thread_rng().fill_bytes(&mut bytes);
+            let code = hex::encode(bytes);
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+/// Replace the user's recovery codes with a fresh set and return them in plain text (shown once)
+async fn replace_recovery_codes(pool: &PgPool, user_id: i32) -> Result<Vec<String>, sqlx::Error> {
+    let codes = generate_recovery_codes();
+    let mut tx = pool.begin().await?;
+
+    sqlx::query("DELETE FROM user_recovery_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut tx)
+        .await?;
+
+    for code in &codes {
+        sqlx::query("INSERT INTO user_recovery_codes (user_id, code_hash) VALUES ($1, $2)")
+            .bind(user_id)
+            .bind(hash_token(&normalize_recovery_code(code)))
+            .execute(&mut tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(codes)
+}
+
+pub async fn is_enabled(pool: &PgPool, user_id: i32) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM user_totp WHERE user_id = $1 AND enabled_at IS NOT NULL)")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await
+}
+
+/// Check a TOTP code, or failing that a recovery code, consuming it on success
+pub async fn verify_second_factor(pool: &PgPool, user_id: i32, code: &str) -> Result<bool, sqlx::Error> {
+    let totp_row: Option<(String, Option<i64>)> = sqlx::query_as(
+        "SELECT secret, last_used_step FROM user_totp WHERE user_id = $1 AND enabled_at IS NOT NULL"
+    )
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await?;
+
+    let Some((secret, last_used_step)) = totp_row else {
+        return Ok(false);
+    };
+
+    if let Some(step) = totp::verify(&secret, code, Utc::now().timestamp(), last_used_step) {
+        // The step guard makes a code single-use even under concurrent requests
+        let updated = sqlx::query(
+            "UPDATE user_totp SET last_used_step = $1 WHERE user_id = $2 AND (last_used_step IS NULL OR last_used_step < $1)"
+        )
+        .bind(step)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+        return Ok(updated.rows_affected() > 0);
+    }
+
+    let used = sqlx::query(
+        "UPDATE user_recovery_codes SET used_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL"
+    )
+    .bind(user_id)
+    .bind(hash_token(&normalize_recovery_code(code)))
+    .execute(pool)
+    .await?;
+
+    if used.rows_affected() > 0 {
+        log::info!("User {user_id} signed in with a recovery code");
+    }
+    Ok(used.rows_affected() > 0)
+}
+
+/// Check the account password and then a second factor, for changes an unattended
+/// session must not be able to make. The password comes first so a stolen session
+/// cannot use these endpoints to guess codes.
+async fn verify_password_and_second_factor(
+    pool: &PgPool,
+    hasher: &PasswordHasher,
+    user_id: i32,
+    password: &str,
+    code: &str,
+) -> Result<(), HttpResponse> {
+    let password_hash: Result<String, sqlx::Error> = sqlx::query_scalar(
+        "SELECT password_hash FROM users WHERE id = $1"
+    )
+    .bind(user_id)
+    .fetch_one(pool)
+    .await;
+
+    let password_ok = match password_hash {
+        Ok(hash) => hasher.verify(password, &hash).await.unwrap_or(false),
+        Err(_) => {
+            return Err(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    };
+
+    if !password_ok {
+        return Err(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Password is incorrect"
+        )));
+    }
+
+    match verify_second_factor(pool, user_id, code).await {
+        Ok(true) => Ok(()),
+        Ok(false) => {
+            Err(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+                "Invalid verification code"
+            )))
+        }
+        Err(_) => {
+            Err(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )))
+        }
+    }
+}
+
+pub async fn get_status(pool: web::Data<PgPool>, req: HttpRequest) -> Result<HttpResponse> {
+    let user_id = user_id(&req);
+
+    let status: Result<(bool, i64), sqlx::Error> = sqlx::query_as(
+        r#"
+        SELECT
+            EXISTS(SELECT 1 FROM user_totp WHERE user_id = $1 AND enabled_at IS NOT NULL),
+            (SELECT COUNT(*) FROM user_recovery_codes WHERE user_id = $1 AND used_at IS NULL)
+        "#
+    )
+    .bind(user_id)
+    .fetch_one(pool.get_ref())
+    .await;
+
+    match status {
+        Ok((enabled, recovery_codes_remaining)) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Two-factor status retrieved successfully",
+                TwoFactorStatus { enabled, recovery_codes_remaining }
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )))
+        }
+    }
+}
+
+// Start enrollment: generate a secret that only becomes active once confirmed
+pub async fn setup(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let user_id = user_id(&req);
+
+    match is_enabled(&pool, user_id).await {
+        Ok(false) => {}
+        Ok(true) => {
+            return Ok(HttpResponse::Conflict().json(ApiResponse::<()>::error(
+                "Two-factor authentication is already enabled"
+            )));
+        }
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    }
+
+    let secret = totp::generate_secret();
+
+    let account: Result<String, sqlx::Error> = async {
+        sqlx::query(
+            r#"
+            INSERT INTO user_totp (user_id, secret) VALUES ($1, $2)
+            ON CONFLICT (user_id) DO UPDATE SET secret = EXCLUDED.secret, enabled_at = NULL, last_used_step = NULL, created_at = CURRENT_TIMESTAMP
+            "#
+        )
+        .bind(user_id)
+        .bind(&secret)
+        .execute(pool.get_ref())
+        .await?;
+
+        sqlx::query_scalar("SELECT email FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_one(pool.get_ref())
+            .await
+    }
+    .await;
+
+    match account {
+        Ok(email) => {
+            let otpauth_uri = totp::provisioning_uri(&secret, &email, &config.totp_issuer);
+
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Scan the QR code with your authenticator app, then confirm with a code",
+                TwoFactorSetupResponse { secret, otpauth_uri }
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to start two-factor setup"
+            )))
+        }
+    }
+}
+
+// Finish enrollment with a code from the authenticator app; returns the recovery codes once
+pub async fn confirm(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+    code_data: web::Json<TwoFactorCodeRequest>,
+) -> Result<HttpResponse> {
+    let user_id = user_id(&req);
+
+    let pending: Result<Option<String>, sqlx::Error> = sqlx::query_scalar(
+        "SELECT secret FROM user_totp WHERE user_id = $1 AND enabled_at IS NULL"
+    )
+    .bind(user_id)
+    .fetch_optional(pool.get_ref())
+    .await;
+
+    let secret = match pending {
+        Ok(Some(secret)) => secret,
+        Ok(None) => {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+                "No two-factor setup in progress"
+            )));
+        }
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    };
+
+    let Some(step) = totp::verify(&secret, &code_data.code, Utc::now().timestamp(), None) else {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Invalid verification code"
+        )));
+    };
+
+    let enabled = sqlx::query(
+        "UPDATE user_totp SET enabled_at = CURRENT_TIMESTAMP, last_used_step = $1 WHERE user_id = $2 AND enabled_at IS NULL"
+    )
+    .bind(step)
+    .bind(user_id)
+    .execute(pool.get_ref())
+    .await;
+
+    if !matches!(enabled, Ok(result) if result.rows_affected() > 0) {
+        return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            "Failed to enable two-factor authentication"
+        )));
+    }
+
+    match replace_recovery_codes(&pool, user_id).await {
+        Ok(recovery_codes) => {
+            log::info!("User {user_id} enabled two-factor authentication");
+
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Two-factor authentication enabled. Store these recovery codes somewhere safe",
+                RecoveryCodesResponse { recovery_codes }
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to generate recovery codes"
+            )))
+        }
+    }
+}
+
+pub async fn regenerate_recovery_codes(
+    pool: web::Data<PgPool>,
+    hasher: web::Data<PasswordHasher>,
+    req: HttpRequest,
+    regenerate_data: web::Json<RegenerateRecoveryCodesRequest>,
+) -> Result<HttpResponse> {
+    let user_id = user_id(&req);
+
+    if let Err(response) = verify_password_and_second_factor(
+        &pool, &hasher, user_id, &regenerate_data.password, &regenerate_data.code,
+    ).await {
+        return Ok(response);
+    }
+
+    match replace_recovery_codes(&pool, user_id).await {
+        Ok(recovery_codes) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Recovery codes regenerated. Previous codes no longer work",
+                RecoveryCodesResponse { recovery_codes }
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to generate recovery codes"
+            )))
+        }
+    }
+}
+
+// Turning 2FA off needs both the password and a second factor
+pub async fn disable(
+    pool: web::Data<PgPool>,
+    hasher: web::Data<PasswordHasher>,
+    req: HttpRequest,
+    disable_data: web::Json<DisableTwoFactorRequest>,
+) -> Result<HttpResponse> {
+    let user_id = user_id(&req);
+
+    if let Err(response) = verify_password_and_second_factor(
+        &pool, &hasher, user_id, &disable_data.password, &disable_data.code,
+    ).await {
+        return Ok(response);
+    }
+
+    let result: Result<(), sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+        sqlx::query("DELETE FROM user_totp WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("DELETE FROM user_recovery_codes WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await
+    }
+    .await;
+
+    match result {
+        Ok(()) => {
+            log::info!("User {user_id} disabled two-factor authentication");
+
+            Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "Two-factor authentication disabled"
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to disable two-factor authentication"
+            )))
+        }
+    }
+}
+
+// Second login step: exchange the challenge token and a valid code for a full session
+pub async fn login_two_factor(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    keys: web::Data<KeyStore>,
+    cache: web::Data<CacheManager>,
+    req: HttpRequest,
+    login_data: web::Json<TwoFactorLoginRequest>,
+) -> Result<HttpResponse> {
+    let invalid_challenge = || {
+        HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
+            "Invalid or expired challenge token"
+        ))
+    };
+
+    let Ok(challenge) = decode_challenge_token(&login_data.challenge_token, &keys, &config) else {
+        return Ok(invalid_challenge());
+    };
+    let (Ok(user_id), Ok(jti)) = (challenge.sub.parse::<i32>(), challenge.jti.parse::<Uuid>()) else {
+        return Ok(invalid_challenge());
+    };
+
+    // Challenges are single-use
+    match revocation::is_token_revoked(&pool, &cache, jti).await {
+        Ok(false) => {}
+        Ok(true) => return Ok(invalid_challenge()),
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    }
+
+    // Codes are short, so guesses count towards the same lockout as passwords
+    let device = SessionDevice::from_request(&req, challenge.device_label);
+    let throttle_keys: Vec<ThrottleKey> = std::iter::once(ThrottleKey::User(user_id))
+        .chain(device.ip_address.clone().map(ThrottleKey::Ip))
+        .collect();
+    if let Err(error_response) = throttle::check(&pool, &throttle_keys).await {
+        return Ok(error_response);
+    }
+
+    match verify_second_factor(&pool, user_id, &login_data.code).await {
+        Ok(true) => {
+            if let Err(e) = throttle::clear(&pool, &ThrottleKey::User(user_id)).await {
+                log::error!("Failed to reset login throttle for user {user_id}: {e}");
+            }
+        }
+        Ok(false) => {
+            throttle::record_failure(&pool, &config, &throttle_keys, Some(user_id), device.ip_address.as_deref()).await;
+            return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
+                "Invalid verification code"
+            )));
+        }
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    }
+
+    let expires_at = DateTime::<Utc>::from_timestamp(challenge.exp as i64, 0).unwrap_or_else(Utc::now);
+    if revocation::revoke_token(&pool, &cache, jti, user_id, expires_at).await.is_err() {
+        return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+            "Database error"
+        )));
+    }
+
+    let user = sqlx::query_as::<_, User>(
+        r#"
+        SELECT id, username, email, password_hash, first_name, last_name, phone, role, professional_role, company_name, is_active, email_verified, created_at, updated_at
+        FROM users
+        WHERE id = $1 AND is_active = true
+        "#
+    )
+    .bind(user_id)
+    .fetch_optional(pool.get_ref())
+    .await;
+
+    let user = match user {
+        Ok(Some(user)) => user,
+        Ok(None) => return Ok(invalid_challenge()),
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    };
+
+    match create_session(&pool, &config, &keys, user, device).await {
+        Ok(response) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Login successful",
+                response
+            )))
+        }
+        Err(error_response) => Ok(error_response),
+    }
+}
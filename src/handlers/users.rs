@@ -1,9 +1,21 @@
 use actix_web::{web, HttpResponse, Result, Scope, HttpRequest, HttpMessage};
 use sqlx::{PgPool, Row};
 
-use crate::models::{ApiResponse, Claims, User, UserPublic, UpdateUserRequest};
+use uuid::Uuid;
+
+use crate::models::{
+    AccessTokenResponse, ApiResponse, ChangePasswordRequest, Claims, User, UserPublic, UpdateUserRequest
+};
+use crate::utils::{generate_jwt, PasswordHasher};
+use crate::config::Config;
 use crate::cache::CacheManager;
 use crate::database;
+use crate::mailer::Mailer;
+use crate::revocation::{self, RevocationEvent};
+use crate::keys::KeyStore;
+use crate::permissions;
+use crate::password_policy::{self, PasswordOwner, PasswordPolicy};
+use crate::handlers::{accounts, api_keys, candidate_profiles, impersonation, oauth, roles, sessions, two_factor, verification};
 
 pub async fn get_profile(req: HttpRequest) -> Result<HttpResponse> {
     // Extract claims from request extensions (set by auth middleware)
@@ -75,6 +87,8 @@ pub async fn get_user_by_id(
 pub async fn update_profile(
     pool: web::Data<PgPool>,
     cache: web::Data<CacheManager>,
+    config: web::Data<Config>,
+    mailer: web::Data<Mailer>,
     req: HttpRequest,
     update_data: web::Json<UpdateUserRequest>,
 ) -> Result<HttpResponse> {
@@ -85,6 +99,14 @@ pub async fn update_profile(
         claims.sub.parse().unwrap()
     };
 
+    // The sign-in identifiers stay with the account holder: a leaked key must not be
+    // able to point the email at an attacker's inbox and take the account over
+    if crate::api_keys::is_key_request(&req) && (update_data.username.is_some() || update_data.email.is_some()) {
+        return Ok(HttpResponse::Forbidden().json(ApiResponse::<()>::error(
+            "Username and email cannot be changed with an API key"
+        )));
+    }
+
     // Input validation
     if let Some(username) = &update_data.username {
         if username.trim().is_empty() || username.len() < 3 {
@@ -102,8 +124,30 @@ pub async fn update_profile(
         }
     }
 
+    // A new email address has to be verified again
+    let email_changed = match &update_data.email {
+        Some(email) => {
+            match sqlx::query_scalar::<_, String>("SELECT email FROM users WHERE id = $1")
+                .bind(user_id)
+                .fetch_optional(pool.get_ref())
+                .await
+            {
+                Ok(current_email) => current_email.as_deref() != Some(email.as_str()),
+                Err(_) => {
+                    return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                        "Database error"
+                    )));
+                }
+            }
+        }
+        None => false,
+    };
+
     // Build dynamic query based on provided fields
     let mut query_parts = vec!["UPDATE users SET updated_at = CURRENT_TIMESTAMP".to_string()];
+    if email_changed {
+        query_parts.push("email_verified = false".to_string());
+    }
     let mut params: Vec<String> = vec![];
     let mut param_count = 1;
 
@@ -171,6 +215,12 @@ pub async fn update_profile(
 
     match user_result {
         Ok(Some(user)) => {
+            if email_changed {
+                verification::spawn_verification_email(
+                    pool.clone(), config.clone(), mailer.clone(), user.id, user.email.clone()
+                );
+            }
+
             let user_public: UserPublic = user.into();
             
             // Update cache with new data
@@ -277,10 +327,184 @@ pub async fn get_users_batch(
     )))
 }
 
+// Change the password of the authenticated user, keeping only the caller's session alive
+#[allow(clippy::too_many_arguments)] // actix extractors
+pub async fn change_password(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    keys: web::Data<KeyStore>,
+    cache: web::Data<CacheManager>,
+    hasher: web::Data<PasswordHasher>,
+    policy: web::Data<PasswordPolicy>,
+    req: HttpRequest,
+    password_data: web::Json<ChangePasswordRequest>,
+) -> Result<HttpResponse> {
+    let (user_id, session_id): (i32, Uuid) = {
+        let extensions = req.extensions();
+        let claims = extensions.get::<Claims>().unwrap();
+        match (claims.sub.parse(), claims.sid.parse()) {
+            (Ok(user_id), Ok(session_id)) => (user_id, session_id),
+            _ => {
+                return Ok(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
+                    "Invalid token"
+                )));
+            }
+        }
+    };
+
+    let current: Option<(String, String, String)> = match sqlx::query_as(
+        "SELECT password_hash, username, email FROM users WHERE id = $1 AND is_active = true"
+    )
+    .bind(user_id)
+    .fetch_optional(pool.get_ref())
+    .await
+    {
+        Ok(hash) => hash,
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    };
+
+    let Some((current_hash, username, email)) = current else {
+        return Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+            "User not found"
+        )));
+    };
+
+    match hasher.verify(&password_data.current_password, &current_hash).await {
+        Ok(true) => {}
+        Ok(false) => {
+            return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+                "Current password is incorrect"
+            )));
+        }
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Password verification failed"
+            )));
+        }
+    }
+
+    let owner = PasswordOwner { username: &username, email: &email };
+    if let Err(errors) = policy.check("new_password", &password_data.new_password, owner).await {
+        return Ok(password_policy::rejected(errors));
+    }
+
+    let password_hash = match hasher.hash(&password_data.new_password).await {
+        Ok(hash) => hash,
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to hash password"
+            )));
+        }
+    };
+
+    let result: Result<(User, Vec<Uuid>), sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+
+        // Revoke every access token issued so far; the caller gets a fresh one below
+        let user = sqlx::query_as::<_, User>(
+            r#"
+            UPDATE users SET password_hash = $1, tokens_invalid_before = CURRENT_TIMESTAMP
+            WHERE id = $2
+            RETURNING id, username, email, password_hash, first_name, last_name, phone, role, professional_role, company_name, is_active, email_verified, created_at, updated_at
+            "#
+        )
+        .bind(&password_hash)
+        .bind(user_id)
+        .fetch_one(&mut tx)
+        .await?;
+
+        // Revoke every other session's refresh tokens
+        let revoked_sessions: Vec<Uuid> = sqlx::query_scalar(
+            "UPDATE user_sessions SET revoked_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND family_id <> $2 AND revoked_at IS NULL RETURNING family_id"
+        )
+        .bind(user_id)
+        .bind(session_id)
+        .fetch_all(&mut tx)
+        .await?;
+
+        crate::api_keys::revoke_all(&mut tx, user_id).await?;
+
+        // Pending reset links were issued for the old password
+        sqlx::query(
+            "UPDATE password_reset_tokens SET used_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND used_at IS NULL"
+        )
+        .bind(user_id)
+        .execute(&mut tx)
+        .await?;
+
+        tx.commit().await?;
+        Ok((user, revoked_sessions))
+    }
+    .await;
+
+    let (user, revoked_sessions) = match result {
+        Ok(result) => result,
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to change password"
+            )));
+        }
+    };
+
+    let mut events = vec![RevocationEvent::User(user_id)];
+    events.extend(revoked_sessions.into_iter().map(RevocationEvent::Session));
+    revocation::publish(&pool, &cache, &events).await;
+
+    let access_token = match permissions::version(&pool, &user.role).await {
+        Ok(permissions_version) => generate_jwt(&user, permissions_version, session_id, &keys, &config).ok(),
+        Err(_) => None,
+    };
+    match access_token {
+        Some(access_token) => {
+            let response = AccessTokenResponse {
+                access_token,
+                token_type: "Bearer".to_string(),
+                expires_in: config.jwt_expiration,
+            };
+
+            Ok(HttpResponse::Ok().json(ApiResponse::success(
+                "Password changed successfully",
+                response
+            )))
+        }
+        None => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to generate token"
+            )))
+        }
+    }
+}
+
 pub fn user_routes() -> Scope {
-    web::scope("/users")
+    let scope = web::scope("/users")
         .route("/profile", web::get().to(get_profile))
         .route("/profile", web::put().to(update_profile))
-        .route("/batch", web::get().to(get_users_batch))
+        .route("/password", web::put().to(change_password))
+        .route("/permissions", web::get().to(roles::my_permissions))
+        .route("/deactivate", web::post().to(accounts::deactivate_account))
+        .route("/account", web::delete().to(accounts::delete_account))
+        .route("/email/resend-verification", web::post().to(verification::resend_verification))
+        .route("/sessions", web::get().to(sessions::list_sessions))
+        .route("/sessions", web::delete().to(sessions::revoke_other_sessions))
+        .route("/sessions/{id}", web::put().to(sessions::rename_session))
+        .route("/sessions/{id}", web::delete().to(sessions::revoke_session))
+        .route("/2fa", web::get().to(two_factor::get_status))
+        .route("/2fa/setup", web::post().to(two_factor::setup))
+        .route("/2fa/confirm", web::post().to(two_factor::confirm))
+        .route("/2fa/disable", web::post().to(two_factor::disable))
+        .route("/2fa/recovery-codes", web::post().to(two_factor::regenerate_recovery_codes))
+        .route("/identities", web::get().to(oauth::list_identities))
+        .route("/identities/{provider}", web::post().to(oauth::link_identity))
+        .route("/identities/{id}", web::delete().to(oauth::unlink_identity))
+        .route("/api-keys", web::get().to(api_keys::list_api_keys))
+        .route("/api-keys", web::post().to(api_keys::create_api_key))
+        .route("/api-keys/{id}", web::delete().to(api_keys::revoke_api_key))
+        .route("/impersonation", web::delete().to(impersonation::stop_current_impersonation))
+        .route("/batch", web::get().to(get_users_batch));
+    candidate_profiles::candidate_profile_routes(scope)
         .route("/{id}", web::get().to(get_user_by_id))
 } 
\ No newline at end of file
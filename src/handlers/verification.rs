@@ -0,0 +1,281 @@
+use actix_web::{web, HttpResponse, Result, HttpRequest, HttpMessage};
+use sqlx::PgPool;
+use chrono::{DateTime, Duration, Utc};
+
+use crate::models::{ApiResponse, Claims, VerifyEmailRequest};
+use crate::utils::{generate_token, hash_token};
+use crate::config::Config;
+use crate::cache::CacheManager;
+use crate::mailer::Mailer;
+
+/// Actions that can be restricted to users with a verified email through
+/// the `REQUIRE_VERIFIED_EMAIL_FOR` setting
+#[derive(Debug, Clone, Copy)]
+pub enum VerifiedAction {
+    PostJobs,
+    ApplyToJobs,
+}
+
+impl VerifiedAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VerifiedAction::PostJobs => "post_jobs",
+            VerifiedAction::ApplyToJobs => "apply_to_jobs",
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            VerifiedAction::PostJobs => "posting jobs",
+            VerifiedAction::ApplyToJobs => "applying to jobs",
+        }
+    }
+}
+
+/// Reject the request with 403 when `action` requires a verified email and the user has none
+pub async fn ensure_email_verified(
+    pool: &PgPool,
+    cache: &CacheManager,
+    config: &Config,
+    user_id: i32,
+    action: VerifiedAction,
+) -> Result<(), HttpResponse> {
+    if !config.require_verified_email_for.iter().any(|a| a == action.as_str()) {
+        return Ok(());
+    }
+
+    let verified = match cache.get_user(user_id).await {
+        Some(user) => user.email_verified,
+        None => sqlx::query_scalar::<_, bool>("SELECT email_verified FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|_| HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )))?
+            .unwrap_or(false),
+    };
+
+    if verified {
+        Ok(())
+    } else {
+        Err(HttpResponse::Forbidden().json(ApiResponse::<()>::error(&format!(
+            "Please verify your email address before {}",
+            action.description()
+        ))))
+    }
+}
+
+/// Issue a new verification token for `email` and send the confirmation link.
+/// Older unused tokens for the user are retired so only the latest link works.
+pub async fn send_verification_email(
+    pool: &PgPool,
+    config: &Config,
+    mailer: &Mailer,
+    user_id: i32,
+    email: &str,
+) -> anyhow::Result<()> {
+    let token = generate_token();
+    let expires_at = Utc::now() + Duration::seconds(config.email_verification_expiration);
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query(
+        "UPDATE email_verification_tokens SET used_at = CURRENT_TIMESTAMP WHERE user_id = $1 AND used_at IS NULL"
+    )
+    .bind(user_id)
+    .execute(&mut tx)
+    .await?;
+
+    sqlx::query(
+        "INSERT INTO email_verification_tokens (user_id, email, token_hash, expires_at) VALUES ($1, $2, $3, $4)"
+    )
+    .bind(user_id)
+    .bind(email)
+    .bind(hash_token(&token))
+    .bind(expires_at)
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let verify_link = format!("{}/verify-email?token={token}", config.app_base_url);
+    let body = format!(
+        "Please confirm your email address by opening the link below.\n\n\
+         {verify_link}\n\n\
+         The link expires in {} hours.",
+        config.email_verification_expiration / 3600
+    );
+    mailer.send(email, "Confirm your email address", &body).await?;
+
+    Ok(())
+}
+
+/// Send the verification email in the background so a mail outage never fails the request
+pub fn spawn_verification_email(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    mailer: web::Data<Mailer>,
+    user_id: i32,
+    email: String,
+) {
+    actix_web::rt::spawn(async move {
+        if let Err(e) = send_verification_email(&pool, &config, &mailer, user_id, &email).await {
+            log::error!("Failed to send verification email to user {user_id}: {e}");
+        }
+    });
+}
+
+// Public endpoint: confirm an email address with the token from the verification link
+pub async fn verify_email(
+    pool: web::Data<PgPool>,
+    cache: web::Data<CacheManager>,
+    verify_data: web::Json<VerifyEmailRequest>,
+) -> Result<HttpResponse> {
+    if verify_data.token.trim().is_empty() {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Verification token is required"
+        )));
+    }
+
+    let result: Result<Option<i32>, sqlx::Error> = async {
+        let mut tx = pool.begin().await?;
+
+        let token_row: Option<(i32, String)> = sqlx::query_as(
+            r#"
+            UPDATE email_verification_tokens SET used_at = CURRENT_TIMESTAMP
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > CURRENT_TIMESTAMP
+            RETURNING user_id, email
+            "#
+        )
+        .bind(hash_token(&verify_data.token))
+        .fetch_optional(&mut tx)
+        .await?;
+
+        let Some((user_id, email)) = token_row else {
+            return Ok(None);
+        };
+
+        // The token only counts if the user still has the address it was sent to
+        let updated = sqlx::query(
+            "UPDATE users SET email_verified = true WHERE id = $1 AND email = $2"
+        )
+        .bind(user_id)
+        .bind(&email)
+        .execute(&mut tx)
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        tx.commit().await?;
+        Ok(Some(user_id))
+    }
+    .await;
+
+    match result {
+        Ok(Some(user_id)) => {
+            cache.invalidate_user(user_id).await;
+
+            Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "Email verified successfully"
+            )))
+        }
+        Ok(None) => {
+            Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+                "Invalid or expired verification token"
+            )))
+        }
+        Err(_) => {
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to verify email"
+            )))
+        }
+    }
+}
+
+// Authenticated endpoint: send a new verification link, limited by a cooldown
+pub async fn resend_verification(
+    pool: web::Data<PgPool>,
+    config: web::Data<Config>,
+    mailer: web::Data<Mailer>,
+    req: HttpRequest,
+) -> Result<HttpResponse> {
+    let user_id: i32 = {
+        let extensions = req.extensions();
+        let claims = extensions.get::<Claims>().unwrap();
+        claims.sub.parse().unwrap()
+    };
+
+    let user: Option<(String, bool)> = match sqlx::query_as(
+        "SELECT email, email_verified FROM users WHERE id = $1 AND is_active = true"
+    )
+    .bind(user_id)
+    .fetch_optional(pool.get_ref())
+    .await
+    {
+        Ok(user) => user,
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    };
+
+    let (email, email_verified) = match user {
+        Some(user) => user,
+        None => {
+            return Ok(HttpResponse::NotFound().json(ApiResponse::<()>::error(
+                "User not found"
+            )));
+        }
+    };
+
+    if email_verified {
+        return Ok(HttpResponse::BadRequest().json(ApiResponse::<()>::error(
+            "Email is already verified"
+        )));
+    }
+
+    let last_sent: Option<DateTime<Utc>> = match sqlx::query_scalar(
+        "SELECT MAX(created_at) FROM email_verification_tokens WHERE user_id = $1"
+    )
+    .bind(user_id)
+    .fetch_one(pool.get_ref())
+    .await
+    {
+        Ok(last_sent) => last_sent,
+        Err(_) => {
+            return Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Database error"
+            )));
+        }
+    };
+
+    if let Some(last_sent) = last_sent {
+        let elapsed = (Utc::now() - last_sent).num_seconds();
+        if elapsed < config.email_verification_cooldown {
+            let retry_after = config.email_verification_cooldown - elapsed;
+            return Ok(HttpResponse::TooManyRequests()
+                .insert_header(("Retry-After", retry_after.to_string()))
+                .json(ApiResponse::<()>::error(&format!(
+                    "Please wait {retry_after} seconds before requesting another verification email"
+                ))));
+        }
+    }
+
+    match send_verification_email(&pool, &config, &mailer, user_id, &email).await {
+        Ok(()) => {
+            Ok(HttpResponse::Ok().json(ApiResponse::<()>::success_no_data(
+                "Verification email sent"
+            )))
+        }
+        Err(e) => {
+            log::error!("Failed to send verification email to user {user_id}: {e}");
+            Ok(HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+                "Failed to send verification email"
+            )))
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use actix_web::http::Method;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Attached to the request extensions by `AuthMiddleware` when an admin is impersonating the user
+#[derive(Debug, Clone, Copy)]
+pub struct Impersonation {
+    pub session_id: Uuid,
+    pub admin_id: i32,
+    pub user_id: i32,
+}
+
+/// What happened during an impersonation session, kept in `impersonation_events`
+#[derive(Debug, Clone, Copy)]
+pub enum ImpersonationEventType {
+    Start,
+    Stop,
+    Request,
+    /// A request refused because the endpoint is off limits while impersonating
+    Blocked,
+}
+
+impl ImpersonationEventType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImpersonationEventType::Start => "start",
+            ImpersonationEventType::Stop => "stop",
+            ImpersonationEventType::Request => "request",
+            ImpersonationEventType::Blocked => "blocked",
+        }
+    }
+}
+
+/// Endpoints that change credentials or account security, refused while impersonating.
+/// A path also matches everything below it; `{id}` matches a numeric segment.
+const BLOCKED_WHILE_IMPERSONATING: &[(Method, &str)] = &[
+    (Method::PUT, "/api/v1/users/password"),
+    (Method::PUT, "/api/v1/users/profile"),
+    (Method::DELETE, "/api/v1/users/sessions"),
+    (Method::POST, "/api/v1/users/2fa"),
+    (Method::POST, "/api/v1/users/identities"),
+    (Method::DELETE, "/api/v1/users/identities"),
+    (Method::POST, "/api/v1/users/api-keys"),
+    (Method::DELETE, "/api/v1/users/api-keys"),
+    (Method::POST, "/api/v1/users/deactivate"),
+    (Method::DELETE, "/api/v1/users/account"),
+    (Method::POST, "/api/v1/companies/invites"),
+    (Method::POST, "/api/v1/companies/{id}/invites"),
+    (Method::DELETE, "/api/v1/companies/{id}/invites"),
+    (Method::PUT, "/api/v1/companies/{id}/members"),
+    (Method::DELETE, "/api/v1/companies/{id}/members"),
+    (Method::POST, "/api/v1/companies/{id}/api-keys"),
+    (Method::DELETE, "/api/v1/companies/{id}/api-keys"),
+];
+
+fn path_matches(pattern: &str, path: &str) -> bool {
+    let mut path_segments = path.split('/');
+    pattern.split('/').all(|expected| match path_segments.next() {
+        Some(segment) if expected == "{id}" => !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit()),
+        Some(segment) => segment == expected,
+        None => false,
+    })
+}
+
+pub fn is_blocked(method: &Method, path: &str) -> bool {
+    BLOCKED_WHILE_IMPERSONATING.iter()
+        .any(|(blocked_method, pattern)| method == blocked_method && path_matches(pattern, path))
+}
+
+/// A request made during an impersonation session
+pub struct RequestDetails<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    pub status_code: Option<u16>,
+}
+
+/// Record an impersonation event. Failures are logged rather than returned so
+/// auditing never breaks a request.
+pub async fn record(
+    pool: &PgPool,
+    session_id: Uuid,
+    event_type: ImpersonationEventType,
+    request: Option<RequestDetails<'_>>,
+    ip_address: Option<&str>,
+    details: Option<&str>,
+) {
+    if let Err(e) = sqlx::query(
+        r#"
+        INSERT INTO impersonation_events (session_id, event_type, method, path, status_code, ip_address, details)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#
+    )
+    .bind(session_id)
+    .bind(event_type.as_str())
+    .bind(request.as_ref().map(|request| request.method))
+    .bind(request.as_ref().map(|request| request.path))
+    .bind(request.as_ref().and_then(|request| request.status_code).map(|status| status as i16))
+    .bind(ip_address)
+    .bind(details)
+    .execute(pool)
+    .await
+    {
+        log::error!("Failed to record impersonation event {} for session {session_id}: {e}", event_type.as_str());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks_listed_paths_and_everything_below_them() {
+        assert!(is_blocked(&Method::PUT, "/api/v1/users/password"));
+        assert!(is_blocked(&Method::POST, "/api/v1/users/2fa/disable"));
+        assert!(is_blocked(&Method::DELETE, "/api/v1/users/sessions/3f2a"));
+
+        assert!(!is_blocked(&Method::GET, "/api/v1/users/password"));
+        assert!(!is_blocked(&Method::PUT, "/api/v1/users/passwords"));
+        assert!(!is_blocked(&Method::PUT, "/api/v1/users"));
+    }
+
+    #[test]
+    fn blocks_company_team_and_key_changes() {
+        assert!(is_blocked(&Method::POST, "/api/v1/companies/invites/accept"));
+        assert!(is_blocked(&Method::POST, "/api/v1/companies/7/invites"));
+        assert!(is_blocked(&Method::DELETE, "/api/v1/companies/7/invites/3"));
+        assert!(is_blocked(&Method::PUT, "/api/v1/companies/7/members/12"));
+        assert!(is_blocked(&Method::DELETE, "/api/v1/companies/7/members/12"));
+        assert!(is_blocked(&Method::POST, "/api/v1/companies/7/api-keys"));
+        assert!(is_blocked(&Method::DELETE, "/api/v1/companies/7/api-keys/5"));
+
+        assert!(!is_blocked(&Method::GET, "/api/v1/companies/7/members"));
+        assert!(!is_blocked(&Method::GET, "/api/v1/companies/7/api-keys"));
+        assert!(!is_blocked(&Method::PUT, "/api/v1/companies/7"));
+    }
+
+    #[test]
+    fn id_segments_match_numbers_only() {
+        assert!(path_matches("/api/v1/things/{id}/keys", "/api/v1/things/42/keys"));
+        assert!(path_matches("/api/v1/things/{id}/keys", "/api/v1/things/42/keys/7"));
+        assert!(!path_matches("/api/v1/things/{id}/keys", "/api/v1/things/mine/keys"));
+        assert!(!path_matches("/api/v1/things/{id}/keys", "/api/v1/things//keys"));
+        assert!(!path_matches("/api/v1/things/{id}/keys", "/api/v1/things/42"));
+    }
+}
@@ -0,0 +1,304 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::postgres::PgArguments;
+use sqlx::query::QueryAs;
+use sqlx::{PgPool, Postgres};
+use std::collections::BTreeMap;
+
+use crate::jobs::{self, contains_pattern, JOB_COLUMNS};
+use crate::models::{FacetCount, JobSearchHit, JobSearchParams, JobSearchResults};
+
+const MAX_PER_PAGE: i64 = 100;
+/// Deepest page that can be requested; keeps the row offset small and well inside `i64`
+const MAX_PAGE: i64 = 10_000;
+/// Values listed for open-ended facets (location, skills), most common first
+const MAX_FACET_VALUES: i64 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SearchSort {
+    Relevance,
+    Recency,
+}
+
+impl SearchSort {
+    fn order_by(&self) -> &'static str {
+        match self {
+            SearchSort::Relevance => "relevance DESC, published_at DESC, id DESC",
+            SearchSort::Recency => "published_at DESC, id DESC",
+        }
+    }
+}
+
+/// A validated search. `None` and empty lists mean the filter is not applied.
+#[derive(Debug)]
+pub struct JobSearch {
+    pub text: Option<String>,
+    pub location: Option<String>,
+    pub remote_policies: Option<Vec<String>>,
+    pub employment_types: Option<Vec<String>>,
+    pub salary_min: Option<i32>,
+    pub salary_max: Option<i32>,
+    pub salary_currency: Option<String>,
+    pub posted_since: Option<DateTime<Utc>>,
+    pub skills: Option<Vec<String>>,
+    pub sort: SearchSort,
+    pub page: i64,
+    pub per_page: i64,
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|value| !value.is_empty())
+}
+
+/// Split a comma-separated filter, checking every value against `allowed` when given
+fn list(field: &str, value: &Option<String>, allowed: Option<&[&str]>) -> Result<Option<Vec<String>>, String> {
+    let Some(value) = non_empty(value) else {
+        return Ok(None);
+    };
+
+    let mut values: Vec<String> = value.split(',')
+        .map(|item| item.trim().to_lowercase())
+        .filter(|item| !item.is_empty())
+        .collect();
+    values.sort();
+    values.dedup();
+
+    if let Some(allowed) = allowed {
+        if values.iter().any(|item| !allowed.contains(&item.as_str())) {
+            return Err(format!("{field} must be a comma-separated list of: {}", allowed.join(", ")));
+        }
+    }
+    Ok(Some(values).filter(|values| !values.is_empty()))
+}
+
+/// `7d` for the last seven days, or an RFC 3339 timestamp
+fn parse_posted_since(value: &str) -> Option<DateTime<Utc>> {
+    if let Some(days) = value.strip_suffix('d') {
+        return days.parse::<i64>().ok()
+            .filter(|days| (1..=3650).contains(days))
+            .map(|days| Utc::now() - Duration::days(days));
+    }
+    DateTime::parse_from_rfc3339(value).ok().map(|since| since.with_timezone(&Utc))
+}
+
+impl JobSearch {
+    /// Validate the query string; the error is the message to show
+    pub fn from_params(params: &JobSearchParams) -> Result<Self, String> {
+        let text = non_empty(&params.q).map(str::to_string);
+        if text.as_ref().is_some_and(|text| text.chars().count() > 200) {
+            return Err("q must be at most 200 characters".to_string());
+        }
+
+        let sort = match params.sort.as_deref() {
+            Some("relevance") => SearchSort::Relevance,
+            Some("recency") => SearchSort::Recency,
+            None if text.is_some() => SearchSort::Relevance,
+            None => SearchSort::Recency,
+            Some(_) => return Err("sort must be relevance or recency".to_string()),
+        };
+
+        if let (Some(min), Some(max)) = (params.salary_min, params.salary_max) {
+            if min > max {
+                return Err("salary_min cannot be greater than salary_max".to_string());
+            }
+        }
+        let salary_currency = non_empty(&params.salary_currency).map(str::to_uppercase);
+        if salary_currency.as_ref().is_some_and(|currency| currency.len() != 3 || !currency.bytes().all(|b| b.is_ascii_uppercase())) {
+            return Err("salary_currency must be a three-letter ISO 4217 code".to_string());
+        }
+
+        let posted_since = match non_empty(&params.posted_since) {
+            Some(value) => Some(parse_posted_since(value).ok_or_else(|| {
+                "posted_since must be a number of days such as 7d, or an RFC 3339 timestamp".to_string()
+            })?),
+            None => None,
+        };
+
+        let page = params.page.unwrap_or(1);
+        if !(1..=MAX_PAGE).contains(&page) {
+            return Err(format!("page must be between 1 and {MAX_PAGE}"));
+        }
+
+        Ok(JobSearch {
+            location: non_empty(&params.location).map(contains_pattern),
+            remote_policies: list("remote_policy", &params.remote_policy, Some(jobs::REMOTE_POLICIES))?,
+            employment_types: list("employment_type", &params.employment_type, Some(jobs::EMPLOYMENT_TYPES))?,
+            salary_min: params.salary_min,
+            salary_max: params.salary_max,
+            salary_currency,
+            posted_since,
+            skills: list("skills", &params.skills, None)?,
+            text,
+            sort,
+            page,
+            per_page: params.per_page.unwrap_or(20).clamp(1, MAX_PER_PAGE),
+        })
+    }
+}
+
+/// Published postings with what matched and the filters each one passes.
+/// Filters are computed as flags rather than applied so each facet can ignore its own filter.
+fn matches_cte() -> String {
+    format!(
+        r#"
+        WITH search AS (
+            SELECT CASE WHEN numnode(websearch_to_tsquery('english', $1::TEXT)) > 0
+                        THEN websearch_to_tsquery('english', $1::TEXT) END AS query
+        ),
+        matches AS (
+            SELECT {JOB_COLUMNS},
+                   COALESCE(ts_rank_cd(j.search_vector, s.query), 0)::REAL AS relevance,
+                   COALESCE($2::TEXT IS NULL OR j.location ILIKE $2, false) AS location_ok,
+                   ($3::VARCHAR[] IS NULL OR j.remote_policy = ANY($3)) AS remote_policy_ok,
+                   ($4::VARCHAR[] IS NULL OR j.employment_type = ANY($4)) AS employment_type_ok,
+                   COALESCE(($5::INTEGER IS NULL OR COALESCE(j.salary_max, j.salary_min) >= $5)
+                        AND ($6::INTEGER IS NULL OR COALESCE(j.salary_min, j.salary_max) <= $6), false) AS salary_ok,
+                   COALESCE($8::TIMESTAMPTZ IS NULL OR j.published_at >= $8, false) AS posted_since_ok,
+                   ($9::TEXT[] IS NULL OR j.skills @> $9) AS skills_ok
+            FROM jobs j
+            JOIN companies c ON c.id = j.company_id
+            LEFT JOIN users u ON u.id = j.employer_id
+            CROSS JOIN search s
+            WHERE j.status = 'published' AND (j.expires_at IS NULL OR j.expires_at > CURRENT_TIMESTAMP)
+              AND COALESCE(u.is_active, true)
+              AND (s.query IS NULL OR j.search_vector @@ s.query)
+              AND ($7::VARCHAR IS NULL OR j.salary_currency = $7)
+        )
+        "#
+    )
+}
+
+const ALL_FILTERS: &str =
+    "location_ok AND remote_policy_ok AND employment_type_ok AND salary_ok AND posted_since_ok AND skills_ok";
+
+fn hits_query(sort: SearchSort) -> String {
+    format!(
+        r#"
+        {}
+        SELECT * FROM matches
+        WHERE {ALL_FILTERS}
+        ORDER BY {}
+        LIMIT $10 OFFSET $11
+        "#,
+        matches_cte(),
+        sort.order_by()
+    )
+}
+
+/// One row per facet value, plus a `total` row. Each facet counts the postings
+/// matching every filter except its own, so clients can show what widening it would add.
+fn facets_query() -> String {
+    format!(
+        r#"
+        {},
+        counted AS (
+            SELECT 'location' AS facet, location AS value, COUNT(*) AS count FROM matches
+            WHERE location IS NOT NULL
+              AND remote_policy_ok AND employment_type_ok AND salary_ok AND posted_since_ok AND skills_ok
+            GROUP BY location
+            UNION ALL
+            SELECT 'remote_policy', remote_policy, COUNT(*) FROM matches
+            WHERE location_ok AND employment_type_ok AND salary_ok AND posted_since_ok AND skills_ok
+            GROUP BY remote_policy
+            UNION ALL
+            SELECT 'employment_type', employment_type, COUNT(*) FROM matches
+            WHERE location_ok AND remote_policy_ok AND salary_ok AND posted_since_ok AND skills_ok
+            GROUP BY employment_type
+            UNION ALL
+            SELECT 'skills', skill, COUNT(*) FROM matches CROSS JOIN UNNEST(skills) AS skill
+            WHERE location_ok AND remote_policy_ok AND employment_type_ok AND salary_ok AND posted_since_ok
+            GROUP BY skill
+        ),
+        ranked AS (
+            SELECT facet, value, count, ROW_NUMBER() OVER (PARTITION BY facet ORDER BY count DESC, value) AS ordinal
+            FROM counted
+        ),
+        buckets AS (
+            SELECT 'posted_since' AS facet, b.label AS value, COUNT(m.id) AS count, b.ordinal::BIGINT AS ordinal
+            FROM (VALUES (1, '1d', INTERVAL '1 day'), (2, '7d', INTERVAL '7 days'), (3, '30d', INTERVAL '30 days'))
+                AS b(ordinal, label, span)
+            LEFT JOIN matches m ON m.published_at >= CURRENT_TIMESTAMP - b.span
+                AND m.location_ok AND m.remote_policy_ok AND m.employment_type_ok AND m.salary_ok AND m.skills_ok
+            GROUP BY b.ordinal, b.label
+            UNION ALL
+            SELECT 'salary', b.label, COUNT(m.id), b.ordinal::BIGINT
+            FROM (VALUES (1, '0-30000', 0, 30000), (2, '30000-60000', 30000, 60000), (3, '60000-100000', 60000, 100000),
+                         (4, '100000-150000', 100000, 150000), (5, '150000+', 150000, NULL))
+                AS b(ordinal, label, low, high)
+            LEFT JOIN matches m ON COALESCE(m.salary_max, m.salary_min) >= b.low
+                AND (b.high IS NULL OR COALESCE(m.salary_min, m.salary_max) < b.high)
+                AND m.location_ok AND m.remote_policy_ok AND m.employment_type_ok AND m.posted_since_ok AND m.skills_ok
+            GROUP BY b.ordinal, b.label
+        )
+        SELECT facet, value, count, ordinal FROM ranked WHERE ordinal <= {MAX_FACET_VALUES}
+        UNION ALL
+        SELECT facet, value, count, ordinal FROM buckets
+        UNION ALL
+        SELECT 'total', NULL, COUNT(*), 1 FROM matches WHERE {ALL_FILTERS}
+        ORDER BY facet, ordinal
+        "#,
+        matches_cte()
+    )
+}
+
+/// Bind the parameters shared by both queries, in `matches_cte` order
+fn bind_filters<'q, O>(
+    query: QueryAs<'q, Postgres, O, PgArguments>,
+    search: &'q JobSearch,
+) -> QueryAs<'q, Postgres, O, PgArguments> {
+    query
+        .bind(&search.text)
+        .bind(&search.location)
+        .bind(&search.remote_policies)
+        .bind(&search.employment_types)
+        .bind(search.salary_min)
+        .bind(search.salary_max)
+        .bind(&search.salary_currency)
+        .bind(search.posted_since)
+        .bind(&search.skills)
+}
+
+pub async fn search(pool: &PgPool, search: &JobSearch) -> Result<JobSearchResults, sqlx::Error> {
+    let hits_sql = hits_query(search.sort);
+    let jobs = bind_filters(sqlx::query_as::<_, JobSearchHit>(&hits_sql), search)
+        .bind(search.per_page)
+        .bind((search.page - 1) * search.per_page)
+        .fetch_all(pool)
+        .await?;
+
+    let facets_sql = facets_query();
+    let rows: Vec<(String, Option<String>, i64, i64)> =
+        bind_filters(sqlx::query_as(&facets_sql), search)
+            .fetch_all(pool)
+            .await?;
+
+    let mut total = 0;
+    let mut facets: BTreeMap<String, Vec<FacetCount>> = BTreeMap::new();
+    for (facet, value, count, _) in rows {
+        match value {
+            Some(value) => facets.entry(facet).or_default().push(FacetCount { value, count }),
+            None => total = count,
+        }
+    }
+
+    Ok(JobSearchResults { jobs, total, page: search.page, per_page: search.per_page, facets })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::web::Query;
+
+    fn search(query: &str) -> Result<JobSearch, String> {
+        JobSearch::from_params(&Query::<JobSearchParams>::from_query(query).unwrap())
+    }
+
+    #[test]
+    fn bounds_the_page() {
+        assert_eq!(search("").unwrap().page, 1);
+        assert_eq!(search("page=10000&per_page=100").unwrap().page, MAX_PAGE);
+        assert!(search("page=0").is_err());
+        assert!(search("page=10001").is_err());
+        assert!(search(&format!("page={}", i64::MAX)).is_err());
+        assert!(search(&format!("page={}", i64::MIN)).is_err());
+    }
+}
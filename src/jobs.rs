@@ -0,0 +1,237 @@
+use chrono::{Duration, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::time::Duration as StdDuration;
+
+use crate::models::{CreateJobRequest, Job, UpdateJobRequest};
+
+pub const EMPLOYMENT_TYPES: &[&str] = &["full_time", "part_time", "contract", "temporary", "internship", "freelance"];
+pub const SENIORITIES: &[&str] = &["intern", "junior", "mid", "senior", "lead", "executive"];
+pub const REMOTE_POLICIES: &[&str] = &["on_site", "hybrid", "remote"];
+
+const MAX_SKILLS: usize = 30;
+const MAX_SKILL_LENGTH: usize = 50;
+const MAX_DESCRIPTION_LENGTH: usize = 20_000;
+
+/// Columns of `Job`, for queries over `jobs j JOIN companies c ON c.id = j.company_id`
+pub const JOB_COLUMNS: &str = r#"
+    j.id, j.company_id, c.name AS company_name, c.verified_at IS NOT NULL AS company_verified, j.employer_id, j.title, j.description, j.employment_type,
+    j.seniority, j.location, j.remote_policy, j.salary_min, j.salary_max, j.salary_currency, j.skills, j.status,
+    j.published_at, j.closed_at, j.expires_at, j.created_at, j.updated_at
+"#;
+
+/// Where a posting is in its lifecycle: draft → published → closed or expired
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    Draft,
+    Published,
+    Closed,
+    Expired,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Draft => "draft",
+            JobStatus::Published => "published",
+            JobStatus::Closed => "closed",
+            JobStatus::Expired => "expired",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<Self> {
+        match status {
+            "draft" => Some(JobStatus::Draft),
+            "published" => Some(JobStatus::Published),
+            "closed" => Some(JobStatus::Closed),
+            "expired" => Some(JobStatus::Expired),
+            _ => None,
+        }
+    }
+
+    /// Transitions an employer may make. Postings only expire on their own,
+    /// and closed or expired postings stay that way.
+    pub fn can_become(&self, next: JobStatus) -> bool {
+        matches!(
+            (self, next),
+            (JobStatus::Draft, JobStatus::Published) | (JobStatus::Published, JobStatus::Closed)
+        )
+    }
+
+    /// Whether the content of a posting in this status can still change
+    pub fn is_editable(&self) -> bool {
+        matches!(self, JobStatus::Draft | JobStatus::Published)
+    }
+}
+
+fn one_of(field: &str, value: &str, allowed: &[&str]) -> Result<(), String> {
+    if allowed.contains(&value) {
+        Ok(())
+    } else {
+        Err(format!("{field} must be one of: {}", allowed.join(", ")))
+    }
+}
+
+/// Check a posting and tidy it up: trimmed text, lowercase unique skills and an
+/// uppercase currency. Returns the message to show when something is wrong.
+pub fn validate(mut job: CreateJobRequest) -> Result<CreateJobRequest, String> {
+    job.title = job.title.trim().to_string();
+    if job.title.chars().count() < 3 || job.title.chars().count() > 200 {
+        return Err("Title must be between 3 and 200 characters".to_string());
+    }
+
+    job.description = job.description.trim().to_string();
+    if job.description.is_empty() || job.description.chars().count() > MAX_DESCRIPTION_LENGTH {
+        return Err(format!("Description must be between 1 and {MAX_DESCRIPTION_LENGTH} characters"));
+    }
+
+    one_of("employment_type", &job.employment_type, EMPLOYMENT_TYPES)?;
+    one_of("seniority", &job.seniority, SENIORITIES)?;
+    one_of("remote_policy", &job.remote_policy, REMOTE_POLICIES)?;
+
+    job.location = job.location
+        .map(|location| location.trim().to_string())
+        .filter(|location| !location.is_empty());
+    if job.location.as_ref().is_some_and(|location| location.chars().count() > 200) {
+        return Err("Location must be at most 200 characters".to_string());
+    }
+    if job.location.is_none() && job.remote_policy != "remote" {
+        return Err("Location is required unless the job is fully remote".to_string());
+    }
+
+    if job.salary_min.is_some_and(|min| min < 0) || job.salary_max.is_some_and(|max| max < 0) {
+        return Err("Salary cannot be negative".to_string());
+    }
+    if let (Some(min), Some(max)) = (job.salary_min, job.salary_max) {
+        if min > max {
+            return Err("salary_min cannot be greater than salary_max".to_string());
+        }
+    }
+    job.salary_currency = job.salary_currency.map(|currency| currency.trim().to_uppercase());
+    let has_salary = job.salary_min.is_some() || job.salary_max.is_some();
+    match &job.salary_currency {
+        Some(currency) if currency.len() != 3 || !currency.bytes().all(|b| b.is_ascii_uppercase()) => {
+            return Err("salary_currency must be a three-letter ISO 4217 code".to_string());
+        }
+        None if has_salary => {
+            return Err("salary_currency is required when a salary is given".to_string());
+        }
+        _ => {}
+    }
+
+    let mut skills: Vec<String> = job.skills.iter()
+        .map(|skill| skill.trim().to_lowercase())
+        .filter(|skill| !skill.is_empty())
+        .collect();
+    skills.sort();
+    skills.dedup();
+    if skills.len() > MAX_SKILLS {
+        return Err(format!("At most {MAX_SKILLS} skills can be listed"));
+    }
+    if skills.iter().any(|skill| skill.chars().count() > MAX_SKILL_LENGTH) {
+        return Err(format!("Skills must be at most {MAX_SKILL_LENGTH} characters each"));
+    }
+    job.skills = skills;
+
+    Ok(job)
+}
+
+/// The posting `current` would become with `changes` applied, still to be validated
+pub fn merge(current: &Job, changes: UpdateJobRequest) -> CreateJobRequest {
+    CreateJobRequest {
+        company_id: Some(current.company_id),
+        title: changes.title.unwrap_or_else(|| current.title.clone()),
+        description: changes.description.unwrap_or_else(|| current.description.clone()),
+        employment_type: changes.employment_type.unwrap_or_else(|| current.employment_type.clone()),
+        seniority: changes.seniority.unwrap_or_else(|| current.seniority.clone()),
+        location: changes.location.or_else(|| current.location.clone()),
+        remote_policy: changes.remote_policy.unwrap_or_else(|| current.remote_policy.clone()),
+        salary_min: changes.salary_min.or(current.salary_min),
+        salary_max: changes.salary_max.or(current.salary_max),
+        salary_currency: changes.salary_currency.or_else(|| current.salary_currency.clone()),
+        skills: changes.skills.unwrap_or_else(|| current.skills.clone()),
+        expires_at: changes.expires_at.or(current.expires_at),
+    }
+}
+
+/// `%value%` for ILIKE, with the wildcards in `value` itself taken literally
+pub fn contains_pattern(value: &str) -> String {
+    let escaped = value.trim()
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{escaped}%")
+}
+
+/// When a posting published now without an expiry of its own stops being listed
+pub fn default_expiry(lifetime_days: i64) -> chrono::DateTime<Utc> {
+    Utc::now() + Duration::days(lifetime_days)
+}
+
+pub async fn find(pool: &PgPool, job_id: i32) -> Result<Option<Job>, sqlx::Error> {
+    sqlx::query_as::<_, Job>(&format!(
+        "SELECT {JOB_COLUMNS} FROM jobs j JOIN companies c ON c.id = j.company_id WHERE j.id = $1"
+    ))
+    .bind(job_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Whether the company may add another posting: verified companies always may,
+/// unverified ones only while they have fewer than `limit` drafts and published
+/// postings. Locks the company so concurrent postings are counted one at a time.
+pub async fn within_posting_limit(
+    tx: &mut Transaction<'_, Postgres>,
+    company_id: i32,
+    limit: i64,
+) -> Result<bool, sqlx::Error> {
+    if limit <= 0 {
+        return Ok(true);
+    }
+
+    let verified: bool = sqlx::query_scalar(
+        "SELECT verified_at IS NOT NULL FROM companies WHERE id = $1 FOR UPDATE"
+    )
+    .bind(company_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    if verified {
+        return Ok(true);
+    }
+
+    let open: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM jobs WHERE company_id = $1 AND status IN ('draft', 'published')"
+    )
+    .bind(company_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    Ok(open < limit)
+}
+
+/// Mark published postings past their expiry as expired
+pub async fn expire_postings(pool: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE jobs SET status = 'expired', updated_at = CURRENT_TIMESTAMP
+        WHERE status = 'published' AND expires_at <= CURRENT_TIMESTAMP
+        "#
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Periodically expire published postings whose expiry has passed. Listings
+/// already leave them out in between runs.
+pub fn spawn_expiry(pool: PgPool, interval_seconds: u64) {
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(StdDuration::from_secs(interval_seconds.max(1)));
+        loop {
+            interval.tick().await;
+            match expire_postings(&pool).await {
+                Ok(0) => {}
+                Ok(expired) => log::info!("Expired {expired} job postings"),
+                Err(e) => log::error!("Failed to expire job postings: {e}"),
+            }
+        }
+    });
+}
@@ -0,0 +1,294 @@
+use std::sync::Arc;
+use std::time::Duration as StdDuration;
+
+use anyhow::Context;
+use chrono::{DateTime, Duration, Utc};
+use data_encoding::BASE64URL_NOPAD;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, OctetKeyPairParameters,
+    OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey};
+use parking_lot::RwLock;
+use rand::RngCore;
+use ring::signature::{Ed25519KeyPair, KeyPair, RsaKeyPair};
+use sqlx::PgPool;
+
+use crate::config::Config;
+
+/// How often every replica reloads the key table
+const REFRESH_INTERVAL_SECONDS: u64 = 30;
+
+/// Delay between publishing a rotated key and signing with it. Twice the refresh
+/// interval, so every replica can verify the new key before any token uses it.
+const ACTIVATION_DELAY_SECONDS: i64 = 2 * REFRESH_INTERVAL_SECONDS as i64;
+
+/// A key pair used to sign access tokens, identified by `kid`
+pub struct SigningKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    jwk: Jwk,
+    activates_at: DateTime<Utc>,
+    /// When the next key took over signing, if it has
+    superseded_at: Option<DateTime<Utc>>,
+}
+
+impl SigningKey {
+    fn from_pem(
+        kid: String,
+        private_key: &str,
+        activates_at: DateTime<Utc>,
+        superseded_at: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<Self> {
+        let (algorithm, encoding_key, algorithm_parameters) = parse_private_key(private_key)?;
+
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                algorithm: Some(algorithm),
+                key_id: Some(kid.clone()),
+                ..Default::default()
+            },
+            algorithm: algorithm_parameters,
+        };
+        let decoding_key = DecodingKey::from_jwk(&jwk)?;
+
+        Ok(Self { kid, algorithm, encoding_key, decoding_key, jwk, activates_at, superseded_at })
+    }
+
+    pub fn encoding_key(&self) -> &EncodingKey {
+        &self.encoding_key
+    }
+
+    pub fn decoding_key(&self) -> &DecodingKey {
+        &self.decoding_key
+    }
+}
+
+/// Work out the algorithm and public parameters of a PEM private key
+fn parse_private_key(private_key: &str) -> anyhow::Result<(Algorithm, EncodingKey, AlgorithmParameters)> {
+    let pem = pem::parse(private_key).context("Invalid PEM")?;
+
+    let rsa_key_pair = match pem.tag.as_str() {
+        "PRIVATE KEY" => {
+            if let Ok(key_pair) = Ed25519KeyPair::from_pkcs8_maybe_unchecked(&pem.contents) {
+                let parameters = AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                    key_type: OctetKeyPairType::OctetKeyPair,
+                    curve: EllipticCurve::Ed25519,
+                    x: BASE64URL_NOPAD.encode(key_pair.public_key().as_ref()),
+                });
+                return Ok((Algorithm::EdDSA, EncodingKey::from_ed_pem(private_key.as_bytes())?, parameters));
+            }
+            RsaKeyPair::from_pkcs8(&pem.contents)
+        }
+        "RSA PRIVATE KEY" => RsaKeyPair::from_der(&pem.contents),
+        tag => anyhow::bail!("Unsupported key type {tag}; expected an Ed25519 or RSA private key"),
+    }
+    .map_err(|e| anyhow::anyhow!("Unsupported private key: {e}"))?;
+
+    let public_key = rsa_key_pair.public_key();
+    let parameters = AlgorithmParameters::RSA(RSAKeyParameters {
+        key_type: RSAKeyType::RSA,
+        n: BASE64URL_NOPAD.encode(public_key.modulus().big_endian_without_leading_zero()),
+        e: BASE64URL_NOPAD.encode(public_key.exponent().big_endian_without_leading_zero()),
+    });
+    Ok((Algorithm::RS256, EncodingKey::from_rsa_pem(private_key.as_bytes())?, parameters))
+}
+
+fn generate_ed25519_pem() -> anyhow::Result<String> {
+    let document = Ed25519KeyPair::generate_pkcs8(&ring::rand::SystemRandom::new())
+        .map_err(|_| anyhow::anyhow!("Failed to generate Ed25519 key"))?;
+
+    Ok(pem::encode(&pem::Pem {
+        tag: "PRIVATE KEY".to_string(),
+        contents: document.as_ref().to_vec(),
+    }))
+}
+
+fn generate_kid() -> String {
+    let mut bytes = [0u8; 8];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    hex::encode(bytes)
+}
+
+#[derive(sqlx::FromRow)]
+struct SigningKeyRow {
+    kid: String,
+    private_key: String,
+    activates_at: DateTime<Utc>,
+    superseded_at: Option<DateTime<Utc>>,
+}
+
+/// The signing keys every replica shares through the `jwt_signing_keys` table.
+/// Lookups are synchronous; a background task keeps the set current.
+#[derive(Clone)]
+pub struct KeyStore {
+    keys: Arc<RwLock<Vec<Arc<SigningKey>>>>,
+    grace_period: Duration,
+}
+
+impl KeyStore {
+    /// Load the keys, creating a first Ed25519 key when the table is empty
+    pub async fn load(pool: &PgPool, config: &Config) -> anyhow::Result<Self> {
+        ensure_initial_key(pool).await?;
+
+        // Retired keys must outlive every token they signed
+        let grace_period = Duration::seconds(
+            config.jwt_key_grace_period
+                .max(config.jwt_expiration)
+                .max(config.two_factor_challenge_expiration)
+                .max(config.impersonation_expiration)
+        );
+        let store = Self { keys: Arc::new(RwLock::new(Vec::new())), grace_period };
+        store.reload(pool).await?;
+
+        if store.signing_key().is_none() {
+            anyhow::bail!("No active JWT signing key");
+        }
+        Ok(store)
+    }
+
+    pub async fn reload(&self, pool: &PgPool) -> anyhow::Result<()> {
+        let rows: Vec<SigningKeyRow> = sqlx::query_as(
+            r#"
+            SELECT kid, private_key, activates_at,
+                   LEAD(activates_at) OVER (ORDER BY activates_at, created_at) AS superseded_at
+            FROM jwt_signing_keys
+            ORDER BY activates_at, created_at
+            "#
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let cutoff = Utc::now() - self.grace_period;
+        let mut keys = Vec::with_capacity(rows.len());
+        for row in rows {
+            if row.superseded_at.is_some_and(|superseded_at| superseded_at < cutoff) {
+                continue;
+            }
+            match SigningKey::from_pem(row.kid.clone(), &row.private_key, row.activates_at, row.superseded_at) {
+                Ok(key) => keys.push(Arc::new(key)),
+                Err(e) => log::error!("Skipping unusable JWT signing key {}: {e}", row.kid),
+            }
+        }
+
+        *self.keys.write() = keys;
+        Ok(())
+    }
+
+    /// The newest key whose activation time has passed
+    pub fn signing_key(&self) -> Option<Arc<SigningKey>> {
+        let now = Utc::now();
+        self.keys.read()
+            .iter()
+            .filter(|key| key.activates_at <= now)
+            .max_by_key(|key| key.activates_at)
+            .cloned()
+    }
+
+    /// A key that may still verify tokens: not yet superseded, or within the grace period
+    pub fn verification_key(&self, kid: &str) -> Option<Arc<SigningKey>> {
+        let cutoff = Utc::now() - self.grace_period;
+        self.keys.read()
+            .iter()
+            .find(|key| key.kid == kid)
+            .filter(|key| key.superseded_at.is_none_or(|superseded_at| superseded_at >= cutoff))
+            .cloned()
+    }
+
+    /// Public keys for `/.well-known/jwks.json`, including keys that are about to activate
+    pub fn jwks(&self) -> JwkSet {
+        let cutoff = Utc::now() - self.grace_period;
+        JwkSet {
+            keys: self.keys.read()
+                .iter()
+                .filter(|key| key.superseded_at.is_none_or(|superseded_at| superseded_at >= cutoff))
+                .map(|key| key.jwk.clone())
+                .collect(),
+        }
+    }
+}
+
+async fn ensure_initial_key(pool: &PgPool) -> anyhow::Result<()> {
+    let mut tx = pool.begin().await?;
+
+    // Replicas starting together must not each create a key
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtext('jwt_signing_keys'))")
+        .execute(&mut tx)
+        .await?;
+
+    let has_key: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM jwt_signing_keys)")
+        .fetch_one(&mut tx)
+        .await?;
+
+    if !has_key {
+        let kid = generate_kid();
+        sqlx::query("INSERT INTO jwt_signing_keys (kid, algorithm, private_key) VALUES ($1, 'EdDSA', $2)")
+            .bind(&kid)
+            .bind(generate_ed25519_pem()?)
+            .execute(&mut tx)
+            .await?;
+        log::info!("Created initial JWT signing key {kid}");
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Add a new signing key that takes over once every replica has picked it up.
+/// Without `private_key` a fresh Ed25519 key is generated.
+pub async fn rotate(pool: &PgPool, private_key: Option<String>) -> anyhow::Result<(String, DateTime<Utc>)> {
+    let private_key = match private_key {
+        Some(private_key) => private_key,
+        None => generate_ed25519_pem()?,
+    };
+    let kid = generate_kid();
+    // Reject bad keys before anything is stored
+    let key = SigningKey::from_pem(kid.clone(), &private_key, Utc::now(), None)?;
+    let activates_at = Utc::now() + Duration::seconds(ACTIVATION_DELAY_SECONDS);
+    let algorithm = match key.algorithm {
+        Algorithm::EdDSA => "EdDSA",
+        _ => "RS256",
+    };
+
+    sqlx::query("INSERT INTO jwt_signing_keys (kid, algorithm, private_key, activates_at) VALUES ($1, $2, $3, $4)")
+        .bind(&kid)
+        .bind(algorithm)
+        .bind(&private_key)
+        .bind(activates_at)
+        .execute(pool)
+        .await?;
+
+    Ok((kid, activates_at))
+}
+
+/// `rotate-signing-key [private-key.pem]`: rotate the JWT signing key from the command line,
+/// importing an RSA or Ed25519 PEM key or generating a new Ed25519 key.
+pub async fn rotate_from_cli(pool: &PgPool, args: &[String]) -> anyhow::Result<()> {
+    let private_key = match args.first() {
+        Some(path) => Some(
+            std::fs::read_to_string(path).with_context(|| format!("Failed to read {path}"))?
+        ),
+        None => None,
+    };
+
+    let (kid, activates_at) = rotate(pool, private_key).await?;
+    println!("Added JWT signing key {kid}; it starts signing at {activates_at}");
+    Ok(())
+}
+
+/// Keep `store` in sync with keys added by other replicas or the CLI
+pub fn spawn_refresher(pool: PgPool, store: KeyStore) {
+    actix_web::rt::spawn(async move {
+        let mut interval = actix_web::rt::time::interval(StdDuration::from_secs(REFRESH_INTERVAL_SECONDS));
+        loop {
+            interval.tick().await;
+            if let Err(e) = store.reload(&pool).await {
+                log::error!("Failed to reload JWT signing keys: {e}");
+            }
+        }
+    });
+}
+
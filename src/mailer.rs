@@ -0,0 +1,53 @@
+use reqwest::Client;
+use serde_json::json;
+use crate::config::Config;
+
+/// Outgoing email delivery.
+///
+/// When `MAIL_API_URL` is configured, messages are POSTed as JSON to that
+/// HTTP endpoint (most transactional mail providers accept this shape).
+/// Otherwise they are written to the log so local development works
+/// without a mail provider.
+#[derive(Clone)]
+pub struct Mailer {
+    client: Client,
+    api_url: Option<String>,
+    api_key: Option<String>,
+    from: String,
+}
+
+impl Mailer {
+    pub fn new(config: &Config) -> Self {
+        if config.mail_api_url.is_none() {
+            log::warn!("MAIL_API_URL is not set, outgoing emails will only be logged");
+        }
+
+        Self {
+            client: Client::new(),
+            api_url: config.mail_api_url.clone(),
+            api_key: config.mail_api_key.clone(),
+            from: config.mail_from.clone(),
+        }
+    }
+
+    pub async fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), reqwest::Error> {
+        let Some(api_url) = &self.api_url else {
+            log::info!("[mail] to={to} subject={subject:?}\n{body}");
+            return Ok(());
+        };
+
+        let mut request = self.client.post(api_url).json(&json!({
+            "from": self.from,
+            "to": to,
+            "subject": subject,
+            "text": body,
+        }));
+
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
@@ -7,17 +7,42 @@ use std::net::{TcpListener, SocketAddr};
 #[global_allocator]
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
+mod accounts;
+mod audit;
+mod bootstrap;
 mod config;
 mod cache;
+mod client_ip;
 mod database;
+mod mailer;
 mod handlers;
 mod models;
 mod middleware; // Re-enabled middleware
 mod utils;
+mod revocation;
+mod keys;
+mod oidc;
+mod impersonation;
+mod companies;
+mod domain_verification;
+mod jobs;
+mod job_search;
+mod api_keys;
+mod applications;
+mod pipelines;
+mod candidate_profiles;
+mod password_policy;
+mod permissions;
+mod throttle;
 
 use config::Config;
 use database::create_pool;
 use cache::CacheManager;
+use mailer::Mailer;
+use keys::KeyStore;
+use oidc::OidcClient;
+use utils::PasswordHasher;
+use password_policy::PasswordPolicy;
 
 /// Find an available port starting from the given port
 fn find_available_port(host: &str, start_port: u16) -> u16 {
@@ -51,12 +76,55 @@ async fn main() -> std::io::Result<()> {
     // Initialize cache manager for performance optimization
     let cache_manager = CacheManager::new();
     
+    // Outgoing email delivery (password resets, notifications)
+    let mailer = Mailer::new(&config);
+    
+    // TXT lookups for company domain verification
+    let txt_resolver = domain_verification::resolver_from_config(&config);
+    
+    // Argon2id for new password hashes; older bcrypt hashes still verify
+    let password_hasher = PasswordHasher::from_config(&config)
+        .expect("Failed to configure password hashing");
+    let password_policy = PasswordPolicy::from_config(&config)
+        .expect("Failed to load password policy");
+    
     // Run database migrations
     sqlx::migrate!("./migrations")
         .run(&pool)
         .await
         .expect("Failed to run database migrations");
     
+    // One-off admin commands, e.g. `cargo run -- create-admin <username> <email>`
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("create-admin") {
+        return bootstrap::create_admin(&pool, &password_hasher, &password_policy, &args[2..])
+            .await
+            .map_err(|e| std::io::Error::other(e.to_string()));
+    }
+    if args.get(1).map(String::as_str) == Some("rotate-signing-key") {
+        return keys::rotate_from_cli(&pool, &args[2..])
+            .await
+            .map_err(|e| std::io::Error::other(e.to_string()));
+    }
+    
+    // JWT signing keys, shared by every replica through the database
+    let key_store = KeyStore::load(&pool, &config)
+        .await
+        .expect("Failed to load JWT signing keys");
+    keys::spawn_refresher(pool.clone(), key_store.clone());
+    
+    // Social login through OpenID Connect / OAuth2 providers
+    let oidc_client = OidcClient::new();
+    
+    // Pick up token revocations made by other replicas
+    revocation::spawn_listener(pool.clone(), cache_manager.clone());
+    
+    // Lift expired suspensions and delete accounts whose grace period is over
+    accounts::spawn_maintenance(pool.clone(), cache_manager.clone(), config.account_maintenance_interval);
+    
+    // Take published job postings down once they expire
+    jobs::spawn_expiry(pool.clone(), config.job_expiry_interval);
+    
     let host = config.host.clone();
     let configured_port = config.port;
     
@@ -84,6 +152,12 @@ async fn main() -> std::io::Result<()> {
             .app_data(web::Data::new(pool.clone()))
             .app_data(web::Data::new(config.clone()))
             .app_data(web::Data::new(cache_manager.clone()))
+            .app_data(web::Data::new(mailer.clone()))
+            .app_data(web::Data::new(key_store.clone()))
+            .app_data(web::Data::new(oidc_client.clone()))
+            .app_data(web::Data::new(password_hasher.clone()))
+            .app_data(web::Data::new(password_policy.clone()))
+            .app_data(web::Data::from(txt_resolver.clone()))
             .wrap(cors)
             .wrap(Logger::default())
             .wrap(Compress::default()) // Enable compression for all responses
@@ -98,9 +172,14 @@ async fn main() -> std::io::Result<()> {
                         web::scope("")
                             .wrap(middleware::AuthMiddleware)
                             .service(handlers::users::user_routes())
+                            .service(handlers::companies::company_routes())
+                            .service(handlers::jobs::job_routes())
+                            .service(handlers::applications::application_routes())
+                            .service(handlers::admin::admin_routes())
                     )
                     .service(handlers::health::health_routes())
             )
+            .route("/.well-known/jwks.json", web::get().to(handlers::keys::jwks))
             .service(
                 web::scope("/")
                     .route("/", web::get().to(handlers::health::index))
@@ -7,7 +7,12 @@ use std::{
     future::{ready, Ready},
     rc::Rc,
 };
-use crate::{utils::jwt::decode_jwt, config::Config, cache::CacheManager};
+use sqlx::PgPool;
+use crate::client_ip::client_ip;
+use crate::{utils::jwt::decode_jwt, config::Config, cache::CacheManager, database, keys::KeyStore, revocation};
+use crate::impersonation::{self, Impersonation, ImpersonationEventType, RequestDetails};
+use crate::api_keys;
+use crate::permissions;
 
 pub struct AuthMiddleware;
 
@@ -78,21 +83,151 @@ where
                 }
             };
 
-            // Check if token is blacklisted
             let cache = req.app_data::<web::Data<CacheManager>>().unwrap();
-            if cache.is_token_blacklisted(&token).await {
-                return Err(actix_web::error::ErrorUnauthorized("Token has been invalidated"));
-            }
+            let pool = req.app_data::<web::Data<PgPool>>().unwrap();
 
-            // Get config from app data
+            // Get config and signing keys from app data
             let config = req.app_data::<web::Data<Config>>().unwrap();
+            let keys = req.app_data::<web::Data<KeyStore>>().unwrap();
+
+            // Integrations authenticate with API keys, limited to the endpoints their scopes allow
+            if api_keys::is_api_key(&token) {
+                let ip_address = client_ip(req.request());
+                let key = api_keys::authenticate(pool, &token, ip_address.as_deref())
+                    .await
+                    .map_err(|_| actix_web::error::ErrorServiceUnavailable("Database error"))?
+                    .ok_or_else(|| actix_web::error::ErrorUnauthorized("Invalid or expired API key"))?;
+
+                let Some(scope) = api_keys::required_scope(req.method(), req.path()) else {
+                    return Err(actix_web::error::ErrorForbidden("This endpoint cannot be called with an API key"));
+                };
+                if !key.has_scope(scope) {
+                    return Err(actix_web::error::ErrorForbidden(format!("API key is missing the {scope} scope")));
+                }
+                if key.company_id.is_some() && api_keys::is_applicant_route(req.method(), req.path()) {
+                    return Err(actix_web::error::ErrorForbidden("Company API keys cannot act as a job seeker"));
+                }
+
+                let role_permissions = permissions::load(pool, cache, &key.role)
+                    .await
+                    .map_err(|_| actix_web::error::ErrorServiceUnavailable("Database error"))?
+                    .ok_or_else(|| actix_web::error::ErrorUnauthorized("Unknown role"))?;
+
+                req.extensions_mut().insert(key.claims(config, role_permissions.version));
+                req.extensions_mut().insert(role_permissions);
+                req.extensions_mut().insert(api_keys::ApiKeyAuth { company_id: key.company_id });
+                return service.call(req).await;
+            }
 
             // Decode and validate JWT token
-            match decode_jwt(&token, &config.jwt_secret) {
+            match decode_jwt(&token, keys, config) {
                 Ok(claims) => {
-                    // Add claims to request extensions for use in handlers
+                    // Check if this specific token has been revoked (e.g. logged out)
+                    let jti: uuid::Uuid = claims.jti.parse()
+                        .map_err(|_| actix_web::error::ErrorUnauthorized("Invalid token id"))?;
+                    let revoked = revocation::is_token_revoked(pool, cache, jti)
+                        .await
+                        .map_err(|_| actix_web::error::ErrorServiceUnavailable("Database error"))?;
+                    if revoked {
+                        return Err(actix_web::error::ErrorUnauthorized("Token has been invalidated"));
+                    }
+
+                    // Reject tokens issued before the user's last revocation (e.g. password reset)
+                    let user_id: i32 = claims.sub.parse()
+                        .map_err(|_| actix_web::error::ErrorUnauthorized("Invalid token subject"))?;
+                    let cutoff = match cache.get_token_cutoff(user_id).await {
+                        Some(cutoff) => cutoff,
+                        None => {
+                            let cutoff = database::get_tokens_invalid_before(pool, user_id)
+                                .await
+                                .map_err(|_| actix_web::error::ErrorServiceUnavailable("Database error"))?;
+                            cache.set_token_cutoff(user_id, cutoff).await;
+                            cutoff
+                        }
+                    };
+                    if cutoff.is_some_and(|cutoff| (claims.iat as i64) < cutoff) {
+                        return Err(actix_web::error::ErrorUnauthorized("Token has been revoked"));
+                    }
+
+                    // Reject tokens whose session was logged out or revoked
+                    let session_id: uuid::Uuid = claims.sid.parse()
+                        .map_err(|_| actix_web::error::ErrorUnauthorized("Invalid token session"))?;
+
+                    // Impersonation tokens belong to an impersonation session instead of a login session
+                    let impersonation = match &claims.act {
+                        Some(actor) => Some(Impersonation {
+                            session_id,
+                            admin_id: actor.sub.parse()
+                                .map_err(|_| actix_web::error::ErrorUnauthorized("Invalid token actor"))?,
+                            user_id,
+                        }),
+                        None => None,
+                    };
+
+                    let session_active = match cache.get_session_active(session_id).await {
+                        Some(active) => active,
+                        None => {
+                            let active = match impersonation {
+                                Some(_) => database::touch_impersonation(pool, session_id).await,
+                                None => database::touch_session(pool, session_id).await,
+                            }
+                            .map_err(|_| actix_web::error::ErrorServiceUnavailable("Database error"))?;
+                            cache.set_session_active(session_id, active).await;
+                            active
+                        }
+                    };
+                    if !session_active {
+                        return Err(actix_web::error::ErrorUnauthorized("Session has been revoked"));
+                    }
+
+                    // Reject tokens issued before the role's permissions last changed; refreshing fixes them
+                    let role_permissions = permissions::load(pool, cache, &claims.role)
+                        .await
+                        .map_err(|_| actix_web::error::ErrorServiceUnavailable("Database error"))?
+                        .ok_or_else(|| actix_web::error::ErrorUnauthorized("Unknown role"))?;
+                    if role_permissions.version != claims.pv {
+                        return Err(actix_web::error::ErrorUnauthorized("Permissions have changed; refresh your access token"));
+                    }
+
+                    let Some(impersonation) = impersonation else {
+                        // Add claims to request extensions for use in handlers
+                        req.extensions_mut().insert(claims);
+                        req.extensions_mut().insert(role_permissions);
+                        return service.call(req).await;
+                    };
+
+                    // Every request made while impersonating is audited, including refused ones
+                    let pool = pool.clone();
+                    let method = req.method().clone();
+                    let path = req.path().to_string();
+                    let ip_address = client_ip(req.request());
+
+                    if impersonation::is_blocked(&method, &path) {
+                        log::warn!(
+                            "Admin {} was refused {method} {path} while impersonating user {}",
+                            impersonation.admin_id, impersonation.user_id
+                        );
+                        let request = RequestDetails { method: method.as_str(), path: &path, status_code: Some(403) };
+                        impersonation::record(
+                            &pool, session_id, ImpersonationEventType::Blocked, Some(request), ip_address.as_deref(), None,
+                        ).await;
+                        return Err(actix_web::error::ErrorForbidden("This action is not available while impersonating a user"));
+                    }
+
                     req.extensions_mut().insert(claims);
-                    service.call(req).await
+                    req.extensions_mut().insert(role_permissions);
+                    req.extensions_mut().insert(impersonation);
+                    let response = service.call(req).await;
+
+                    let request = RequestDetails {
+                        method: method.as_str(),
+                        path: &path,
+                        status_code: response.as_ref().ok().map(|response| response.status().as_u16()),
+                    };
+                    impersonation::record(
+                        &pool, session_id, ImpersonationEventType::Request, Some(request), ip_address.as_deref(), None,
+                    ).await;
+                    response
                 }
                 Err(_) => {
                     Err(actix_web::error::ErrorUnauthorized("Invalid or expired token"))
@@ -107,10 +242,19 @@ fn should_skip_auth(path: &str) -> bool {
         "/",
         "/api/v1/health",
         "/api/v1/auth/login",
+        "/api/v1/auth/login/2fa",
+        "/api/v1/auth/reactivate",
         "/api/v1/auth/register",
+        "/api/v1/auth/refresh",
         "/api/v1/auth/forgot-password",
+        "/api/v1/auth/magic-link",
+        "/api/v1/auth/magic-link/login",
         "/api/v1/auth/reset-password",
+        "/api/v1/auth/verify-email",
+        "/api/v1/auth/verify-company",
+        "/api/v1/auth/accept-invite",
     ];
     
-    public_routes.contains(&path)
+    // Social login: /api/v1/auth/oauth/providers and /api/v1/auth/oauth/{provider}/...
+    public_routes.contains(&path) || path.starts_with("/api/v1/auth/oauth/")
 } 
\ No newline at end of file
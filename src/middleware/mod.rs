@@ -1,3 +1,5 @@
 pub mod auth;
+pub mod permission;
 
-pub use auth::*; 
\ No newline at end of file
+pub use auth::*;
+pub use permission::*;
@@ -0,0 +1,103 @@
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpMessage, HttpRequest, HttpResponse,
+};
+use futures_util::future::LocalBoxFuture;
+use std::{
+    future::{ready, Ready},
+    rc::Rc,
+};
+use crate::models::ApiResponse;
+use crate::permissions::{Permission, RolePermissions};
+
+/// Restrict a scope or resource to users whose role grants a permission.
+/// Must be nested inside `AuthMiddleware`, which provides the role's permissions.
+///
+/// ```ignore
+/// web::resource("/security-events").wrap(RequirePermission::new(Permission::AuditRead))
+/// ```
+pub struct RequirePermission {
+    permission: Permission,
+}
+
+impl RequirePermission {
+    pub fn new(permission: Permission) -> Self {
+        Self { permission }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequirePermission
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RequirePermissionService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequirePermissionService {
+            service: Rc::new(service),
+            permission: self.permission,
+        }))
+    }
+}
+
+pub struct RequirePermissionService<S> {
+    service: Rc<S>,
+    permission: Permission,
+}
+
+impl<S, B> Service<ServiceRequest> for RequirePermissionService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let response = refusal(req.extensions().get::<RolePermissions>(), self.permission);
+
+        let Some(response) = response else {
+            let service = self.service.clone();
+            return Box::pin(async move {
+                service.call(req).await.map(ServiceResponse::map_into_left_body)
+            });
+        };
+
+        let (req, _) = req.into_parts();
+        Box::pin(ready(Ok(ServiceResponse::new(req, response).map_into_right_body())))
+    }
+}
+
+/// The same check as `RequirePermission`, for handlers sharing a path with
+/// methods that need a different permission (or none)
+pub fn require_permission(req: &HttpRequest, permission: Permission) -> Result<(), HttpResponse> {
+    match refusal(req.extensions().get::<RolePermissions>(), permission) {
+        Some(response) => Err(response),
+        None => Ok(()),
+    }
+}
+
+/// The response refusing a caller, or None when their role grants `permission`
+fn refusal(permissions: Option<&RolePermissions>, permission: Permission) -> Option<HttpResponse> {
+    match permissions.map(|permissions| permissions.check(permission)) {
+        Some(decision) if decision.allowed => None,
+        Some(decision) => Some(HttpResponse::Forbidden().json(ApiResponse::<()>::error(&format!(
+            "You do not have permission to perform this action: {}",
+            decision.reason
+        )))),
+        None => Some(HttpResponse::Unauthorized().json(ApiResponse::<()>::error(
+            "Authentication required"
+        ))),
+    }
+}
@@ -0,0 +1,146 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct AdminInvite {
+    pub id: i32,
+    pub email: String,
+    pub invited_by: Option<i32>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAdminInviteRequest {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AcceptAdminInviteRequest {
+    pub token: String,
+    pub username: String,
+    pub password: String,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct SecurityEvent {
+    pub id: i32,
+    pub event_type: String,
+    pub user_id: Option<i32>,
+    pub actor_id: Option<i32>,
+    pub ip_address: Option<String>,
+    pub details: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SecurityEventQuery {
+    pub user_id: Option<i32>,
+    pub event_type: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SigningKeyRotationResponse {
+    pub kid: String,
+    pub activates_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SuspendUserRequest {
+    pub reason: String,
+    pub until: Option<DateTime<Utc>>, // suspended until lifted when absent
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StartImpersonationRequest {
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImpersonationResponse {
+    pub impersonation_id: Uuid,
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    pub expires_at: DateTime<Utc>,
+    pub user: crate::models::UserPublic,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ImpersonationSession {
+    pub id: Uuid,
+    pub admin_id: i32,
+    pub user_id: i32,
+    pub reason: String,
+    pub ip_address: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub last_seen_at: Option<DateTime<Utc>>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub ended_by: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImpersonationQuery {
+    pub admin_id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub active: Option<bool>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct ImpersonationEvent {
+    pub id: i64,
+    pub event_type: String,
+    pub method: Option<String>,
+    pub path: Option<String>,
+    pub status_code: Option<i16>,
+    pub ip_address: Option<String>,
+    pub details: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct RoleInfo {
+    pub name: String,
+    pub description: Option<String>,
+    pub self_assignable: bool,
+    pub builtin: bool,
+    pub permissions_version: i32,
+    pub permissions: Vec<String>,
+    pub user_count: i64,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct PermissionInfo {
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRoleRequest {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub self_assignable: bool,
+    #[serde(default)]
+    pub permissions: Vec<String>,
+}
+
+/// Replaces the permissions of a role; tokens issued under the old set stop working
+#[derive(Debug, Deserialize)]
+pub struct UpdateRolePermissionsRequest {
+    pub permissions: Vec<String>,
+}
+
+/// The caller's role and what it grants
+#[derive(Debug, Serialize)]
+pub struct UserPermissions {
+    pub role: String,
+    pub permissions: Vec<String>,
+}
@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, Utc};
+
+/// An application with the posting and applicant it links
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Application {
+    pub id: i32,
+    pub job_id: i32,
+    pub job_title: String,
+    pub company_id: i32,
+    pub applicant_id: i32,
+    pub applicant_username: String,
+    pub applicant_email: String,
+    pub applicant_first_name: Option<String>,
+    pub applicant_last_name: Option<String>,
+    pub cover_letter: Option<String>,
+    pub resume_url: String,
+    pub stage_id: i32,
+    pub stage_name: String,
+    pub status: String, // active, hired, rejected or withdrawn
+    pub status_changed_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateApplicationRequest {
+    pub cover_letter: Option<String>,
+    pub resume_url: String,
+}
+
+/// Filters for listing applications
+#[derive(Debug, Deserialize)]
+pub struct ApplicationListQuery {
+    pub job_id: Option<i32>, // only for applications received
+    pub stage_id: Option<i32>,
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
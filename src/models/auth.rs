@@ -2,6 +2,7 @@ use serde::{Deserialize, Serialize};
 use validator::Validate;
 use chrono::{DateTime, Utc};
 use sqlx::FromRow;
+use uuid::Uuid;
 
 #[derive(Debug, Deserialize, Validate)]
 pub struct LoginRequest {
@@ -10,6 +11,8 @@ pub struct LoginRequest {
     
     #[validate(length(min = 1, message = "Password is required"))]
     pub password: String,
+
+    pub device_label: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -17,6 +20,7 @@ pub struct LoginResponse {
     pub access_token: String,
     pub token_type: String,
     pub expires_in: i64,
+    pub refresh_token: String,
     pub user: crate::models::UserPublic,
 }
 
@@ -26,13 +30,27 @@ pub struct Claims {
     pub username: String,
     pub email: String,
     pub role: String,
+    pub sid: String,     // Session (refresh token family) id
+    pub jti: String,     // Unique token id, used for revocation
+    pub iss: String,     // Issuer
+    pub aud: String,     // Audience
     pub exp: usize,      // Expiration time
     pub iat: usize,      // Issued at
+    #[serde(default)]
+    pub pv: i32,         // Permissions version of the role when issued
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<Actor>, // Admin acting as the subject, on impersonation tokens only
+}
+
+/// The party actually making requests with a token (RFC 8693 `act` claim)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Actor {
+    pub sub: String,
+    pub username: String,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct RefreshTokenRequest {
-    #[allow(dead_code)]
     pub refresh_token: String,
 }
 
@@ -41,18 +59,47 @@ pub struct RefreshTokenResponse {
     pub access_token: String,
     pub token_type: String,
     pub expires_in: i64,
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccessTokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
 }
 
 #[derive(Debug, FromRow)]
-#[allow(dead_code)]
 pub struct UserSession {
     pub id: i32,
     pub user_id: i32,
-    pub session_token: String,
+    #[allow(dead_code)]
+    pub session_token: String, // SHA-256 of the refresh token
+    pub family_id: Uuid,
     pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub replaced_by: Option<i32>,
+    #[allow(dead_code)]
     pub created_at: DateTime<Utc>,
 }
 
+/// One signed-in device, as shown to the user
+#[derive(Debug, Serialize, FromRow)]
+pub struct SessionInfo {
+    pub id: Uuid,
+    pub device_label: Option<String>,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub signed_in_at: DateTime<Utc>,
+    pub last_seen_at: Option<DateTime<Utc>>,
+    pub current: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateSessionRequest {
+    pub device_label: String,
+}
+
 #[derive(Debug, Deserialize, Validate)]
 pub struct ForgotPasswordRequest {
     #[validate(email(message = "Invalid email format"))]
@@ -60,14 +107,28 @@ pub struct ForgotPasswordRequest {
 }
 
 #[derive(Debug, Deserialize, Validate)]
+pub struct MagicLinkRequest {
+    #[validate(email(message = "Invalid email format"))]
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MagicLinkLoginRequest {
+    pub token: String,
+    pub device_label: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
 pub struct ResetPasswordRequest {
-    #[allow(dead_code)]
     pub token: String,
-    
-    #[validate(length(min = 8, message = "Password must be at least 8 characters"))]
     pub new_password: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
 #[derive(Debug, Serialize)]
 pub struct ApiResponse<T> {
     pub success: bool,
@@ -99,4 +160,35 @@ impl<T> ApiResponse<T> {
             data: None,
         }
     }
+
+    pub fn error_with_data(message: &str, data: T) -> Self {
+        Self {
+            success: false,
+            message: message.to_string(),
+            data: Some(data),
+        }
+    }
+}
+
+/// One rule an input field broke, e.g. `{"field": "password", "code": "too_short", ...}`
+#[derive(Debug, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub code: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, code: &str, message: &str) -> Self {
+        Self {
+            field: field.to_string(),
+            code: code.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct FieldErrors {
+    pub errors: Vec<FieldError>,
 } 
\ No newline at end of file
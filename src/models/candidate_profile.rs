@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{postgres::PgRow, FromRow, Row};
+use chrono::{DateTime, NaiveDate, Utc};
+
+/// An entry of one section of a candidate profile, with the candidate's fields flattened in
+#[derive(Debug, Serialize)]
+pub struct ProfileEntry<T> {
+    pub id: i32,
+    #[serde(flatten)]
+    pub fields: T,
+    pub position: i32, // 1-based order within the section, as arranged by the candidate
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl<'r, T: FromRow<'r, PgRow>> FromRow<'r, PgRow> for ProfileEntry<T> {
+    fn from_row(row: &'r PgRow) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            id: row.try_get("id")?,
+            fields: T::from_row(row)?,
+            position: row.try_get("position")?,
+            created_at: row.try_get("created_at")?,
+            updated_at: row.try_get("updated_at")?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Experience {
+    pub title: String,
+    pub company: String,
+    pub location: Option<String>,
+    pub employment_type: Option<String>, // one of the job posting employment types
+    pub start_date: NaiveDate,
+    pub end_date: Option<NaiveDate>, // None while it is the current position
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Education {
+    pub institution: String,
+    pub degree: Option<String>,
+    pub field_of_study: Option<String>,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+    pub grade: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Certification {
+    pub name: String,
+    pub issuer: Option<String>,
+    pub issued_on: Option<NaiveDate>,
+    pub expires_on: Option<NaiveDate>,
+    pub credential_id: Option<String>,
+    pub credential_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SpokenLanguage {
+    pub language: String,
+    pub proficiency: String, // elementary, limited_working, professional_working, full_professional or native
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CandidateSkill {
+    pub name: String, // stored lowercase
+    pub level: String, // beginner, intermediate, advanced or expert
+    pub years_of_experience: Option<i16>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ProfileLink {
+    pub kind: String, // portfolio, github, linkedin, website or other
+    pub url: String,
+    pub label: Option<String>,
+}
+
+/// The new order of a section: the ids of all its entries, first to last
+#[derive(Debug, Deserialize)]
+pub struct ReorderEntriesRequest {
+    pub ids: Vec<i32>,
+}
+
+/// A candidate's whole profile in one document, as employers see it
+#[derive(Debug, Serialize)]
+pub struct CandidateProfile {
+    pub user_id: i32,
+    pub username: String,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub professional_role: Option<String>,
+    pub experience: Vec<ProfileEntry<Experience>>,
+    pub education: Vec<ProfileEntry<Education>>,
+    pub certifications: Vec<ProfileEntry<Certification>>,
+    pub languages: Vec<ProfileEntry<SpokenLanguage>>,
+    pub skills: Vec<ProfileEntry<CandidateSkill>>,
+    pub links: Vec<ProfileEntry<ProfileLink>>,
+}
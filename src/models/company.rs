@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Company {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub logo_url: Option<String>,
+    pub website: Option<String>,
+    pub industry: Option<String>,
+    pub size: Option<String>, // employee count band, e.g. 11-50
+    pub verified: bool, // has proven it owns verified_domain
+    pub verified_domain: Option<String>,
+    pub verified_at: Option<DateTime<Utc>>,
+    pub member_count: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A company the caller belongs to, with their role there
+#[derive(Debug, Serialize, FromRow)]
+pub struct MyCompany {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub company: Company,
+    pub role: String, // owner, recruiter or viewer
+}
+
+/// Every editable field of a company profile
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateCompanyRequest {
+    pub name: String,
+    pub description: Option<String>,
+    pub logo_url: Option<String>,
+    pub website: Option<String>,
+    pub industry: Option<String>,
+    pub size: Option<String>,
+}
+
+/// Fields left out are kept as they are
+#[derive(Debug, Deserialize)]
+pub struct UpdateCompanyRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub logo_url: Option<String>,
+    pub website: Option<String>,
+    pub industry: Option<String>,
+    pub size: Option<String>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct CompanyMember {
+    pub user_id: i32,
+    pub username: String,
+    pub email: String,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub role: String,
+    pub created_at: DateTime<Utc>, // when they joined
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateCompanyInviteRequest {
+    pub email: String,
+    pub role: String,
+}
+
+/// A pending invite to join a company, as shown to its owners
+#[derive(Debug, Serialize, FromRow)]
+pub struct CompanyInvite {
+    pub id: i32,
+    pub email: String,
+    pub role: String,
+    pub invited_by: Option<i32>,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AcceptCompanyInviteRequest {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateCompanyMemberRequest {
+    pub role: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CompanyEmailVerificationRequest {
+    pub email: String, // admin@, hostmaster@, postmaster@ or webmaster@ the website's domain
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConfirmCompanyVerificationRequest {
+    pub token: String,
+}
+
+/// The TXT record to publish on the company's domain
+#[derive(Debug, Serialize)]
+pub struct DnsVerificationChallenge {
+    pub record_name: String,
+    pub record_type: &'static str, // always TXT
+    pub record_value: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PendingEmailVerification {
+    pub email: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Where a company stands on proving it owns its website's domain
+#[derive(Debug, Serialize)]
+pub struct CompanyVerificationStatus {
+    pub domain: Option<String>, // taken from the website, None without one
+    pub verified: bool,
+    pub verified_domain: Option<String>,
+    pub verified_at: Option<DateTime<Utc>>,
+    pub pending_email: Option<PendingEmailVerification>,
+    pub pending_dns: Option<DnsVerificationChallenge>,
+}
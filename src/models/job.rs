@@ -0,0 +1,131 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, Utc};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Job {
+    pub id: i32,
+    pub company_id: i32,
+    pub company_name: String,
+    pub company_verified: bool, // the company has proven it owns its website's domain
+    pub employer_id: Option<i32>, // the member who posted it, None once their account is deleted
+    pub title: String,
+    pub description: String,
+    pub employment_type: String,
+    pub seniority: String,
+    pub location: Option<String>,
+    pub remote_policy: String,
+    pub salary_min: Option<i32>,
+    pub salary_max: Option<i32>,
+    pub salary_currency: Option<String>,
+    pub skills: Vec<String>,
+    pub status: String, // draft, published, closed or expired
+    pub published_at: Option<DateTime<Utc>>,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Every editable field of a posting. New postings start as drafts.
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateJobRequest {
+    pub company_id: Option<i32>, // may be left out by members of a single company; fixed once created
+    pub title: String,
+    pub description: String,
+    pub employment_type: String,
+    pub seniority: String,
+    pub location: Option<String>,
+    pub remote_policy: String,
+    pub salary_min: Option<i32>,
+    pub salary_max: Option<i32>,
+    pub salary_currency: Option<String>,
+    #[serde(default)]
+    pub skills: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Fields left out are kept as they are
+#[derive(Debug, Deserialize)]
+pub struct UpdateJobRequest {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub employment_type: Option<String>,
+    pub seniority: Option<String>,
+    pub location: Option<String>,
+    pub remote_policy: Option<String>,
+    pub salary_min: Option<i32>,
+    pub salary_max: Option<i32>,
+    pub salary_currency: Option<String>,
+    pub skills: Option<Vec<String>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateJobStatusRequest {
+    pub status: String,
+}
+
+/// Filters for browsing published postings
+#[derive(Debug, Deserialize)]
+pub struct JobSearchQuery {
+    pub q: Option<String>, // matched against the title and description
+    pub employment_type: Option<String>,
+    pub seniority: Option<String>,
+    pub remote_policy: Option<String>,
+    pub location: Option<String>,
+    pub skill: Option<String>,
+    pub min_salary: Option<i32>, // postings whose range reaches at least this much
+    pub company_id: Option<i32>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// Filters for the postings of the caller's companies
+#[derive(Debug, Deserialize)]
+pub struct MyJobsQuery {
+    pub company_id: Option<i32>,
+    pub status: Option<String>,
+}
+
+/// Query string of `GET /jobs/search`. List filters are comma-separated and match any of their values.
+#[derive(Debug, Deserialize)]
+pub struct JobSearchParams {
+    pub q: Option<String>,
+    pub location: Option<String>,
+    pub remote_policy: Option<String>,
+    pub employment_type: Option<String>,
+    pub salary_min: Option<i32>,
+    pub salary_max: Option<i32>,
+    pub salary_currency: Option<String>,
+    pub posted_since: Option<String>, // RFC 3339 timestamp, or a number of days such as `7d`
+    pub skills: Option<String>, // postings must list all of them
+    pub sort: Option<String>, // relevance or recency
+    pub page: Option<i64>,
+    pub per_page: Option<i64>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct JobSearchHit {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub job: Job,
+    pub relevance: f32, // 0 without a text query
+}
+
+#[derive(Debug, Serialize)]
+pub struct FacetCount {
+    pub value: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JobSearchResults {
+    pub jobs: Vec<JobSearchHit>,
+    pub total: i64,
+    pub page: i64,
+    pub per_page: i64,
+    /// Per filter, how many postings each value would match given the other filters
+    pub facets: BTreeMap<String, Vec<FacetCount>>,
+}
@@ -1,5 +1,23 @@
 pub mod user;
 pub mod auth;
+pub mod admin;
+pub mod two_factor;
+pub mod oauth;
+pub mod api_key;
+pub mod company;
+pub mod job;
+pub mod application;
+pub mod pipeline;
+pub mod candidate_profile;
 
 pub use user::*;
-pub use auth::*; 
\ No newline at end of file
+pub use auth::*;
+pub use admin::*;
+pub use two_factor::*;
+pub use oauth::*;
+pub use api_key::*;
+pub use company::*;
+pub use job::*;
+pub use application::*;
+pub use pipeline::*;
+pub use candidate_profile::*;
@@ -0,0 +1,29 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Deserialize)]
+pub struct OAuthAuthorizeRequest {
+    pub device_label: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OAuthAuthorizeResponse {
+    pub authorization_url: String,
+    pub state: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OAuthCallbackRequest {
+    pub code: String,
+    pub state: String,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct LinkedIdentity {
+    pub id: i32,
+    pub provider: String,
+    pub email: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_login_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct PipelineStage {
+    pub id: i32,
+    pub job_id: i32,
+    pub name: String,
+    pub kind: String, // active, hired or rejected
+    pub position: i32,
+    pub application_count: i64, // applications currently in the stage, withdrawn ones excluded
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateStageRequest {
+    pub name: String,
+    pub kind: Option<String>, // active unless given
+    pub position: Option<i32>, // 1-based; after the last active stage unless given
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateStageRequest {
+    pub name: Option<String>,
+    pub position: Option<i32>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MoveApplicationRequest {
+    pub stage_id: i32,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkMoveApplicationsRequest {
+    pub application_ids: Vec<i32>,
+    pub stage_id: i32,
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BulkMoveResponse {
+    pub moved: u64,
+    pub unchanged: u64, // already in the stage
+}
+
+/// One move of an application between stages, as recorded at the time
+#[derive(Debug, Serialize, FromRow)]
+pub struct StageHistoryEntry {
+    pub id: i64,
+    pub application_id: i32,
+    pub from_stage_id: Option<i32>,
+    pub from_stage_name: Option<String>,
+    pub to_stage_id: i32,
+    pub to_stage_name: String,
+    pub moved_by: Option<i32>,
+    pub moved_by_username: Option<String>,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
@@ -63,12 +63,21 @@ pub struct UpdateUserRequest {
 
 #[derive(Debug, Deserialize)]
 pub struct ChangePasswordRequest {
-    #[allow(dead_code)]
     pub current_password: String,
-    #[allow(dead_code)]
     pub new_password: String,
 }
 
+/// Password confirmation for deactivating or deleting one's own account
+#[derive(Debug, Deserialize)]
+pub struct ConfirmPasswordRequest {
+    pub password: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccountDeletionResponse {
+    pub deletion_scheduled_at: DateTime<Utc>,
+}
+
 impl From<User> for UserPublic {
     fn from(user: User) -> Self {
         UserPublic {
@@ -88,8 +97,11 @@ impl From<User> for UserPublic {
     }
 }
 
-// Validation function for user roles
-#[allow(dead_code)]
-pub fn is_valid_role(role: &str) -> bool {
-    matches!(role, "job_seeker" | "employer" | "admin")
+/// Role given to new accounts that do not ask for one. Roles themselves live in
+/// the `roles` table, see `permissions.rs`.
+pub const DEFAULT_ROLE: &str = "job_seeker";
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRoleRequest {
+    pub role: String,
 } 
\ No newline at end of file
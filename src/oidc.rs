@@ -0,0 +1,616 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use data_encoding::BASE64URL_NOPAD;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use moka::future::Cache;
+use rand::RngCore;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::config::OidcProviderConfig;
+
+/// Endpoints of a provider, from discovery and/or configuration
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProviderMetadata {
+    pub issuer: Option<String>,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: Option<String>,
+    pub jwks_uri: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    id_token: Option<String>,
+}
+
+/// The user as described by the identity provider
+#[derive(Debug, Clone)]
+pub struct ExternalIdentity {
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub preferred_username: Option<String>,
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
+}
+
+impl ExternalIdentity {
+    /// Read the standard OIDC claims, falling back to the field names plain OAuth2 APIs use (GitHub)
+    fn from_claims(claims: &Value, trust_email: bool) -> anyhow::Result<Self> {
+        let string = |key: &str| claims.get(key).and_then(Value::as_str).map(str::to_string);
+
+        let subject = match claims.get("sub").or_else(|| claims.get("id")) {
+            Some(Value::String(subject)) => subject.clone(),
+            Some(Value::Number(subject)) => subject.to_string(),
+            _ => anyhow::bail!("Identity has no subject"),
+        };
+
+        // Some providers send email_verified as a string
+        let email_verified = match claims.get("email_verified") {
+            Some(Value::Bool(verified)) => *verified,
+            Some(Value::String(verified)) => verified == "true",
+            _ => trust_email,
+        };
+
+        let (given_name, family_name) = match (string("given_name"), string("family_name"), string("name")) {
+            (None, None, Some(name)) => match name.split_once(' ') {
+                Some((given, family)) => (Some(given.to_string()), Some(family.to_string())),
+                None => (Some(name), None),
+            },
+            (given_name, family_name, _) => (given_name, family_name),
+        };
+
+        Ok(Self {
+            subject,
+            email: string("email").filter(|email| email.contains('@')),
+            email_verified,
+            preferred_username: string("preferred_username").or_else(|| string("login")),
+            given_name,
+            family_name,
+        })
+    }
+}
+
+/// Values that tie an authorization response back to the request that started it
+pub struct AuthorizationRequest {
+    pub url: String,
+    pub state: String,
+    pub nonce: String,
+    pub code_verifier: String,
+}
+
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    BASE64URL_NOPAD.encode(&bytes)
+}
+
+/// Generic OpenID Connect client with discovery, PKCE, state and nonce.
+/// Provider metadata and signing keys are cached for an hour.
+#[derive(Clone)]
+pub struct OidcClient {
+    http: Client,
+    metadata: Cache<String, Arc<ProviderMetadata>>,
+    jwks: Cache<String, Arc<JwkSet>>,
+}
+
+impl OidcClient {
+    pub fn new() -> Self {
+        Self::with_http_client(
+            Client::builder()
+                .timeout(Duration::from_secs(10))
+                .build()
+                .expect("Failed to build HTTP client"),
+        )
+    }
+
+    /// A client that talks to providers through `http`, e.g. one set up with a proxy
+    pub fn with_http_client(http: Client) -> Self {
+        Self {
+            http,
+            metadata: Cache::builder()
+                .max_capacity(100)
+                .time_to_live(Duration::from_secs(3600))
+                .build(),
+            jwks: Cache::builder()
+                .max_capacity(100)
+                .time_to_live(Duration::from_secs(3600))
+                .build(),
+        }
+    }
+
+    async fn metadata(&self, provider: &OidcProviderConfig) -> anyhow::Result<Arc<ProviderMetadata>> {
+        if let Some(metadata) = self.metadata.get(&provider.name).await {
+            return Ok(metadata);
+        }
+
+        let mut metadata = match &provider.issuer {
+            Some(issuer) => {
+                let discovery_url = format!("{}/.well-known/openid-configuration", issuer.trim_end_matches('/'));
+                let discovered: ProviderMetadata = self.http.get(&discovery_url)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await
+                    .with_context(|| format!("Invalid discovery document at {discovery_url}"))?;
+
+                // The discovery document must describe the issuer we asked about
+                let discovered_issuer = discovered.issuer.as_deref().unwrap_or_default();
+                if discovered_issuer.trim_end_matches('/') != issuer.trim_end_matches('/') {
+                    anyhow::bail!("Discovery issuer {discovered_issuer} does not match {issuer}");
+                }
+                discovered
+            }
+            None => ProviderMetadata {
+                issuer: None,
+                authorization_endpoint: provider.authorization_endpoint.clone()
+                    .context("Provider has neither an issuer nor an authorization endpoint")?,
+                token_endpoint: provider.token_endpoint.clone()
+                    .context("Provider has neither an issuer nor a token endpoint")?,
+                userinfo_endpoint: None,
+                jwks_uri: None,
+            },
+        };
+
+        // Explicit endpoints win over discovered ones
+        if let Some(endpoint) = &provider.authorization_endpoint {
+            metadata.authorization_endpoint = endpoint.clone();
+        }
+        if let Some(endpoint) = &provider.token_endpoint {
+            metadata.token_endpoint = endpoint.clone();
+        }
+        if provider.userinfo_endpoint.is_some() {
+            metadata.userinfo_endpoint = provider.userinfo_endpoint.clone();
+        }
+
+        let metadata = Arc::new(metadata);
+        self.metadata.insert(provider.name.clone(), metadata.clone()).await;
+        Ok(metadata)
+    }
+
+    /// Build the URL to send the user to, with fresh state, nonce and PKCE verifier
+    pub async fn authorization_request(
+        &self,
+        provider: &OidcProviderConfig,
+        redirect_uri: &str,
+    ) -> anyhow::Result<AuthorizationRequest> {
+        let metadata = self.metadata(provider).await?;
+
+        let state = random_token();
+        let nonce = random_token();
+        let code_verifier = random_token();
+        let code_challenge = BASE64URL_NOPAD.encode(&Sha256::digest(code_verifier.as_bytes()));
+
+        let mut url = url::Url::parse(&metadata.authorization_endpoint)?;
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &provider.client_id)
+            .append_pair("redirect_uri", redirect_uri)
+            .append_pair("scope", &provider.scopes)
+            .append_pair("state", &state)
+            .append_pair("nonce", &nonce)
+            .append_pair("code_challenge", &code_challenge)
+            .append_pair("code_challenge_method", "S256");
+
+        Ok(AuthorizationRequest { url: url.into(), state, nonce, code_verifier })
+    }
+
+    /// Exchange the authorization code and return the verified identity
+    pub async fn complete(
+        &self,
+        provider: &OidcProviderConfig,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+        redirect_uri: &str,
+    ) -> anyhow::Result<ExternalIdentity> {
+        let metadata = self.metadata(provider).await?;
+
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", redirect_uri),
+            ("client_id", provider.client_id.as_str()),
+            ("code_verifier", code_verifier),
+        ];
+        if let Some(client_secret) = &provider.client_secret {
+            form.push(("client_secret", client_secret.as_str()));
+        }
+
+        let tokens: TokenResponse = self.http.post(&metadata.token_endpoint)
+            .header("Accept", "application/json")
+            .form(&form)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .context("Invalid token response")?;
+
+        let mut claims = match &tokens.id_token {
+            Some(id_token) => self.verify_id_token(provider, &metadata, id_token, nonce).await?,
+            None => Value::Object(Default::default()),
+        };
+
+        // Plain OAuth2 providers, and OIDC providers that keep email out of the id_token
+        if let Some(userinfo_endpoint) = &metadata.userinfo_endpoint {
+            if tokens.id_token.is_none() || claims.get("email").is_none() {
+                let userinfo: Value = self.http.get(userinfo_endpoint)
+                    .bearer_auth(&tokens.access_token)
+                    .header("Accept", "application/json")
+                    .header("User-Agent", "connecting-opportunities")
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await
+                    .context("Invalid userinfo response")?;
+
+                match (&mut claims, userinfo) {
+                    // The id_token subject is authoritative; userinfo must agree with it
+                    (Value::Object(claims), Value::Object(userinfo)) => {
+                        if let (Some(sub), Some(userinfo_sub)) = (claims.get("sub"), userinfo.get("sub")) {
+                            if sub != userinfo_sub {
+                                anyhow::bail!("userinfo subject does not match the id_token");
+                            }
+                        }
+                        for (key, value) in userinfo {
+                            claims.entry(key).or_insert(value);
+                        }
+                    }
+                    _ => anyhow::bail!("Invalid userinfo response"),
+                }
+            }
+        } else if tokens.id_token.is_none() {
+            anyhow::bail!("Provider returned neither an id_token nor a userinfo endpoint");
+        }
+
+        ExternalIdentity::from_claims(&claims, provider.trust_email)
+    }
+
+    async fn verify_id_token(
+        &self,
+        provider: &OidcProviderConfig,
+        metadata: &ProviderMetadata,
+        id_token: &str,
+        nonce: &str,
+    ) -> anyhow::Result<Value> {
+        let header = decode_header(id_token)?;
+        // Only asymmetric algorithms; never trust "none" or a shared secret here
+        if !matches!(
+            header.alg,
+            Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512
+                | Algorithm::PS256 | Algorithm::PS384 | Algorithm::PS512
+                | Algorithm::ES256 | Algorithm::ES384 | Algorithm::EdDSA
+        ) {
+            anyhow::bail!("Unsupported id_token algorithm {:?}", header.alg);
+        }
+
+        let issuer = metadata.issuer.as_deref().context("Provider has no issuer")?;
+        let jwks_uri = metadata.jwks_uri.as_deref().context("Provider has no jwks_uri")?;
+
+        let decoding_key = match self.find_key(jwks_uri, header.kid.as_deref(), false).await? {
+            Some(key) => key,
+            // The provider may have rotated its keys since we cached them
+            None => self.find_key(jwks_uri, header.kid.as_deref(), true).await?
+                .context("id_token signed with an unknown key")?,
+        };
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_issuer(&[issuer]);
+        validation.set_audience(&[&provider.client_id]);
+
+        let claims = decode::<Value>(id_token, &decoding_key, &validation)?.claims;
+
+        if claims.get("nonce").and_then(Value::as_str) != Some(nonce) {
+            anyhow::bail!("id_token nonce does not match");
+        }
+
+        Ok(claims)
+    }
+
+    async fn find_key(&self, jwks_uri: &str, kid: Option<&str>, refresh: bool) -> anyhow::Result<Option<DecodingKey>> {
+        if refresh {
+            self.jwks.invalidate(jwks_uri).await;
+        }
+
+        let jwks = match self.jwks.get(jwks_uri).await {
+            Some(jwks) => jwks,
+            None => {
+                let jwks: JwkSet = self.http.get(jwks_uri)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await
+                    .context("Invalid JWKS")?;
+                let jwks = Arc::new(jwks);
+                self.jwks.insert(jwks_uri.to_string(), jwks.clone()).await;
+                jwks
+            }
+        };
+
+        let jwk = match kid {
+            Some(kid) => jwks.find(kid),
+            None if jwks.keys.len() == 1 => jwks.keys.first(),
+            None => None,
+        };
+
+        jwk.map(DecodingKey::from_jwk).transpose().map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+    use jsonwebtoken::jwk::{
+        AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, OctetKeyPairParameters, OctetKeyPairType,
+    };
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use parking_lot::Mutex;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    const CLIENT_ID: &str = "test-client";
+    const REDIRECT_URI: &str = "http://localhost:3000/oauth/callback/mock";
+
+    struct TestKey {
+        kid: String,
+        encoding_key: EncodingKey,
+        jwk: Jwk,
+    }
+
+    fn test_key(kid: &str) -> TestKey {
+        let document = Ed25519KeyPair::generate_pkcs8(&ring::rand::SystemRandom::new()).unwrap();
+        let key_pair = Ed25519KeyPair::from_pkcs8(document.as_ref()).unwrap();
+        let pem = pem::encode(&pem::Pem { tag: "PRIVATE KEY".to_string(), contents: document.as_ref().to_vec() });
+        TestKey {
+            kid: kid.to_string(),
+            encoding_key: EncodingKey::from_ed_pem(pem.as_bytes()).unwrap(),
+            jwk: Jwk {
+                common: CommonParameters {
+                    algorithm: Some(Algorithm::EdDSA),
+                    key_id: Some(kid.to_string()),
+                    ..Default::default()
+                },
+                algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                    key_type: OctetKeyPairType::OctetKeyPair,
+                    curve: EllipticCurve::Ed25519,
+                    x: BASE64URL_NOPAD.encode(key_pair.public_key().as_ref()),
+                }),
+            },
+        }
+    }
+
+    fn sign(key: &TestKey, claims: &Value) -> String {
+        let header = Header { kid: Some(key.kid.clone()), ..Header::new(Algorithm::EdDSA) };
+        encode(&header, claims, &key.encoding_key).unwrap()
+    }
+
+    /// What the mock provider hands out, and the token requests it received
+    struct MockIdp {
+        issuer: String,
+        jwks: JwkSet,
+        id_token: Mutex<Option<String>>,
+        userinfo: Mutex<Value>,
+        token_requests: Mutex<Vec<HashMap<String, String>>>,
+    }
+
+    async fn discovery(idp: web::Data<MockIdp>) -> HttpResponse {
+        HttpResponse::Ok().json(json!({
+            "issuer": idp.issuer,
+            "authorization_endpoint": format!("{}/authorize", idp.issuer),
+            "token_endpoint": format!("{}/token", idp.issuer),
+            "userinfo_endpoint": format!("{}/userinfo", idp.issuer),
+            "jwks_uri": format!("{}/jwks", idp.issuer),
+        }))
+    }
+
+    async fn token(idp: web::Data<MockIdp>, form: web::Form<HashMap<String, String>>) -> HttpResponse {
+        idp.token_requests.lock().push(form.into_inner());
+        let mut response = json!({ "access_token": "mock-access-token", "token_type": "Bearer" });
+        if let Some(id_token) = idp.id_token.lock().clone() {
+            response["id_token"] = json!(id_token);
+        }
+        HttpResponse::Ok().json(response)
+    }
+
+    async fn userinfo(idp: web::Data<MockIdp>, req: HttpRequest) -> HttpResponse {
+        let authorization = req.headers().get("Authorization").and_then(|value| value.to_str().ok());
+        if authorization != Some("Bearer mock-access-token") {
+            return HttpResponse::Unauthorized().finish();
+        }
+        HttpResponse::Ok().json(idp.userinfo.lock().clone())
+    }
+
+    async fn jwks(idp: web::Data<MockIdp>) -> HttpResponse {
+        HttpResponse::Ok().json(&idp.jwks)
+    }
+
+    /// Serve a provider on a free local port. Its discovery document is also served
+    /// under `/impostor`, where it names the real issuer instead.
+    async fn start_idp(keys: &[&TestKey]) -> web::Data<MockIdp> {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let idp = web::Data::new(MockIdp {
+            issuer: format!("http://{}", listener.local_addr().unwrap()),
+            jwks: JwkSet { keys: keys.iter().map(|key| key.jwk.clone()).collect() },
+            id_token: Mutex::new(None),
+            userinfo: Mutex::new(json!({})),
+            token_requests: Mutex::new(Vec::new()),
+        });
+
+        let data = idp.clone();
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(data.clone())
+                .route("/.well-known/openid-configuration", web::get().to(discovery))
+                .route("/impostor/.well-known/openid-configuration", web::get().to(discovery))
+                .route("/token", web::post().to(token))
+                .route("/userinfo", web::get().to(userinfo))
+                .route("/jwks", web::get().to(jwks))
+        })
+        .workers(1)
+        .listen(listener)
+        .unwrap()
+        .run();
+        actix_rt::spawn(server);
+        idp
+    }
+
+    fn provider(issuer: &str) -> OidcProviderConfig {
+        OidcProviderConfig {
+            name: "mock".to_string(),
+            issuer: Some(issuer.to_string()),
+            client_id: CLIENT_ID.to_string(),
+            client_secret: Some("test-secret".to_string()),
+            scopes: "openid email profile".to_string(),
+            authorization_endpoint: None,
+            token_endpoint: None,
+            userinfo_endpoint: None,
+            trust_email: false,
+        }
+    }
+
+    fn client() -> OidcClient {
+        OidcClient::with_http_client(Client::builder().timeout(Duration::from_secs(5)).build().unwrap())
+    }
+
+    fn id_token_claims(idp: &MockIdp, nonce: &str) -> Value {
+        let now = chrono::Utc::now().timestamp();
+        json!({
+            "iss": idp.issuer,
+            "aud": CLIENT_ID,
+            "sub": "user-1",
+            "email": "jane@example.com",
+            "email_verified": true,
+            "given_name": "Jane",
+            "family_name": "Doe",
+            "nonce": nonce,
+            "iat": now,
+            "exp": now + 300,
+        })
+    }
+
+    #[actix_rt::test]
+    async fn exchanges_the_code_and_validates_the_id_token() {
+        let key = test_key("key-1");
+        let idp = start_idp(&[&key]).await;
+        let provider = provider(&idp.issuer);
+        let oidc = client();
+
+        let request = oidc.authorization_request(&provider, REDIRECT_URI).await.unwrap();
+        let url = url::Url::parse(&request.url).unwrap();
+        assert_eq!(url.as_str().split('?').next(), Some(format!("{}/authorize", idp.issuer).as_str()));
+        let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+        assert_eq!(query["client_id"], CLIENT_ID);
+        assert_eq!(query["redirect_uri"], REDIRECT_URI);
+        assert_eq!(query["state"], request.state);
+        assert_eq!(query["nonce"], request.nonce);
+        assert_eq!(query["code_challenge_method"], "S256");
+        assert_eq!(query["code_challenge"], BASE64URL_NOPAD.encode(&Sha256::digest(request.code_verifier.as_bytes())));
+
+        *idp.id_token.lock() = Some(sign(&key, &id_token_claims(&idp, &request.nonce)));
+        let identity = oidc
+            .complete(&provider, "the-code", &request.code_verifier, &request.nonce, REDIRECT_URI)
+            .await
+            .unwrap();
+        assert_eq!(identity.subject, "user-1");
+        assert_eq!(identity.email.as_deref(), Some("jane@example.com"));
+        assert!(identity.email_verified);
+        assert_eq!(identity.given_name.as_deref(), Some("Jane"));
+        assert_eq!(identity.family_name.as_deref(), Some("Doe"));
+
+        let requests = idp.token_requests.lock();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0]["grant_type"], "authorization_code");
+        assert_eq!(requests[0]["code"], "the-code");
+        assert_eq!(requests[0]["code_verifier"], request.code_verifier);
+        assert_eq!(requests[0]["redirect_uri"], REDIRECT_URI);
+        assert_eq!(requests[0]["client_id"], CLIENT_ID);
+        assert_eq!(requests[0]["client_secret"], "test-secret");
+    }
+
+    #[actix_rt::test]
+    async fn rejects_id_tokens_that_do_not_check_out() {
+        let key = test_key("key-1");
+        let unpublished = test_key("key-2");
+        let impostor = test_key("key-1"); // claims the published kid with another key
+        let idp = start_idp(&[&key]).await;
+        let provider = provider(&idp.issuer);
+        let oidc = client();
+        let nonce = "expected-nonce";
+
+        let with = |changes: Value| {
+            let mut claims = id_token_claims(&idp, nonce);
+            for (name, value) in changes.as_object().unwrap() {
+                claims[name] = value.clone();
+            }
+            claims
+        };
+        let hs256 = encode(
+            &Header { kid: Some("key-1".to_string()), ..Header::new(Algorithm::HS256) },
+            &id_token_claims(&idp, nonce),
+            &EncodingKey::from_secret(b"guessable"),
+        )
+        .unwrap();
+
+        let cases = [
+            ("wrong nonce", sign(&key, &with(json!({ "nonce": "replayed" })))),
+            ("wrong audience", sign(&key, &with(json!({ "aud": "another-client" })))),
+            ("wrong issuer", sign(&key, &with(json!({ "iss": "https://evil.example" })))),
+            ("expired", sign(&key, &with(json!({ "exp": chrono::Utc::now().timestamp() - 3600 })))),
+            ("unknown key", sign(&unpublished, &id_token_claims(&idp, nonce))),
+            ("forged signature", sign(&impostor, &id_token_claims(&idp, nonce))),
+            ("symmetric algorithm", hs256),
+        ];
+        for (case, id_token) in cases {
+            *idp.id_token.lock() = Some(id_token);
+            let result = oidc.complete(&provider, "code", "verifier", nonce, REDIRECT_URI).await;
+            assert!(result.is_err(), "{case} was accepted");
+        }
+
+        // The same client still accepts a good token afterwards
+        *idp.id_token.lock() = Some(sign(&key, &id_token_claims(&idp, nonce)));
+        assert!(oidc.complete(&provider, "code", "verifier", nonce, REDIRECT_URI).await.is_ok());
+    }
+
+    #[actix_rt::test]
+    async fn fills_missing_claims_from_userinfo_for_the_same_subject() {
+        let key = test_key("key-1");
+        let idp = start_idp(&[&key]).await;
+        let provider = provider(&idp.issuer);
+        let oidc = client();
+        let nonce = "nonce";
+
+        let mut claims = id_token_claims(&idp, nonce);
+        let claims_object = claims.as_object_mut().unwrap();
+        claims_object.remove("email");
+        claims_object.remove("email_verified");
+        *idp.id_token.lock() = Some(sign(&key, &claims));
+
+        *idp.userinfo.lock() = json!({ "sub": "user-1", "email": "jane@example.com", "email_verified": true });
+        let identity = oidc.complete(&provider, "code", "verifier", nonce, REDIRECT_URI).await.unwrap();
+        assert_eq!(identity.email.as_deref(), Some("jane@example.com"));
+        assert!(identity.email_verified);
+
+        *idp.userinfo.lock() = json!({ "sub": "someone-else", "email": "mallory@example.com" });
+        assert!(oidc.complete(&provider, "code", "verifier", nonce, REDIRECT_URI).await.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn rejects_a_discovery_document_for_another_issuer() {
+        let idp = start_idp(&[]).await;
+        let provider = provider(&format!("{}/impostor", idp.issuer));
+        assert!(client().authorization_request(&provider, REDIRECT_URI).await.is_err());
+    }
+}
@@ -0,0 +1,331 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use actix_web::HttpResponse;
+use sha1::{Digest, Sha1};
+
+use crate::config::Config;
+use crate::models::{ApiResponse, FieldError, FieldErrors};
+
+/// Rules every new password has to meet, wherever it is set
+#[derive(Clone)]
+pub struct PasswordPolicy {
+    min_length: usize,
+    max_length: usize,
+    min_entropy_bits: f64,
+    breach_list: Option<Arc<BreachList>>,
+}
+
+/// Who the password belongs to, so it can be checked for personal details
+pub struct PasswordOwner<'a> {
+    pub username: &'a str,
+    pub email: &'a str,
+}
+
+impl PasswordPolicy {
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let breach_list = match &config.password_breach_list {
+            Some(path) => Some(Arc::new(BreachList::open(path)?)),
+            None => None,
+        };
+
+        Ok(Self {
+            min_length: config.password_min_length,
+            max_length: config.password_max_length,
+            min_entropy_bits: config.password_min_entropy_bits,
+            breach_list,
+        })
+    }
+
+    /// Check `password` against every rule, reporting all failures against `field`
+    pub async fn check(&self, field: &str, password: &str, owner: PasswordOwner<'_>) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        let length = password.chars().count();
+
+        if length < self.min_length {
+            errors.push(FieldError::new(
+                field,
+                "too_short",
+                &format!("Password must be at least {} characters long", self.min_length),
+            ));
+        }
+        if length > self.max_length {
+            errors.push(FieldError::new(
+                field,
+                "too_long",
+                &format!("Password must be at most {} characters long", self.max_length),
+            ));
+        }
+        if estimate_entropy_bits(password) < self.min_entropy_bits {
+            errors.push(FieldError::new(
+                field,
+                "too_weak",
+                "Password is too easy to guess; use a longer password or mix in other kinds of characters",
+            ));
+        }
+
+        let lowered = password.to_lowercase();
+        let username = owner.username.trim().to_lowercase();
+        if username.chars().count() >= 3 && lowered.contains(&username) {
+            errors.push(FieldError::new(field, "contains_username", "Password must not contain your username"));
+        }
+        let email = owner.email.trim().to_lowercase();
+        let local_part = email.split('@').next().unwrap_or_default();
+        if local_part.chars().count() >= 3 && lowered.contains(local_part) {
+            errors.push(FieldError::new(field, "contains_email", "Password must not contain your email address"));
+        }
+
+        if let Some(breach_list) = &self.breach_list {
+            if length <= self.max_length && breach_list.contains(password).await {
+                errors.push(FieldError::new(
+                    field,
+                    "breached",
+                    "Password has appeared in a data breach; please choose a different one",
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// 400 response listing every rule the password broke
+pub fn rejected(errors: Vec<FieldError>) -> HttpResponse {
+    HttpResponse::BadRequest().json(ApiResponse::error_with_data(
+        "Password does not meet the password policy",
+        FieldErrors { errors },
+    ))
+}
+
+/// Rough guessing entropy: the size of the character classes used, raised to the
+/// number of characters that are not repeats or steps of the one before (`aaaa`, `1234`, `cba`)
+fn estimate_entropy_bits(password: &str) -> f64 {
+    let mut pool = 0u32;
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        pool += 10;
+    }
+    if password.chars().any(|c| c.is_ascii_punctuation() || c == ' ') {
+        pool += 33;
+    }
+    if !password.is_ascii() {
+        pool += 100;
+    }
+    if pool == 0 {
+        return 0.0;
+    }
+
+    let mut effective_length = 0u32;
+    let mut previous: Option<char> = None;
+    for c in password.chars() {
+        let predictable = previous.is_some_and(|p| (c as i64 - p as i64).abs() <= 1);
+        if !predictable {
+            effective_length += 1;
+        }
+        previous = Some(c);
+    }
+
+    effective_length as f64 * (pool as f64).log2()
+}
+
+/// A breached-password list in the Have I Been Pwned format: one upper-case SHA-1 hash
+/// per line, optionally followed by `:count`, ordered by hash. The file is binary searched
+/// on disk, so even the full dump needs no memory.
+pub struct BreachList {
+    path: PathBuf,
+}
+
+impl BreachList {
+    fn open(path: &str) -> anyhow::Result<Self> {
+        let path = PathBuf::from(path);
+        let mut first_line = String::new();
+        BufReader::new(File::open(&path).map_err(|e| anyhow::anyhow!("Failed to open {}: {e}", path.display()))?)
+            .read_line(&mut first_line)?;
+
+        let first_hash = first_line.trim().split(':').next().unwrap_or_default();
+        if first_hash.len() != 40 || !first_hash.chars().all(|c| c.is_ascii_hexdigit()) {
+            anyhow::bail!("{} is not a SHA-1 breached-password list", path.display());
+        }
+
+        log::info!("Screening passwords against {}", path.display());
+        Ok(Self { path })
+    }
+
+    /// Whether `password` is on the list. Lookup errors are logged and treated as
+    /// not found, so a broken list never stops users from setting a password.
+    async fn contains(&self, password: &str) -> bool {
+        let hash = hex::encode_upper(Sha1::digest(password.as_bytes()));
+        let path = self.path.clone();
+
+        match actix_web::web::block(move || search_sorted_file(&path, &hash)).await {
+            Ok(Ok(found)) => found,
+            Ok(Err(e)) => {
+                log::error!("Failed to search breached-password list: {e}");
+                false
+            }
+            Err(e) => {
+                log::error!("Breached-password lookup task failed: {e}");
+                false
+            }
+        }
+    }
+}
+
+/// Binary search a file of lines sorted by their `key[:value]` prefix, by byte offset
+fn search_sorted_file(path: &Path, key: &str) -> std::io::Result<bool> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut low = 0u64; // always the start of a line
+    let mut high = reader.get_ref().metadata()?.len();
+    let mut line = Vec::new();
+
+    while low < high {
+        let mid = low + (high - low) / 2;
+
+        // Find the first line starting at or after `mid`
+        let line_start = if mid == low {
+            reader.seek(SeekFrom::Start(low))?;
+            low
+        } else {
+            reader.seek(SeekFrom::Start(mid - 1))?;
+            line.clear();
+            mid - 1 + reader.read_until(b'\n', &mut line)? as u64
+        };
+        if line_start >= high {
+            high = mid;
+            continue;
+        }
+
+        line.clear();
+        let read = reader.read_until(b'\n', &mut line)? as u64;
+        if read == 0 {
+            high = mid;
+            continue;
+        }
+
+        let line_key = line.split(|b| *b == b':').next().unwrap_or_default();
+        let line_key = line_key.trim_ascii();
+        match line_key.cmp(key.as_bytes()) {
+            std::cmp::Ordering::Equal => return Ok(true),
+            std::cmp::Ordering::Less => low = line_start + read,
+            std::cmp::Ordering::Greater => high = mid,
+        }
+    }
+
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn sorted_hashes(count: usize) -> Vec<String> {
+        let mut hashes: Vec<String> = (0..count)
+            .map(|i| hex::encode_upper(Sha1::digest(i.to_string().as_bytes())))
+            .collect();
+        hashes.sort();
+        hashes
+    }
+
+    /// Write `hash:count` lines to a fresh file, ended by `line_ending` (also after the last line when `trailing`)
+    fn write_list(name: &str, hashes: &[String], line_ending: &str, trailing: bool) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("breach-list-{}-{name}.txt", std::process::id()));
+        let mut contents = hashes
+            .iter()
+            .enumerate()
+            .map(|(i, hash)| format!("{hash}:{}", i + 1))
+            .collect::<Vec<_>>()
+            .join(line_ending);
+        if trailing {
+            contents.push_str(line_ending);
+        }
+        File::create(&path).unwrap().write_all(contents.as_bytes()).unwrap();
+        path
+    }
+
+    fn assert_finds_every_line(path: &Path, hashes: &[String]) {
+        assert!(search_sorted_file(path, &hashes[0]).unwrap(), "first line");
+        assert!(search_sorted_file(path, hashes.last().unwrap()).unwrap(), "last line");
+        for hash in hashes {
+            assert!(search_sorted_file(path, hash).unwrap(), "{hash}");
+        }
+    }
+
+    fn assert_misses(path: &Path, hashes: &[String]) {
+        assert!(!search_sorted_file(path, &"0".repeat(40)).unwrap(), "before the first line");
+        assert!(!search_sorted_file(path, &"F".repeat(40)).unwrap(), "after the last line");
+        // Between two neighbouring lines
+        let mut between = hashes[hashes.len() / 2].clone();
+        between.push('0');
+        assert!(!search_sorted_file(path, &between).unwrap(), "between lines");
+        // A prefix of a listed hash is not a match
+        assert!(!search_sorted_file(path, &hashes[1][..39]).unwrap(), "prefix");
+    }
+
+    #[test]
+    fn finds_first_last_and_every_line() {
+        let hashes = sorted_hashes(200);
+        let path = write_list("lf", &hashes, "\n", true);
+        assert_finds_every_line(&path, &hashes);
+        assert_misses(&path, &hashes);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn handles_crlf_line_endings() {
+        let hashes = sorted_hashes(200);
+        let path = write_list("crlf", &hashes, "\r\n", true);
+        assert_finds_every_line(&path, &hashes);
+        assert_misses(&path, &hashes);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn handles_a_missing_final_newline() {
+        let hashes = sorted_hashes(200);
+        let path = write_list("no-trailing", &hashes, "\n", false);
+        assert_finds_every_line(&path, &hashes);
+        assert_misses(&path, &hashes);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn handles_tiny_files() {
+        let hashes = sorted_hashes(1);
+        let path = write_list("single", &hashes, "\n", true);
+        assert!(search_sorted_file(&path, &hashes[0]).unwrap());
+        assert!(!search_sorted_file(&path, &"0".repeat(40)).unwrap());
+        assert!(!search_sorted_file(&path, &"F".repeat(40)).unwrap());
+        std::fs::remove_file(path).unwrap();
+
+        let path = write_list("empty", &[], "\n", false);
+        assert!(!search_sorted_file(&path, &"0".repeat(40)).unwrap());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn breach_list_hashes_the_password() {
+        let mut hashes = vec![
+            hex::encode_upper(Sha1::digest(b"password")),
+            hex::encode_upper(Sha1::digest(b"123456")),
+        ];
+        hashes.sort();
+        let path = write_list("breach", &hashes, "\r\n", true);
+        let list = BreachList::open(path.to_str().unwrap()).unwrap();
+        assert!(list.contains("password").await);
+        assert!(list.contains("123456").await);
+        assert!(!list.contains("correct horse battery staple").await);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,179 @@
+use serde::Serialize;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::cache::CacheManager;
+
+/// Something a role can be allowed to do. The names are stored in `permissions`
+/// and `role_permissions`; adding one here needs a migration that inserts it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    InvitesManage,
+    RolesManage,
+    RolesAssign,
+    UsersUnlock,
+    UsersSuspend,
+    UsersImpersonate,
+    AuditRead,
+    SigningKeysRotate,
+    MaintenanceRun,
+    JobsPost,
+    JobsApply,
+    ApplicationsReadAny,
+}
+
+impl Permission {
+    pub const ALL: &'static [Permission] = &[
+        Permission::InvitesManage,
+        Permission::RolesManage,
+        Permission::RolesAssign,
+        Permission::UsersUnlock,
+        Permission::UsersSuspend,
+        Permission::UsersImpersonate,
+        Permission::AuditRead,
+        Permission::SigningKeysRotate,
+        Permission::MaintenanceRun,
+        Permission::JobsPost,
+        Permission::JobsApply,
+        Permission::ApplicationsReadAny,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Permission::InvitesManage => "invites:manage",
+            Permission::RolesManage => "roles:manage",
+            Permission::RolesAssign => "roles:assign",
+            Permission::UsersUnlock => "users:unlock",
+            Permission::UsersSuspend => "users:suspend",
+            Permission::UsersImpersonate => "users:impersonate",
+            Permission::AuditRead => "audit:read",
+            Permission::SigningKeysRotate => "signing_keys:rotate",
+            Permission::MaintenanceRun => "maintenance:run",
+            Permission::JobsPost => "jobs:post",
+            Permission::JobsApply => "jobs:apply",
+            Permission::ApplicationsReadAny => "applications:read_any",
+        }
+    }
+
+    pub fn parse(permission: &str) -> Option<Self> {
+        Permission::ALL.iter().copied().find(|candidate| candidate.as_str() == permission)
+    }
+
+    /// Permissions over other people's accounts or the service itself. Users
+    /// holding any of them cannot be impersonated.
+    pub fn is_administrative(&self) -> bool {
+        match self {
+            Permission::InvitesManage
+            | Permission::RolesManage
+            | Permission::RolesAssign
+            | Permission::UsersUnlock
+            | Permission::UsersSuspend
+            | Permission::UsersImpersonate
+            | Permission::AuditRead
+            | Permission::SigningKeysRotate
+            | Permission::MaintenanceRun
+            | Permission::ApplicationsReadAny => true,
+            Permission::JobsPost | Permission::JobsApply => false,
+        }
+    }
+}
+
+/// The answer to a permission check, with the reason for it
+#[derive(Debug, Clone, Serialize)]
+pub struct Decision {
+    pub allowed: bool,
+    pub reason: String,
+}
+
+/// What a role may do, as of `version`. Cached per role and attached to every
+/// authenticated request by `AuthMiddleware`.
+#[derive(Debug, Clone)]
+pub struct RolePermissions {
+    pub role: String,
+    pub version: i32,
+    pub permissions: Arc<Vec<String>>,
+}
+
+impl RolePermissions {
+    pub fn grants(&self, permission: Permission) -> bool {
+        self.permissions.iter().any(|granted| granted == permission.as_str())
+    }
+
+    pub fn check(&self, permission: Permission) -> Decision {
+        if self.grants(permission) {
+            Decision {
+                allowed: true,
+                reason: format!("Role {} grants {}", self.role, permission.as_str()),
+            }
+        } else {
+            Decision {
+                allowed: false,
+                reason: format!("Role {} does not grant {}", self.role, permission.as_str()),
+            }
+        }
+    }
+
+    /// Permissions in `other` this role does not have. Nobody may hand out
+    /// permissions they lack, whether by assigning a role or by editing one.
+    pub fn missing<'a>(&self, other: &'a [String]) -> Vec<&'a str> {
+        other.iter()
+            .filter(|permission| !self.permissions.contains(permission))
+            .map(String::as_str)
+            .collect()
+    }
+
+    pub fn is_administrative(&self) -> bool {
+        any_administrative(&self.permissions)
+    }
+}
+
+/// Whether a list of permission names includes an administrative one
+pub fn any_administrative(permissions: &[String]) -> bool {
+    permissions.iter()
+        .filter_map(|permission| Permission::parse(permission))
+        .any(|permission| permission.is_administrative())
+}
+
+/// Read-through lookup of a role's permissions; `None` if the role does not exist
+pub async fn load(pool: &PgPool, cache: &CacheManager, role: &str) -> Result<Option<RolePermissions>, sqlx::Error> {
+    if let Some(permissions) = cache.get_role_permissions(role).await {
+        return Ok(Some(permissions));
+    }
+
+    let row: Option<(i32, Vec<String>)> = sqlx::query_as(
+        r#"
+        SELECT r.permissions_version,
+               COALESCE(ARRAY_AGG(rp.permission ORDER BY rp.permission) FILTER (WHERE rp.permission IS NOT NULL), '{}')
+        FROM roles r
+        LEFT JOIN role_permissions rp ON rp.role = r.name
+        WHERE r.name = $1
+        GROUP BY r.name, r.permissions_version
+        "#
+    )
+    .bind(role)
+    .fetch_optional(pool)
+    .await?;
+
+    let Some((version, permissions)) = row else {
+        return Ok(None);
+    };
+
+    let permissions = RolePermissions { role: role.to_string(), version, permissions: Arc::new(permissions) };
+    cache.cache_role_permissions(permissions.clone()).await;
+    Ok(Some(permissions))
+}
+
+/// Current permissions version of a role, for the `pv` claim of a new access token
+pub async fn version(pool: &PgPool, role: &str) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar("SELECT permissions_version FROM roles WHERE name = $1")
+        .bind(role)
+        .fetch_one(pool)
+        .await
+}
+
+/// Roles users can pick for themselves at registration
+pub async fn self_assignable_roles(pool: &PgPool) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT name FROM roles WHERE self_assignable = true ORDER BY name")
+        .fetch_all(pool)
+        .await
+}
@@ -0,0 +1,223 @@
+use sqlx::{PgPool, Postgres, Transaction};
+
+use crate::applications::ApplicationStatus;
+use crate::models::{PipelineStage, StageHistoryEntry};
+
+pub const MAX_STAGES: usize = 20;
+pub const MAX_BULK_MOVE: usize = 100;
+const MAX_STAGE_NAME_LENGTH: usize = 50;
+const MAX_REASON_LENGTH: usize = 1000;
+
+/// What being in a stage means for an application
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StageKind {
+    Active,
+    Hired,
+    Rejected,
+}
+
+impl StageKind {
+    pub const ALL: &'static [StageKind] = &[StageKind::Active, StageKind::Hired, StageKind::Rejected];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StageKind::Active => "active",
+            StageKind::Hired => "hired",
+            StageKind::Rejected => "rejected",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        StageKind::ALL.iter().copied().find(|candidate| candidate.as_str() == kind)
+    }
+
+    /// The status of an application in a stage of this kind
+    pub fn application_status(&self) -> ApplicationStatus {
+        match self {
+            StageKind::Active => ApplicationStatus::Active,
+            StageKind::Hired => ApplicationStatus::Hired,
+            StageKind::Rejected => ApplicationStatus::Rejected,
+        }
+    }
+}
+
+pub fn validate_stage_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_STAGE_NAME_LENGTH {
+        return Err(format!("Stage name must be between 1 and {MAX_STAGE_NAME_LENGTH} characters"));
+    }
+    Ok(name.to_string())
+}
+
+pub fn validate_reason(reason: Option<String>) -> Result<Option<String>, String> {
+    let reason = reason
+        .map(|reason| reason.trim().to_string())
+        .filter(|reason| !reason.is_empty());
+    if reason.as_ref().is_some_and(|reason| reason.chars().count() > MAX_REASON_LENGTH) {
+        return Err(format!("Reason must be at most {MAX_REASON_LENGTH} characters"));
+    }
+    Ok(reason)
+}
+
+/// Give a new job the stages of the default template
+pub async fn create_default(tx: &mut Transaction<'_, Postgres>, job_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO pipeline_stages (job_id, name, kind, position)
+        SELECT $1, name, kind, position FROM pipeline_template_stages
+        "#
+    )
+    .bind(job_id)
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
+const STAGES_QUERY: &str = r#"
+    SELECT s.id, s.job_id, s.name, s.kind, s.position,
+           COUNT(a.id) FILTER (WHERE a.status <> 'withdrawn') AS application_count
+    FROM pipeline_stages s
+    LEFT JOIN applications a ON a.stage_id = s.id
+    WHERE s.job_id = $1
+    GROUP BY s.id
+    ORDER BY s.position, s.id
+"#;
+
+/// The stages of a job in order, with how many applications are in each
+pub async fn stages(pool: &PgPool, job_id: i32) -> Result<Vec<PipelineStage>, sqlx::Error> {
+    sqlx::query_as::<_, PipelineStage>(STAGES_QUERY)
+        .bind(job_id)
+        .fetch_all(pool)
+        .await
+}
+
+/// Lock a job's pipeline for changes and return its stages in order
+pub async fn lock_stages(tx: &mut Transaction<'_, Postgres>, job_id: i32) -> Result<Vec<PipelineStage>, sqlx::Error> {
+    sqlx::query("SELECT id FROM jobs WHERE id = $1 FOR UPDATE")
+        .bind(job_id)
+        .execute(&mut *tx)
+        .await?;
+
+    sqlx::query_as::<_, PipelineStage>(STAGES_QUERY)
+        .bind(job_id)
+        .fetch_all(&mut *tx)
+        .await
+}
+
+/// Store positions 1..n in the order given
+pub async fn renumber(tx: &mut Transaction<'_, Postgres>, stage_ids: &[i32]) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        UPDATE pipeline_stages s SET position = o.position::INTEGER
+        FROM UNNEST($1::INTEGER[]) WITH ORDINALITY AS o(id, position)
+        WHERE s.id = o.id
+        "#
+    )
+    .bind(stage_ids)
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
+/// The stage new applications to a job start in: its first active stage.
+/// The row stays locked against deletion until the transaction ends.
+pub async fn entry_stage(
+    tx: &mut Transaction<'_, Postgres>,
+    job_id: i32,
+) -> Result<Option<(i32, String)>, sqlx::Error> {
+    sqlx::query_as(
+        r#"
+        SELECT id, name FROM pipeline_stages
+        WHERE job_id = $1 AND kind = 'active'
+        ORDER BY position, id
+        LIMIT 1
+        FOR SHARE
+        "#
+    )
+    .bind(job_id)
+    .fetch_optional(&mut *tx)
+    .await
+}
+
+/// A move of one or more applications into a stage
+pub struct StageMove<'a> {
+    pub stage_id: i32,
+    pub stage_name: &'a str,
+    pub kind: StageKind,
+    pub moved_by: i32,
+    pub reason: Option<&'a str>,
+}
+
+/// Record where a new application starts
+pub async fn record_entry(
+    tx: &mut Transaction<'_, Postgres>,
+    application_id: i32,
+    entry: &StageMove<'_>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO application_stage_history (application_id, to_stage_id, to_stage_name, moved_by, reason)
+        VALUES ($1, $2, $3, $4, $5)
+        "#
+    )
+    .bind(application_id)
+    .bind(entry.stage_id)
+    .bind(entry.stage_name)
+    .bind(entry.moved_by)
+    .bind(entry.reason)
+    .execute(&mut *tx)
+    .await?;
+    Ok(())
+}
+
+/// Move applications into a stage and record each move. Applications already
+/// in the stage are left alone; returns how many moved.
+pub async fn move_applications(
+    tx: &mut Transaction<'_, Postgres>,
+    application_ids: &[i32],
+    to: &StageMove<'_>,
+) -> Result<u64, sqlx::Error> {
+    // The self-join reads each application's stage from before the update
+    let result = sqlx::query(
+        r#"
+        WITH moved AS (
+            UPDATE applications a
+            SET stage_id = $2, status = $3, updated_at = CURRENT_TIMESTAMP,
+                status_changed_at = CASE WHEN a.status <> $3 THEN CURRENT_TIMESTAMP ELSE a.status_changed_at END
+            FROM applications previous
+            JOIN pipeline_stages s ON s.id = previous.stage_id
+            WHERE previous.id = a.id AND a.id = ANY($1) AND a.stage_id <> $2
+            RETURNING a.id, previous.stage_id, s.name
+        )
+        INSERT INTO application_stage_history
+            (application_id, from_stage_id, from_stage_name, to_stage_id, to_stage_name, moved_by, reason)
+        SELECT id, stage_id, name, $2, $4, $5, $6 FROM moved
+        "#
+    )
+    .bind(application_ids)
+    .bind(to.stage_id)
+    .bind(to.kind.application_status().as_str())
+    .bind(to.stage_name)
+    .bind(to.moved_by)
+    .bind(to.reason)
+    .execute(&mut *tx)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Every stage move of an application, oldest first
+pub async fn history(pool: &PgPool, application_id: i32) -> Result<Vec<StageHistoryEntry>, sqlx::Error> {
+    sqlx::query_as::<_, StageHistoryEntry>(
+        r#"
+        SELECT h.id, h.application_id, h.from_stage_id, h.from_stage_name, h.to_stage_id, h.to_stage_name,
+               h.moved_by, u.username AS moved_by_username, h.reason, h.created_at
+        FROM application_stage_history h
+        LEFT JOIN users u ON u.id = h.moved_by
+        WHERE h.application_id = $1
+        ORDER BY h.created_at, h.id
+        "#
+    )
+    .bind(application_id)
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,154 @@
+use chrono::{DateTime, Utc};
+use sqlx::postgres::PgListener;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::cache::CacheManager;
+
+/// Postgres channel used to tell every replica about new revocations
+const CHANNEL: &str = "auth_revocations";
+
+/// Something that invalidates credentials and must be seen by every replica
+#[derive(Debug, Clone, Copy)]
+pub enum RevocationEvent {
+    /// A single access token, by its `jti` claim
+    Token(Uuid),
+    /// Every token of a user issued before `users.tokens_invalid_before`
+    User(i32),
+    /// A session (refresh token family) and the access tokens bound to it
+    Session(Uuid),
+    /// The permissions of a role changed; tokens with the old `pv` claim are stale
+    RolePermissions,
+}
+
+impl RevocationEvent {
+    fn to_payload(self) -> String {
+        match self {
+            RevocationEvent::Token(jti) => format!("token:{jti}"),
+            RevocationEvent::User(user_id) => format!("user:{user_id}"),
+            RevocationEvent::Session(session_id) => format!("session:{session_id}"),
+            RevocationEvent::RolePermissions => "roles:*".to_string(),
+        }
+    }
+
+    fn from_payload(payload: &str) -> Option<Self> {
+        let (kind, value) = payload.split_once(':')?;
+        match kind {
+            "token" => value.parse().ok().map(RevocationEvent::Token),
+            "user" => value.parse().ok().map(RevocationEvent::User),
+            "session" => value.parse().ok().map(RevocationEvent::Session),
+            "roles" => Some(RevocationEvent::RolePermissions),
+            _ => None,
+        }
+    }
+
+    async fn apply(self, cache: &CacheManager) {
+        match self {
+            RevocationEvent::Token(jti) => cache.blacklist_token(jti).await,
+            RevocationEvent::User(user_id) => {
+                cache.token_cutoffs.invalidate(&user_id).await;
+                cache.invalidate_user(user_id).await;
+            }
+            RevocationEvent::Session(session_id) => cache.invalidate_sessions(&[session_id]).await,
+            RevocationEvent::RolePermissions => cache.role_permissions.invalidate_all(),
+        }
+    }
+}
+
+/// Apply revocations to the local cache right away and broadcast them to the other replicas.
+/// The database change itself must already be committed.
+pub async fn publish(pool: &PgPool, cache: &CacheManager, events: &[RevocationEvent]) {
+    for event in events {
+        event.apply(cache).await;
+
+        if let Err(e) = sqlx::query("SELECT pg_notify($1, $2)")
+            .bind(CHANNEL)
+            .bind(event.to_payload())
+            .execute(pool)
+            .await
+        {
+            log::error!("Failed to broadcast revocation {event:?}: {e}");
+        }
+    }
+}
+
+/// Durably revoke one access token until it would have expired
+pub async fn revoke_token(
+    pool: &PgPool,
+    cache: &CacheManager,
+    jti: Uuid,
+    user_id: i32,
+    expires_at: DateTime<Utc>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO revoked_tokens (jti, user_id, expires_at) VALUES ($1, $2, $3) ON CONFLICT (jti) DO NOTHING"
+    )
+    .bind(jti)
+    .bind(user_id)
+    .bind(expires_at)
+    .execute(pool)
+    .await?;
+
+    publish(pool, cache, &[RevocationEvent::Token(jti)]).await;
+    Ok(())
+}
+
+/// Read-through revocation check: the cache answers when it can, Postgres is the source of truth
+pub async fn is_token_revoked(pool: &PgPool, cache: &CacheManager, jti: Uuid) -> Result<bool, sqlx::Error> {
+    if let Some(revoked) = cache.is_token_blacklisted(jti).await {
+        return Ok(revoked);
+    }
+
+    let revoked: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM revoked_tokens WHERE jti = $1)")
+        .bind(jti)
+        .fetch_one(pool)
+        .await?;
+
+    cache.set_token_revoked(jti, revoked).await;
+    Ok(revoked)
+}
+
+/// Listen for revocations published by any replica and apply them to this replica's cache
+pub fn spawn_listener(pool: PgPool, cache: CacheManager) {
+    actix_web::rt::spawn(async move {
+        loop {
+            let mut listener = match PgListener::connect_with(&pool).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    log::error!("Failed to connect revocation listener: {e}");
+                    tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+
+            if let Err(e) = listener.listen(CHANNEL).await {
+                log::error!("Failed to listen on {CHANNEL}: {e}");
+                tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+                continue;
+            }
+
+            // Anything published while we were not listening is lost, so start from a clean cache
+            cache.invalidate_auth_state();
+
+            loop {
+                match listener.try_recv().await {
+                    Ok(Some(notification)) => {
+                        match RevocationEvent::from_payload(notification.payload()) {
+                            Some(event) => event.apply(&cache).await,
+                            None => log::warn!("Ignoring malformed revocation payload: {}", notification.payload()),
+                        }
+                    }
+                    Ok(None) => {
+                        // The connection dropped; the listener reconnects on the next call
+                        log::warn!("Revocation listener lost its connection, clearing auth caches");
+                        cache.invalidate_auth_state();
+                    }
+                    Err(e) => {
+                        log::error!("Revocation listener failed: {e}");
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,167 @@
+use actix_web::HttpResponse;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::PgPool;
+
+use crate::audit::{self, SecurityEventType};
+use crate::config::Config;
+use crate::models::ApiResponse;
+
+/// Something failed logins are counted against
+#[derive(Debug, Clone)]
+pub enum ThrottleKey {
+    /// An existing account
+    User(i32),
+    /// A login identifier that matches no account, so probing unknown names is throttled too
+    Login(String),
+    /// A source IP address
+    Ip(String),
+}
+
+impl ThrottleKey {
+    pub fn login(identifier: &str) -> Self {
+        ThrottleKey::Login(identifier.trim().to_lowercase().chars().take(255).collect())
+    }
+
+    fn as_key(&self) -> String {
+        match self {
+            ThrottleKey::User(user_id) => format!("user:{user_id}"),
+            ThrottleKey::Login(identifier) => format!("login:{identifier}"),
+            ThrottleKey::Ip(address) => format!("ip:{address}"),
+        }
+    }
+
+    fn max_attempts(&self, config: &Config) -> i32 {
+        match self {
+            ThrottleKey::User(_) | ThrottleKey::Login(_) => config.login_max_attempts,
+            ThrottleKey::Ip(_) => config.login_ip_max_attempts,
+        }
+    }
+}
+
+/// Lockout length after `failed_count` failures: doubles with every failure past the threshold
+fn lockout_seconds(config: &Config, failed_count: i32, max_attempts: i32) -> i64 {
+    let excess = (failed_count - max_attempts).clamp(0, 20) as u32;
+    config.login_lockout_base
+        .saturating_mul(1i64 << excess)
+        .min(config.login_lockout_max)
+}
+
+pub fn too_many_attempts(retry_after: i64) -> HttpResponse {
+    HttpResponse::TooManyRequests()
+        .insert_header(("Retry-After", retry_after.to_string()))
+        .json(ApiResponse::<()>::error(&format!(
+            "Too many failed login attempts. Try again in {retry_after} seconds"
+        )))
+}
+
+/// Reject the request with 429 while any of `keys` is locked out
+pub async fn check(pool: &PgPool, keys: &[ThrottleKey]) -> Result<(), HttpResponse> {
+    let keys: Vec<String> = keys.iter().map(ThrottleKey::as_key).collect();
+
+    let locked_until: Option<DateTime<Utc>> = sqlx::query_scalar(
+        "SELECT MAX(locked_until) FROM login_throttles WHERE throttle_key = ANY($1) AND locked_until > CURRENT_TIMESTAMP"
+    )
+    .bind(&keys)
+    .fetch_one(pool)
+    .await
+    .map_err(|_| HttpResponse::InternalServerError().json(ApiResponse::<()>::error(
+        "Database error"
+    )))?;
+
+    match locked_until {
+        Some(locked_until) => {
+            let retry_after = (locked_until - Utc::now()).num_seconds().max(0) + 1;
+            Err(too_many_attempts(retry_after))
+        }
+        None => Ok(()),
+    }
+}
+
+/// Count a failed attempt against every key, locking out the ones that crossed their threshold.
+/// `user_id` and `ip_address` only feed the security log.
+pub async fn record_failure(
+    pool: &PgPool,
+    config: &Config,
+    keys: &[ThrottleKey],
+    user_id: Option<i32>,
+    ip_address: Option<&str>,
+) {
+    for key in keys {
+        if let Err(e) = record_key_failure(pool, config, key, user_id, ip_address).await {
+            log::error!("Failed to record failed login for {}: {e}", key.as_key());
+        }
+    }
+}
+
+async fn record_key_failure(
+    pool: &PgPool,
+    config: &Config,
+    key: &ThrottleKey,
+    user_id: Option<i32>,
+    ip_address: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let now = Utc::now();
+    // Failures older than the window no longer count
+    let window_start = now - Duration::seconds(config.login_attempt_window);
+
+    let failed_count: i32 = sqlx::query_scalar(
+        r#"
+        INSERT INTO login_throttles (throttle_key, failed_count, last_failed_at)
+        VALUES ($1, 1, $2)
+        ON CONFLICT (throttle_key) DO UPDATE SET
+            failed_count = CASE WHEN login_throttles.last_failed_at < $3 THEN 1 ELSE login_throttles.failed_count + 1 END,
+            last_failed_at = EXCLUDED.last_failed_at
+        RETURNING failed_count
+        "#
+    )
+    .bind(key.as_key())
+    .bind(now)
+    .bind(window_start)
+    .fetch_one(pool)
+    .await?;
+
+    let max_attempts = key.max_attempts(config);
+    if failed_count < max_attempts {
+        return Ok(());
+    }
+
+    let lockout = lockout_seconds(config, failed_count, max_attempts);
+    sqlx::query("UPDATE login_throttles SET locked_until = $1 WHERE throttle_key = $2")
+        .bind(now + Duration::seconds(lockout))
+        .bind(key.as_key())
+        .execute(pool)
+        .await?;
+
+    let event_type = match key {
+        ThrottleKey::Ip(_) => SecurityEventType::IpLocked,
+        ThrottleKey::User(_) | ThrottleKey::Login(_) => SecurityEventType::AccountLocked,
+    };
+    let details = format!("{} locked for {lockout} seconds after {failed_count} failed attempts", key.as_key());
+    audit::record(pool, event_type, user_id, None, ip_address, &details).await;
+
+    Ok(())
+}
+
+/// Forget failed attempts against `key`, e.g. after a successful login
+pub async fn clear(pool: &PgPool, key: &ThrottleKey) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM login_throttles WHERE throttle_key = $1")
+        .bind(key.as_key())
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Drop counters that have expired, called from the periodic cleanup endpoint
+pub async fn cleanup(pool: &PgPool, config: &Config) -> Result<u64, sqlx::Error> {
+    let window_start = Utc::now() - Duration::seconds(config.login_attempt_window);
+
+    let result = sqlx::query(
+        "DELETE FROM login_throttles WHERE last_failed_at < $1 AND (locked_until IS NULL OR locked_until < CURRENT_TIMESTAMP)"
+    )
+    .bind(window_start)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
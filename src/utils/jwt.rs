@@ -1,38 +1,125 @@
-use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
-use chrono::{Duration, Utc};
-use crate::models::{Claims, User};
+use jsonwebtoken::{decode, decode_header, encode, DecodingKey, Header, Validation};
+use jsonwebtoken::errors::{Error, ErrorKind};
+use serde::{de::DeserializeOwned, Serialize};
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+use crate::config::Config;
+use crate::keys::KeyStore;
+use crate::models::{Actor, Claims, TwoFactorChallengeClaims, User};
 
-pub fn generate_jwt(user: &User, secret: &str, expiration_seconds: i64) -> Result<String, jsonwebtoken::errors::Error> {
-    let now = Utc::now();
-    let exp = (now + Duration::seconds(expiration_seconds)).timestamp() as usize;
-    let iat = now.timestamp() as usize;
+const TWO_FACTOR_PURPOSE: &str = "2fa_challenge";
+
+/// Sign `claims` with the current signing key, naming it in the `kid` header
+fn sign<T: Serialize>(claims: &T, keys: &KeyStore) -> Result<String, Error> {
+    let key = keys.signing_key().ok_or_else(|| Error::from(ErrorKind::InvalidKeyFormat))?;
+
+    let mut header = Header::new(key.algorithm);
+    header.kid = Some(key.kid.clone());
+
+    encode(&header, claims, key.encoding_key())
+}
+
+/// Verify a token against the key named in its header, along with `iss` and `aud`
+fn verify<T: DeserializeOwned>(token: &str, keys: &KeyStore, config: &Config) -> Result<T, Error> {
+    let header = decode_header(token)?;
+    let key = header.kid
+        .as_deref()
+        .and_then(|kid| keys.verification_key(kid))
+        .ok_or_else(|| Error::from(ErrorKind::InvalidSignature))?;
 
-    let claims = Claims {
+    // Only the algorithm the key was made for, never the one the header asks for
+    let mut validation = Validation::new(key.algorithm);
+    validation.set_issuer(&[&config.jwt_issuer]);
+    validation.set_audience(&[&config.jwt_audience]);
+
+    let decoding_key: &DecodingKey = key.decoding_key();
+    Ok(decode::<T>(token, decoding_key, &validation)?.claims)
+}
+
+fn access_claims(
+    user: &User,
+    permissions_version: i32,
+    session_id: Uuid,
+    expires_at: DateTime<Utc>,
+    act: Option<Actor>,
+    config: &Config,
+) -> Claims {
+    Claims {
         sub: user.id.to_string(),
         username: user.username.clone(),
         email: user.email.clone(),
         role: user.role.clone(),
-        exp,
-        iat,
+        sid: session_id.to_string(),
+        jti: Uuid::new_v4().to_string(),
+        iss: config.jwt_issuer.clone(),
+        aud: config.jwt_audience.clone(),
+        exp: expires_at.timestamp() as usize,
+        iat: Utc::now().timestamp() as usize,
+        pv: permissions_version,
+        act,
+    }
+}
+
+/// `permissions_version` is the current version of the user's role, see `permissions::version`
+pub fn generate_jwt(
+    user: &User,
+    permissions_version: i32,
+    session_id: Uuid,
+    keys: &KeyStore,
+    config: &Config,
+) -> Result<String, Error> {
+    let expires_at = Utc::now() + Duration::seconds(config.jwt_expiration);
+    sign(&access_claims(user, permissions_version, session_id, expires_at, None, config), keys)
+}
+
+/// Access token for `actor` acting as `user`, bound to an impersonation session
+pub fn generate_impersonation_jwt(
+    user: &User,
+    permissions_version: i32,
+    actor: Actor,
+    session_id: Uuid,
+    expires_at: DateTime<Utc>,
+    keys: &KeyStore,
+    config: &Config,
+) -> Result<String, Error> {
+    sign(&access_claims(user, permissions_version, session_id, expires_at, Some(actor), config), keys)
+}
+
+pub fn decode_jwt(token: &str, keys: &KeyStore, config: &Config) -> Result<Claims, Error> {
+    verify(token, keys, config)
+}
+
+/// Short-lived token proving the password step of login succeeded
+pub fn generate_challenge_token(
+    user_id: i32,
+    device_label: Option<String>,
+    keys: &KeyStore,
+    config: &Config,
+) -> Result<String, Error> {
+    let now = Utc::now();
+    let claims = TwoFactorChallengeClaims {
+        sub: user_id.to_string(),
+        purpose: TWO_FACTOR_PURPOSE.to_string(),
+        device_label,
+        jti: Uuid::new_v4().to_string(),
+        iss: config.jwt_issuer.clone(),
+        aud: config.jwt_audience.clone(),
+        exp: (now + Duration::seconds(config.two_factor_challenge_expiration)).timestamp() as usize,
+        iat: now.timestamp() as usize,
     };
 
-    encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(secret.as_ref()),
-    )
+    sign(&claims, keys)
 }
 
-#[allow(dead_code)]
-pub fn decode_jwt(token: &str, secret: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
-    let validation = Validation::new(Algorithm::HS256);
-    let token_data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(secret.as_ref()),
-        &validation,
-    )?;
-
-    Ok(token_data.claims)
+pub fn decode_challenge_token(token: &str, keys: &KeyStore, config: &Config) -> Result<TwoFactorChallengeClaims, Error> {
+    let claims: TwoFactorChallengeClaims = verify(token, keys, config)?;
+
+    // An access token must never be usable as a challenge, or the other way round
+    if claims.purpose != TWO_FACTOR_PURPOSE {
+        return Err(ErrorKind::InvalidToken.into());
+    }
+
+    Ok(claims)
 }
 
 #[allow(dead_code)]
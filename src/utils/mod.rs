@@ -1,6 +1,9 @@
 pub mod jwt;
 pub mod password;
 pub mod performance;
+pub mod token;
+pub mod totp;
 
 pub use jwt::*;
-pub use password::*; 
\ No newline at end of file
+pub use password::*;
+pub use token::*;
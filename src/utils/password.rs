@@ -1,9 +1,219 @@
-use bcrypt::{hash, verify, DEFAULT_COST};
+use std::sync::Arc;
 
-pub fn hash_password(password: &str) -> Result<String, bcrypt::BcryptError> {
-    hash(password, DEFAULT_COST)
+use argon2::password_hash::{self, PasswordHash, PasswordHasher as _, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand::RngCore;
+
+use crate::config::Config;
+
+/// A password hashing scheme, recognised by the identifier at the start of its hashes
+pub trait PasswordScheme: Send + Sync {
+    /// Whether `hash` was produced by this scheme
+    fn recognizes(&self, hash: &str) -> bool;
+
+    fn verify(&self, password: &str, hash: &str) -> anyhow::Result<bool>;
+
+    /// Whether `hash` is as strong as the hashes this service creates today
+    fn is_current(&self, hash: &str) -> bool;
+}
+
+/// Argon2id, storing its version and parameters in the PHC string, e.g.
+/// `$argon2id$v=19$m=19456,t=2,p=1$<salt>$<hash>`
+pub struct Argon2idScheme {
+    params: Params,
+}
+
+impl Argon2idScheme {
+    pub fn new(memory_kib: u32, iterations: u32, parallelism: u32) -> anyhow::Result<Self> {
+        let params = Params::new(memory_kib, iterations, parallelism, None)
+            .map_err(|e| anyhow::anyhow!("Invalid Argon2 parameters: {e}"))?;
+        Ok(Self { params })
+    }
+
+    fn hasher(&self) -> Argon2<'static> {
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, self.params.clone())
+    }
+
+    pub fn hash(&self, password: &str) -> anyhow::Result<String> {
+        let mut salt = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let salt = SaltString::encode_b64(&salt)?;
+
+        Ok(self.hasher().hash_password(password.as_bytes(), &salt)?.to_string())
+    }
 }
 
-pub fn verify_password(password: &str, hash: &str) -> Result<bool, bcrypt::BcryptError> {
-    verify(password, hash)
-} 
\ No newline at end of file
+impl PasswordScheme for Argon2idScheme {
+    fn recognizes(&self, hash: &str) -> bool {
+        hash.starts_with("$argon2")
+    }
+
+    fn verify(&self, password: &str, hash: &str) -> anyhow::Result<bool> {
+        // Parameters come from the stored hash, so older hashes keep verifying
+        let parsed = PasswordHash::new(hash)?;
+        match Argon2::default().verify_password(password.as_bytes(), &parsed) {
+            Ok(()) => Ok(true),
+            Err(password_hash::Error::Password) => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn is_current(&self, hash: &str) -> bool {
+        let Ok(parsed) = PasswordHash::new(hash) else {
+            return false;
+        };
+        let Ok(params) = Params::try_from(&parsed) else {
+            return false;
+        };
+
+        parsed.algorithm == Algorithm::Argon2id.ident()
+            && parsed.version == Some(Version::V0x13.into())
+            && params.m_cost() >= self.params.m_cost()
+            && params.t_cost() >= self.params.t_cost()
+            && params.p_cost() >= self.params.p_cost()
+    }
+}
+
+/// bcrypt hashes from before the switch to Argon2id: verified, never created
+pub struct BcryptScheme;
+
+impl PasswordScheme for BcryptScheme {
+    fn recognizes(&self, hash: &str) -> bool {
+        ["$2a$", "$2b$", "$2x$", "$2y$"].iter().any(|prefix| hash.starts_with(prefix))
+    }
+
+    fn verify(&self, password: &str, hash: &str) -> anyhow::Result<bool> {
+        Ok(bcrypt::verify(password, hash)?)
+    }
+
+    fn is_current(&self, _hash: &str) -> bool {
+        false
+    }
+}
+
+/// Hashes new passwords with Argon2id and verifies hashes from any known scheme.
+/// The work runs on the blocking thread pool so it never stalls the async workers.
+#[derive(Clone)]
+pub struct PasswordHasher {
+    current: Arc<Argon2idScheme>,
+    legacy: Arc<Vec<Box<dyn PasswordScheme>>>,
+}
+
+impl PasswordHasher {
+    pub fn from_config(config: &Config) -> anyhow::Result<Self> {
+        let current = Argon2idScheme::new(
+            config.argon2_memory_kib,
+            config.argon2_iterations,
+            config.argon2_parallelism,
+        )?;
+
+        Ok(Self {
+            current: Arc::new(current),
+            legacy: Arc::new(vec![Box::new(BcryptScheme)]),
+        })
+    }
+
+    pub async fn hash(&self, password: &str) -> anyhow::Result<String> {
+        let current = self.current.clone();
+        let password = password.to_string();
+
+        actix_web::web::block(move || current.hash(&password))
+            .await
+            .map_err(|e| anyhow::anyhow!("Password hashing task failed: {e}"))?
+    }
+
+    pub async fn verify(&self, password: &str, hash: &str) -> anyhow::Result<bool> {
+        let hasher = self.clone();
+        let password = password.to_string();
+        let hash = hash.to_string();
+
+        actix_web::web::block(move || match hasher.scheme_for(&hash) {
+            Some(scheme) => scheme.verify(&password, &hash),
+            None => anyhow::bail!("Unrecognised password hash format"),
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Password verification task failed: {e}"))?
+    }
+
+    /// Whether `hash` should be replaced with a fresh Argon2id hash after the next successful login
+    pub fn needs_rehash(&self, hash: &str) -> bool {
+        !self.current.is_current(hash)
+    }
+
+    fn scheme_for(&self, hash: &str) -> Option<&dyn PasswordScheme> {
+        if self.current.recognizes(hash) {
+            return Some(self.current.as_ref());
+        }
+        self.legacy.iter().map(Box::as_ref).find(|scheme| scheme.recognizes(hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Small parameters keep the tests fast; they are what this service "uses today"
+    fn hasher() -> PasswordHasher {
+        PasswordHasher {
+            current: Arc::new(Argon2idScheme::new(1024, 2, 1).unwrap()),
+            legacy: Arc::new(vec![Box::new(BcryptScheme)]),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn current_hashes_verify_and_need_no_rehash() {
+        let hasher = hasher();
+        let hash = hasher.hash("correct horse").await.unwrap();
+
+        assert!(hash.starts_with("$argon2id$v=19$m=1024,t=2,p=1$"));
+        assert!(hasher.verify("correct horse", &hash).await.unwrap());
+        assert!(!hasher.verify("wrong horse", &hash).await.unwrap());
+        assert!(!hasher.needs_rehash(&hash));
+    }
+
+    #[actix_rt::test]
+    async fn weaker_argon2_parameters_need_a_rehash() {
+        let hasher = hasher();
+        for (memory_kib, iterations, parallelism) in [(512, 2, 1), (1024, 1, 1)] {
+            let weaker = Argon2idScheme::new(memory_kib, iterations, parallelism).unwrap();
+            let hash = weaker.hash("correct horse").unwrap();
+
+            assert!(hasher.verify("correct horse", &hash).await.unwrap());
+            assert!(hasher.needs_rehash(&hash), "m={memory_kib} t={iterations} p={parallelism}");
+        }
+
+        // Stronger parameters than configured are left alone
+        let stronger = Argon2idScheme::new(2048, 3, 2).unwrap().hash("correct horse").unwrap();
+        assert!(!hasher.needs_rehash(&stronger));
+
+        // Other Argon2 variants are replaced with Argon2id
+        let salt = SaltString::encode_b64(&[7u8; 16]).unwrap();
+        let argon2i = Argon2::new(Algorithm::Argon2i, Version::V0x13, Params::new(1024, 2, 1, None).unwrap())
+            .hash_password(b"correct horse", &salt)
+            .unwrap()
+            .to_string();
+        assert!(hasher.verify("correct horse", &argon2i).await.unwrap());
+        assert!(hasher.needs_rehash(&argon2i));
+    }
+
+    #[actix_rt::test]
+    async fn bcrypt_hashes_verify_and_always_need_a_rehash() {
+        let hasher = hasher();
+        let hash = bcrypt::hash("correct horse", 4).unwrap();
+
+        assert!(hash.starts_with("$2b$"));
+        assert!(hasher.verify("correct horse", &hash).await.unwrap());
+        assert!(!hasher.verify("wrong horse", &hash).await.unwrap());
+        assert!(hasher.needs_rehash(&hash));
+    }
+
+    #[actix_rt::test]
+    async fn unknown_hash_formats_are_errors() {
+        let hasher = hasher();
+        for hash in ["$1$salt$md5crypt", "5f4dcc3b5aa765d61d8327deb882cf99", ""] {
+            assert!(hasher.scheme_for(hash).is_none());
+            assert!(hasher.verify("password", hash).await.is_err());
+            assert!(hasher.needs_rehash(hash));
+        }
+    }
+}
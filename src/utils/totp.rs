@@ -0,0 +1,130 @@
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha1::Sha1;
+
+// RFC 6238 defaults understood by every authenticator app
+const PERIOD: i64 = 30;
+const DIGITS: u32 = 6;
+// Accept one step of clock drift either way
+const ALLOWED_DRIFT: i64 = 1;
+
+/// Generate a new 160-bit TOTP secret, base32 encoded
+pub fn generate_secret() -> String {
+    let mut bytes = [0u8; 20];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    BASE32_NOPAD.encode(&bytes)
+}
+
+/// `otpauth://` URI that authenticator apps import, usually rendered as a QR code
+pub fn provisioning_uri(secret: &str, account: &str, issuer: &str) -> String {
+    let mut uri = url::Url::parse("otpauth://totp/").expect("static URI is valid");
+    uri.set_path(&format!("{issuer}:{account}"));
+    uri.query_pairs_mut()
+        .append_pair("secret", secret)
+        .append_pair("issuer", issuer)
+        .append_pair("algorithm", "SHA1")
+        .append_pair("digits", &DIGITS.to_string())
+        .append_pair("period", &PERIOD.to_string());
+    uri.to_string()
+}
+
+fn code_at(key: &[u8], step: i64) -> Option<u32> {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).ok()?;
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    // Dynamic truncation (RFC 4226 section 5.3)
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        digest[offset] & 0x7f,
+        digest[offset + 1],
+        digest[offset + 2],
+        digest[offset + 3],
+    ]);
+    Some(binary % 10u32.pow(DIGITS))
+}
+
+/// Check `code` against `secret` at unix time `now`.
+///
+/// Returns the matching time step so callers can store it and refuse to
+/// accept the same code twice. Steps at or before `last_used_step` never match.
+pub fn verify(secret: &str, code: &str, now: i64, last_used_step: Option<i64>) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+    let key = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+
+    let current_step = now / PERIOD;
+    (current_step - ALLOWED_DRIFT..=current_step + ALLOWED_DRIFT)
+        .filter(|step| last_used_step.is_none_or(|last| *step > last))
+        .find(|step| code_at(&key, *step) == Some(code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The RFC 4226 / RFC 6238 SHA-1 test key "12345678901234567890", base32 encoded
+    const SECRET: &str = "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ";
+
+    fn key() -> Vec<u8> {
+        BASE32_NOPAD.decode(SECRET.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn hotp_matches_rfc_4226_vectors() {
+        let expected = [755224, 287082, 359152, 969429, 338314, 254676, 287922, 162583, 399871, 520489];
+        for (counter, code) in expected.iter().enumerate() {
+            assert_eq!(code_at(&key(), counter as i64), Some(*code), "counter {counter}");
+        }
+    }
+
+    #[test]
+    fn totp_matches_rfc_6238_vectors() {
+        // The RFC lists 8-digit codes; 6-digit codes are their last six digits
+        let vectors = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+            (20000000000, "353130"),
+        ];
+        for (time, code) in vectors {
+            assert_eq!(verify(SECRET, code, time, None), Some(time / PERIOD), "time {time}");
+        }
+    }
+
+    #[test]
+    fn accepts_one_step_of_drift_either_way() {
+        let step = 1234567890 / PERIOD;
+        let code = format!("{:06}", code_at(&key(), step).unwrap());
+
+        assert_eq!(verify(SECRET, &code, (step - 1) * PERIOD, None), Some(step));
+        assert_eq!(verify(SECRET, &code, (step + 1) * PERIOD + PERIOD - 1, None), Some(step));
+        assert_eq!(verify(SECRET, &code, (step - 2) * PERIOD, None), None);
+        assert_eq!(verify(SECRET, &code, (step + 2) * PERIOD, None), None);
+    }
+
+    #[test]
+    fn rejects_steps_already_used() {
+        let step = 1234567890 / PERIOD;
+        let code = format!("{:06}", code_at(&key(), step).unwrap());
+        let now = step * PERIOD;
+
+        assert_eq!(verify(SECRET, &code, now, Some(step - 1)), Some(step));
+        assert_eq!(verify(SECRET, &code, now, Some(step)), None);
+        assert_eq!(verify(SECRET, &code, now, Some(step + 1)), None);
+    }
+
+    #[test]
+    fn rejects_malformed_codes() {
+        assert_eq!(verify(SECRET, "28708", 59, None), None);
+        assert_eq!(verify(SECRET, "2870821", 59, None), None);
+        assert_eq!(verify(SECRET, "28708a", 59, None), None);
+        assert_eq!(verify("not base32!", "287082", 59, None), None);
+    }
+}